@@ -1,22 +1,126 @@
 // Tauri commands for frontend communication
 
-use std::sync::Mutex;
-use tauri::State;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
 
-use crate::usi::MockEngine;
+use crate::engine_options::{engine_key, EngineOptionStore, SavedOption, ENGINE_OPTIONS_FILE};
+use crate::game::{EndReason, GameInfo, GameRules, GameSession, GameSnapshot, TimeControl};
+use crate::kifu::{format_kifu, parse_kifu, read_kifu, write_kifu, Kifu, KifuFormat, TextEncoding};
+use crate::search::{
+    TsumeLimits, TsumeOutcome, TsumeSolution, TsumeSolver, DEFAULT_TSUME_NODES, DEFAULT_TSUME_PLIES,
+};
+use crate::shogi::{DeclarationRule, Player, Position};
+use crate::usi::{
+    position_after_moves, spawn_search, BestMove, EngineError, EngineInfo, GoLimits, MateLimit,
+    MockEngine, MockStrategy, NativeEngine, RestartPolicy, SearchError, SearchHandle,
+    SearchOutcome, SearchRequest, SharedEngine, ShogiEngine, ShutdownOutcome, ThinkingInfo,
+    TsumeResult, UsiEngine, UsiError, DEFAULT_QUIT_TIMEOUT,
+};
+
+/// Event carrying the engine's thinking info while it searches
+pub const ENGINE_INFO_EVENT: &str = "engine-info";
+
+/// Event carrying the `SearchOutcome` when a background search ends
+pub const ENGINE_BESTMOVE_EVENT: &str = "engine-bestmove";
+
+/// Event carrying the `GameSnapshot` after every change to the game
+pub const GAME_STATE_EVENT: &str = "game-state";
+
+/// How long `stop_search` waits for the engine's bestmove
+const STOP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Payload of `ENGINE_INFO_EVENT`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineInfoEvent {
+    /// Identifies the search the info belongs to; increases with every search
+    pub search_id: u64,
+    pub info: ThinkingInfo,
+}
+
+/// Engine implementation selectable from the frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EngineBackend {
+    /// External USI engine process (requires `engine_path`)
+    Usi,
+    /// Built-in alpha-beta search
+    Native,
+    /// Mock engine playing legal moves by a simple strategy
+    Mock,
+}
+
+/// The engine's part in the session's game; the position and moves always
+/// come from `SessionState`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EngineGame {
+    /// "usinewgame" has been sent for the current game
+    pub started: bool,
+    /// Let the engine think on its expected reply while the opponent is to move;
+    /// kept from game to game
+    pub ponder: bool,
+}
 
 /// Global engine state
-/// Using MockEngine for now, can be switched to UsiEngine when real engine is available
 pub struct EngineState {
-    pub engine: Mutex<Option<MockEngine>>,
+    /// Shared with the background search thread, which holds it while searching
+    pub engine: SharedEngine,
+    /// Identity and options reported by the current engine's handshake
+    pub info: Mutex<Option<EngineInfo>>,
+    /// Key under which the current engine's options are saved
+    pub engine_key: Mutex<Option<String>>,
+    /// How the engine takes part in the session's game
+    pub game: Mutex<EngineGame>,
+    /// Most recent background search (kept after it ends so it can be awaited)
+    pub search: Mutex<Option<SearchHandle>>,
+    next_search_id: AtomicU64,
 }
 
 impl EngineState {
     pub fn new() -> Self {
         EngineState {
-            engine: Mutex::new(None),
+            engine: Arc::new(Mutex::new(None)),
+            info: Mutex::new(None),
+            engine_key: Mutex::new(None),
+            game: Mutex::new(EngineGame::default()),
+            search: Mutex::new(None),
+            next_search_id: AtomicU64::new(1),
         }
     }
+
+    /// Allocate the id of a new search
+    pub fn next_search_id(&self) -> u64 {
+        self.next_search_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Take the engine out, forgetting its identity and its part in the game
+    fn take_engine(&self) -> Result<Option<Box<dyn ShogiEngine>>, EngineError> {
+        let engine = self.engine.lock().map_err(|e| e.to_string())?.take();
+        *self.info.lock().map_err(|e| e.to_string())? = None;
+        *self.engine_key.lock().map_err(|e| e.to_string())? = None;
+        // The next engine has not heard of the game and does not ponder
+        *self.game.lock().map_err(|e| e.to_string())? = EngineGame::default();
+        Ok(engine)
+    }
+
+    /// Stop the running search, if any, and wait for it to release the engine
+    /// The wait runs on a blocking thread so the async runtime stays responsive
+    pub async fn stop_search(&self) -> Result<Option<SearchOutcome>, EngineError> {
+        let handle = self.search.lock().map_err(|e| e.to_string())?.clone();
+        let Some(handle) = handle else {
+            return Ok(None);
+        };
+        let outcome =
+            tauri::async_runtime::spawn_blocking(move || handle.stop_and_wait(STOP_TIMEOUT))
+                .await
+                .map_err(|e| e.to_string())?;
+        // An engine that ignores "stop" still holds the engine slot
+        outcome.map(Some).ok_or(UsiError::SearchTimeout.into())
+    }
 }
 
 impl Default for EngineState {
@@ -25,61 +129,737 @@ impl Default for EngineState {
     }
 }
 
-/// Initialize the engine
-/// For mock engine, we don't need a path, but keeping the signature for compatibility
+/// Create the engine for the requested backend
+/// Without an explicit backend, a USI process is used when `engine_path` is given
+/// and the mock engine otherwise; `max_restarts` only applies to USI processes
+fn create_engine(
+    backend: Option<EngineBackend>,
+    engine_path: Option<&str>,
+    mock_strategy: Option<MockStrategy>,
+    max_restarts: Option<u32>,
+) -> Result<Box<dyn ShogiEngine>, UsiError> {
+    let backend = backend.unwrap_or(match engine_path {
+        Some(_) => EngineBackend::Usi,
+        None => EngineBackend::Mock,
+    });
+
+    match backend {
+        EngineBackend::Usi => {
+            let path = engine_path.ok_or("Engine path is required for a USI engine")?;
+            let mut engine = UsiEngine::new();
+            engine.set_restart_policy(
+                max_restarts.map(|max_restarts| RestartPolicy { max_restarts }),
+            );
+            engine.start(path)?;
+            Ok(Box::new(engine))
+        }
+        EngineBackend::Native => Ok(Box::new(NativeEngine::new())),
+        EngineBackend::Mock => Ok(Box::new(match mock_strategy {
+            Some(strategy) => MockEngine::with_strategy(strategy),
+            None => MockEngine::new(),
+        })),
+    }
+}
+
+/// Location of the saved engine options
+fn engine_options_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+    Ok(dir.join(ENGINE_OPTIONS_FILE))
+}
+
+/// Send saved option values to a freshly initialized engine
+/// Values the engine no longer accepts are skipped; returns how many were applied
+fn apply_saved_options(engine: &mut dyn ShogiEngine, saved: &[SavedOption]) -> usize {
+    saved
+        .iter()
+        .filter(|opt| engine.set_option(&opt.name, Some(&opt.value)).is_ok())
+        .count()
+}
+
+/// Initialize the engine and re-apply the options saved for it
+/// `backend` selects the implementation; `mock_strategy` selects how the mock
+/// engine picks its moves (random by default); `max_restarts` lets a crashed
+/// USI engine be restarted mid-game with its options and position replayed
 #[tauri::command]
-pub fn init_engine(state: State<EngineState>, _engine_path: Option<String>) -> Result<String, String> {
-    let mut engine_lock = state.engine.lock().map_err(|e| e.to_string())?;
+pub async fn init_engine(
+    app: AppHandle,
+    state: State<'_, EngineState>,
+    engine_path: Option<String>,
+    backend: Option<EngineBackend>,
+    mock_strategy: Option<MockStrategy>,
+    max_restarts: Option<u32>,
+) -> Result<String, EngineError> {
+    state.stop_search().await?;
+
+    // Dropping the previous engine shuts it down in the background
+    drop(state.take_engine()?);
 
-    // Create and initialize mock engine
-    let mut engine = MockEngine::new();
-    engine.init()?;
+    // A missing or unreadable store must not prevent the engine from starting
+    let store = engine_options_path(&app)
+        .and_then(|path| EngineOptionStore::load(&path))
+        .unwrap_or_default();
 
-    *engine_lock = Some(engine);
+    // The handshake can take seconds, so it runs on a blocking thread and the
+    // engine is only stored once it is ready
+    let (engine, info, key) = tauri::async_runtime::spawn_blocking(move || {
+        let mut engine =
+            create_engine(backend, engine_path.as_deref(), mock_strategy, max_restarts)?;
+        let info = engine.init()?;
+        let key = engine_key(engine_path.as_deref(), &info);
+        if apply_saved_options(engine.as_mut(), store.options(&key)) > 0 {
+            engine.wait_ready()?;
+        }
+        Ok::<_, EngineError>((engine, info, key))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    *state.engine.lock().map_err(|e| e.to_string())? = Some(engine);
+    *state.info.lock().map_err(|e| e.to_string())? = Some(info);
+    *state.engine_key.lock().map_err(|e| e.to_string())? = Some(key);
 
     Ok("Engine initialized successfully".to_string())
 }
 
 /// Get AI move for a given position
+/// Runs as a background search; thinking info is emitted as `ENGINE_INFO_EVENT`
+/// The answer is a legal move, a resignation or a win declaration that meets
+/// the 27-point rule; anything else is reported as `SearchError::IllegalMove`
 #[tauri::command]
-pub fn get_ai_move(
-    state: State<EngineState>,
+pub async fn get_ai_move(
+    app: AppHandle,
+    state: State<'_, EngineState>,
     sfen: String,
     time_ms: u32,
-) -> Result<String, String> {
+) -> Result<BestMove, SearchError> {
+    // Reject malformed positions before they reach the engine
+    Position::from_sfen(&sfen).map_err(|e| e.to_string())?;
+
+    let request = SearchRequest {
+        sfen,
+        moves: Vec::new(),
+        limits: GoLimits::byoyomi(time_ms),
+        new_game: false,
+        declaration_rule: DeclarationRule::default(),
+    };
+    let handle = begin_search(app, &state, request).await?;
+    wait_for(handle).await?.into_result()
+}
+
+/// Start searching the game's current position and return the search id at once
+/// The result arrives as `ENGINE_BESTMOVE_EVENT` or through `wait_search`; it is
+/// not played, as it is by `play_engine_move`
+#[tauri::command]
+pub async fn start_search(
+    app: AppHandle,
+    engine_state: State<'_, EngineState>,
+    session_state: State<'_, SessionState>,
+    time_ms: u32,
+) -> Result<u64, EngineError> {
+    let request = {
+        let session = session_state.session.lock().map_err(|e| e.to_string())?;
+        game_search_request(&engine_state, &session, time_ms)?
+    };
+    Ok(begin_search(app, &engine_state, request).await?.id())
+}
+
+/// Wait for the search with the given id to finish
+#[tauri::command]
+pub async fn wait_search(
+    state: State<'_, EngineState>,
+    search_id: u64,
+) -> Result<SearchOutcome, EngineError> {
+    let handle = state
+        .search
+        .lock()
+        .map_err(|e| e.to_string())?
+        .clone()
+        .filter(|handle| handle.id() == search_id)
+        .ok_or_else(|| format!("No search with id {}", search_id))?;
+    Ok(wait_for(handle).await?)
+}
+
+/// Send "stop" to the running search and return the bestmove it produced
+/// Returns None when no search has been started
+#[tauri::command]
+pub async fn stop_search(
+    state: State<'_, EngineState>,
+) -> Result<Option<SearchOutcome>, EngineError> {
+    state.stop_search().await
+}
+
+/// Build the request for the engine to play the session's side to move
+/// The engine's first search in a game sends "usinewgame"
+fn game_search_request(
+    state: &EngineState,
+    session: &GameSession,
+    time_ms: u32,
+) -> Result<SearchRequest, String> {
+    let mut game = state.game.lock().map_err(|e| e.to_string())?;
+    let new_game = !game.started;
+    game.started = true;
+    Ok(SearchRequest {
+        sfen: session.initial_sfen().to_string(),
+        moves: session.usi_moves(),
+        limits: session.engine_limits(time_ms),
+        new_game,
+        declaration_rule: session.rules().declaration,
+    })
+}
+
+/// Let the engine think on its expected reply after each of its moves, in this
+/// game and the following ones
+#[tauri::command]
+pub async fn set_engine_ponder(
+    state: State<'_, EngineState>,
+    ponder: bool,
+) -> Result<(), EngineError> {
+    state.stop_search().await?;
+    set_ponder(&state, ponder)?;
+    state.game.lock().map_err(|e| e.to_string())?.ponder = ponder;
+    Ok(())
+}
+
+/// Tell the engine whether it may ponder
+/// Without an engine there is nothing to tell, unless pondering is asked for
+fn set_ponder(state: &EngineState, ponder: bool) -> Result<(), EngineError> {
     let mut engine_lock = state.engine.lock().map_err(|e| e.to_string())?;
+    let Some(engine) = engine_lock.as_mut() else {
+        return match ponder {
+            true => Err(UsiError::NotStarted.into()),
+            false => Ok(()),
+        };
+    };
+    if engine.can_ponder() {
+        // Lets the engine plan its time knowing it may ponder
+        let value = if ponder { "true" } else { "false" };
+        engine.set_option("USI_Ponder", Some(value))?;
+        engine.wait_ready()?;
+    } else if ponder {
+        return Err(UsiError::from("Engine does not support pondering").into());
+    }
+    Ok(())
+}
 
-    if let Some(engine) = engine_lock.as_mut() {
-        engine.get_best_move(&sfen, time_ms)
-    } else {
-        Err("Engine not initialized".to_string())
+/// Send "ponderhit" to the running ponder search if it thinks on exactly the
+/// position of `request`, and return it as the search for that position
+/// A ponder search on any other position is left for `begin_search` to stop
+fn ponder_hit(
+    state: &EngineState,
+    request: &SearchRequest,
+) -> Result<Option<SearchHandle>, EngineError> {
+    let handle = state.search.lock().map_err(|e| e.to_string())?.clone();
+    match handle {
+        Some(handle) if !request.new_game && handle.ponders_on(&request.sfen, &request.moves) => {
+            handle.ponderhit()?;
+            Ok(Some(handle))
+        }
+        _ => Ok(None),
     }
 }
 
-/// Shutdown the engine
+/// After the engine moved, let it think on the reply it expects
+/// Does nothing unless the game allows pondering and the engine named a reply
+async fn start_pondering(
+    app: AppHandle,
+    state: &EngineState,
+    request: &SearchRequest,
+    outcome: &SearchOutcome,
+) -> Result<Option<SearchHandle>, EngineError> {
+    let enabled = state.game.lock().map_err(|e| e.to_string())?.ponder;
+    // "resign" and "win" leave nothing to ponder on
+    let best_move = outcome.best_move.as_ref().and_then(BestMove::as_move);
+    let (Some(best_move), Some(reply)) = (best_move, &outcome.ponder) else {
+        return Ok(None);
+    };
+    if !enabled {
+        return Ok(None);
+    }
+
+    let mut moves = request.moves.clone();
+    moves.extend([best_move.to_string(), reply.clone()]);
+    // An illegal expected reply leaves nothing to ponder on either
+    position_after_moves(&request.sfen, &moves)?;
+
+    let ponder_request = SearchRequest {
+        sfen: request.sfen.clone(),
+        moves,
+        limits: GoLimits {
+            ponder: true,
+            ..request.limits.clone()
+        },
+        new_game: false,
+        declaration_rule: request.declaration_rule,
+    };
+    begin_search(app, state, ponder_request).await.map(Some)
+}
+
+/// Stop any previous search and run `request` in the background,
+/// emitting thinking info and the final outcome as events
+async fn begin_search(
+    app: AppHandle,
+    state: &EngineState,
+    request: SearchRequest,
+) -> Result<SearchHandle, EngineError> {
+    state.stop_search().await?;
+
+    let search_id = state.next_search_id();
+    let info_app = app.clone();
+    let on_info = Box::new(move |info: &ThinkingInfo| {
+        let event = EngineInfoEvent {
+            search_id,
+            info: info.clone(),
+        };
+        let _ = info_app.emit(ENGINE_INFO_EVENT, event);
+    });
+    let on_done = move |outcome: &SearchOutcome| {
+        // A ponder search that was stopped answers a position that never arose
+        if !outcome.pondering {
+            let _ = app.emit(ENGINE_BESTMOVE_EVENT, outcome.clone());
+        }
+    };
+
+    let handle = spawn_search(
+        Arc::clone(&state.engine),
+        search_id,
+        request,
+        Some(on_info),
+        on_done,
+    )?;
+    *state.search.lock().map_err(|e| e.to_string())? = Some(handle.clone());
+    Ok(handle)
+}
+
+/// Wait for a search without blocking the async runtime
+async fn wait_for(handle: SearchHandle) -> Result<SearchOutcome, String> {
+    tauri::async_runtime::spawn_blocking(move || handle.wait())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Solve the mating problem (tsume) at `sfen`, searching for up to `time_ms`
+/// Any running search is stopped first; the answer is the mating line, "no mate",
+/// a timeout, or that the engine cannot solve tsume
 #[tauri::command]
-pub fn shutdown_engine(state: State<EngineState>) -> Result<String, String> {
-    let mut engine_lock = state.engine.lock().map_err(|e| e.to_string())?;
+pub async fn solve_tsume(
+    state: State<'_, EngineState>,
+    sfen: String,
+    time_ms: u32,
+) -> Result<TsumeResult, EngineError> {
+    // Reject malformed positions before they reach the engine
+    Position::from_sfen(&sfen).map_err(|e| e.to_string())?;
+    state.stop_search().await?;
 
-    if let Some(mut engine) = engine_lock.take() {
-        engine.quit()?;
-        Ok("Engine shutdown successfully".to_string())
-    } else {
-        Err("Engine not running".to_string())
+    let engine = Arc::clone(&state.engine);
+    tauri::async_runtime::spawn_blocking(move || {
+        run_mate_search(&engine, &sfen, MateLimit::Time(time_ms))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Set up `sfen` on the engine and run "go mate" on it
+fn run_mate_search(
+    engine: &SharedEngine,
+    sfen: &str,
+    limit: MateLimit,
+) -> Result<TsumeResult, EngineError> {
+    let mut engine_lock = engine.lock().map_err(|e| e.to_string())?;
+    let engine = engine_lock.as_mut().ok_or(UsiError::NotStarted)?;
+    engine.set_position(sfen, &[])?;
+    Ok(engine.go_mate(limit)?)
+}
+
+/// Answer of the built-in tsume solver
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TsumeAnswer {
+    /// Shortest mating line in USI notation
+    Mate { moves: Vec<String> },
+    /// There is no forced mate
+    NoMate,
+    /// No mate in `plies` or fewer; a longer one may exist
+    NoMateWithin { plies: u32 },
+    /// The node budget ran out first
+    Unknown,
+}
+
+/// Result of `solve_tsume_native`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TsumeReport {
+    pub answer: TsumeAnswer,
+    /// Positions the solver expanded
+    pub nodes: u64,
+}
+
+impl From<TsumeSolution> for TsumeReport {
+    fn from(solution: TsumeSolution) -> Self {
+        let answer = match solution.outcome {
+            TsumeOutcome::Mate(line) => TsumeAnswer::Mate {
+                moves: line.iter().map(|mv| mv.to_usi()).collect(),
+            },
+            TsumeOutcome::NoMate => TsumeAnswer::NoMate,
+            TsumeOutcome::NoMateWithin(plies) => TsumeAnswer::NoMateWithin { plies },
+            TsumeOutcome::Unknown => TsumeAnswer::Unknown,
+        };
+        TsumeReport {
+            answer,
+            nodes: solution.nodes,
+        }
     }
 }
 
+/// Solve the mating problem at `sfen` with the built-in df-pn solver,
+/// independently of the current engine
+/// `max_plies` asks whether there is a mate in that many plies (31 by default);
+/// `max_nodes` bounds the work (1,000,000 positions by default)
+#[tauri::command]
+pub async fn solve_tsume_native(
+    sfen: String,
+    max_nodes: Option<u64>,
+    max_plies: Option<u32>,
+) -> Result<TsumeReport, String> {
+    let pos = Position::from_sfen(&sfen).map_err(|e| e.to_string())?;
+    let limits = TsumeLimits {
+        max_nodes: max_nodes.unwrap_or(DEFAULT_TSUME_NODES),
+        max_plies: max_plies.unwrap_or(DEFAULT_TSUME_PLIES),
+        time_ms: None,
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        TsumeReport::from(TsumeSolver::new().solve(&pos, limits))
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Shut the engine down, giving it up to `timeout_ms` (5 s by default) to
+/// quit on its own before it is killed
+/// Waits on a blocking thread so the event loop stays responsive
+#[tauri::command]
+pub async fn shutdown_engine(
+    state: State<'_, EngineState>,
+    timeout_ms: Option<u64>,
+) -> Result<ShutdownOutcome, EngineError> {
+    state.stop_search().await?;
+    let mut engine = state.take_engine()?.ok_or(UsiError::NotStarted)?;
+
+    let timeout = timeout_ms.map_or(DEFAULT_QUIT_TIMEOUT, Duration::from_millis);
+    let outcome = tauri::async_runtime::spawn_blocking(move || engine.quit(timeout))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(outcome?)
+}
+
 /// Check if engine is ready
 #[tauri::command]
-pub fn is_engine_ready(state: State<EngineState>) -> Result<bool, String> {
-    let engine_lock = state.engine.lock().map_err(|e| e.to_string())?;
+pub fn is_engine_ready(state: State<EngineState>) -> Result<bool, EngineError> {
+    // A search in progress holds the engine; it is initialized in that case
+    match state.engine.try_lock() {
+        Ok(engine_lock) => Ok(engine_lock.as_ref().is_some_and(|engine| engine.is_ready())),
+        Err(std::sync::TryLockError::WouldBlock) => Ok(true),
+        Err(e) => Err(e.to_string().into()),
+    }
+}
+
+/// Set an engine option and save it for the next time this engine starts
+/// `value` is omitted for button options, which are sent but not saved
+#[tauri::command]
+pub async fn set_engine_option(
+    app: AppHandle,
+    state: State<'_, EngineState>,
+    name: String,
+    value: Option<String>,
+) -> Result<(), EngineError> {
+    state.stop_search().await?;
+    let mut engine_lock = state.engine.lock().map_err(|e| e.to_string())?;
+    let engine = engine_lock.as_mut().ok_or(UsiError::NotStarted)?;
+
+    engine.set_option(&name, value.as_deref())?;
+
+    if let Some(value) = value {
+        let key_lock = state.engine_key.lock().map_err(|e| e.to_string())?;
+        if let Some(key) = key_lock.as_deref() {
+            let path = engine_options_path(&app)?;
+            let mut store = EngineOptionStore::load(&path).unwrap_or_default();
+            store.set(key, &name, value.trim());
+            store.save(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the option values saved for the running engine
+#[tauri::command]
+pub fn get_saved_engine_options(
+    app: AppHandle,
+    state: State<EngineState>,
+) -> Result<Vec<SavedOption>, EngineError> {
+    let key_lock = state.engine_key.lock().map_err(|e| e.to_string())?;
+    let key = key_lock.as_deref().ok_or(UsiError::NotStarted)?;
+
+    let store = EngineOptionStore::load(&engine_options_path(&app)?)?;
+    Ok(store.options(key).to_vec())
+}
+
+/// Get the name, author and option declarations of the running engine
+#[tauri::command]
+pub fn get_engine_info(state: State<EngineState>) -> Result<EngineInfo, EngineError> {
+    let info_lock = state.info.lock().map_err(|e| e.to_string())?;
+
+    Ok(info_lock.clone().ok_or(UsiError::NotStarted)?)
+}
+
+/// The game being played; `new_game` replaces it
+pub struct SessionState {
+    pub session: Mutex<GameSession>,
+}
+
+impl SessionState {
+    pub fn new() -> Self {
+        SessionState {
+            session: Mutex::new(
+                GameSession::new(None, None, GameRules::default(), GameInfo::default())
+                    .expect("the standard position is valid"),
+            ),
+        }
+    }
+}
+
+impl Default for SessionState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Apply `change` to the game and broadcast the new state as `GAME_STATE_EVENT`
+/// Nothing is broadcast when the change is rejected
+fn update_session<E: From<String>>(
+    app: &AppHandle,
+    state: &SessionState,
+    change: impl FnOnce(&mut GameSession) -> Result<(), E>,
+) -> Result<GameSnapshot, E> {
+    let mut session = state.session.lock().map_err(|e| e.to_string())?;
+    change(&mut session)?;
+    let snapshot = session.snapshot();
+    let _ = app.emit(GAME_STATE_EVENT, snapshot.clone());
+    Ok(snapshot)
+}
+
+/// Start a new game from `initial_sfen` (the standard position when omitted)
+/// Any search on the previous game is stopped and the engine gets "usinewgame"
+/// with its next search
+#[tauri::command]
+pub async fn new_game(
+    app: AppHandle,
+    engine_state: State<'_, EngineState>,
+    session_state: State<'_, SessionState>,
+    initial_sfen: Option<String>,
+    time_control: Option<TimeControl>,
+    rules: Option<GameRules>,
+    info: Option<GameInfo>,
+) -> Result<GameSnapshot, EngineError> {
+    let session = GameSession::new(
+        initial_sfen.as_deref(),
+        time_control,
+        rules.unwrap_or_default(),
+        info.unwrap_or_default(),
+    )?;
+
+    engine_state.stop_search().await?;
+    engine_state.game.lock().map_err(|e| e.to_string())?.started = false;
+    update_session(&app, &session_state, |current| {
+        *current = session;
+        Ok(())
+    })
+}
+
+/// Play `usi` for the side to move
+#[tauri::command]
+pub fn make_move(
+    app: AppHandle,
+    state: State<SessionState>,
+    usi: String,
+) -> Result<GameSnapshot, String> {
+    update_session(&app, &state, |session| session.make_move(&usi))
+}
+
+/// Take back the last move
+#[tauri::command]
+pub fn undo_move(app: AppHandle, state: State<SessionState>) -> Result<GameSnapshot, String> {
+    update_session(&app, &state, GameSession::undo)
+}
+
+/// `player` resigns (the side to move when omitted)
+#[tauri::command]
+pub fn resign_game(
+    app: AppHandle,
+    state: State<SessionState>,
+    player: Option<Player>,
+) -> Result<GameSnapshot, String> {
+    update_session(&app, &state, |session| {
+        let player = player.unwrap_or_else(|| session.position().side_to_move());
+        session.resign(player)
+    })
+}
+
+/// The side to move declares by entering king under the game's declaration rule
+/// Fails without ending the game when the position does not meet the rule
+#[tauri::command]
+pub fn declare_win(app: AppHandle, state: State<SessionState>) -> Result<GameSnapshot, String> {
+    update_session(&app, &state, GameSession::declare_win)
+}
+
+/// Get the current state of the game
+#[tauri::command]
+pub fn get_game_state(state: State<SessionState>) -> Result<GameSnapshot, String> {
+    let session = state.session.lock().map_err(|e| e.to_string())?;
+    Ok(session.snapshot())
+}
+
+/// Let the engine play the side to move
+/// Under the game's time control the engine is told both clocks; an untimed
+/// game gives it `time_ms` per move
+/// Its move, resignation or win declaration is applied to the game; an illegal
+/// answer loses the game for the engine
+#[tauri::command]
+pub async fn play_engine_move(
+    app: AppHandle,
+    engine_state: State<'_, EngineState>,
+    session_state: State<'_, SessionState>,
+    time_ms: u32,
+) -> Result<GameSnapshot, EngineError> {
+    let (request, version) = {
+        let session = session_state.session.lock().map_err(|e| e.to_string())?;
+        if session.is_over() {
+            return Err("The game is over".into());
+        }
+        (
+            game_search_request(&engine_state, &session, time_ms)?,
+            session.version(),
+        )
+    };
+    let handle = match ponder_hit(&engine_state, &request)? {
+        Some(handle) => handle,
+        None => begin_search(app.clone(), &engine_state, request.clone()).await?,
+    };
+    let outcome = wait_for(handle).await?;
 
-    Ok(engine_lock.is_some() && engine_lock.as_ref().unwrap().is_ready())
+    let snapshot = update_session(&app, &session_state, |session| {
+        // The answer belongs to a position the game has moved on from
+        if session.version() != version {
+            return Err("The game changed while the engine was thinking".into());
+        }
+        let player = session.position().side_to_move();
+        match outcome.clone().into_result() {
+            Ok(best_move) => Ok(session.apply_best_move(&best_move, outcome.score)?),
+            Err(SearchError::IllegalMove { .. }) => {
+                Ok(session.forfeit(player, EndReason::IllegalMove)?)
+            }
+            Err(SearchError::Failed(error)) => Err(error),
+        }
+    })?;
+
+    // The engine ponders with its clock as it stands after its move
+    let limits = session_state
+        .session
+        .lock()
+        .map_err(|e| e.to_string())?
+        .engine_limits(time_ms);
+    let request = SearchRequest { limits, ..request };
+    // Failing to start pondering must not lose the move
+    let _ = start_pondering(app, &engine_state, &request, &outcome).await;
+    Ok(snapshot)
+}
+
+/// Replace the game with the main line of `kifu`, as `new_game` does
+async fn load_session(
+    app: &AppHandle,
+    engine_state: &EngineState,
+    session_state: &SessionState,
+    kifu: &Kifu,
+    rules: Option<GameRules>,
+) -> Result<(), EngineError> {
+    let session = kifu.to_session(rules.unwrap_or_default())?;
+    engine_state.stop_search().await?;
+    engine_state.game.lock().map_err(|e| e.to_string())?.started = false;
+    update_session(app, session_state, |current| {
+        *current = session;
+        Ok(())
+    })
+    .map(|_| ())
+}
+
+/// Save the game as a KIF or KI2 file
+/// The format follows the extension unless given; the encoding defaults to
+/// UTF-8 for .kifu and .ki2u and to Shift_JIS otherwise
+#[tauri::command]
+pub fn save_kifu(
+    state: State<SessionState>,
+    path: String,
+    format: Option<KifuFormat>,
+    encoding: Option<TextEncoding>,
+) -> Result<(), String> {
+    let format = format
+        .or_else(|| KifuFormat::from_path(&path))
+        .ok_or_else(|| format!("Unknown record format: {}", path))?;
+    let encoding = encoding.unwrap_or_else(|| TextEncoding::from_path(&path));
+    let kifu = {
+        let session = state.session.lock().map_err(|e| e.to_string())?;
+        Kifu::from_session(&session)
+    };
+    let bytes = write_kifu(&kifu, format, encoding)?;
+    std::fs::write(&path, bytes).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+/// Load a KIF or KI2 file, in Shift_JIS or UTF-8, and replay its main line as
+/// the game; returns the whole record with comments and variations
+#[tauri::command]
+pub async fn load_kifu(
+    app: AppHandle,
+    engine_state: State<'_, EngineState>,
+    session_state: State<'_, SessionState>,
+    path: String,
+    rules: Option<GameRules>,
+) -> Result<Kifu, EngineError> {
+    let format =
+        KifuFormat::from_path(&path).ok_or_else(|| format!("Unknown record format: {}", path))?;
+    let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let kifu = read_kifu(&bytes, format)?;
+    load_session(&app, &engine_state, &session_state, &kifu, rules).await?;
+    Ok(kifu)
+}
+
+/// The game as KIF or KI2 text, for the clipboard
+#[tauri::command]
+pub fn export_kifu(state: State<SessionState>, format: KifuFormat) -> Result<String, String> {
+    let session = state.session.lock().map_err(|e| e.to_string())?;
+    format_kifu(&Kifu::from_session(&session), format)
+}
+
+/// Replay KIF or KI2 text, such as from the clipboard, as the game
+#[tauri::command]
+pub async fn import_kifu(
+    app: AppHandle,
+    engine_state: State<'_, EngineState>,
+    session_state: State<'_, SessionState>,
+    text: String,
+    format: KifuFormat,
+    rules: Option<GameRules>,
+) -> Result<Kifu, EngineError> {
+    let kifu = parse_kifu(&text, format)?;
+    load_session(&app, &engine_state, &session_state, &kifu, rules).await?;
+    Ok(kifu)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::usi::ScriptedEngine;
 
     #[test]
     fn test_engine_state_creation() {
@@ -87,4 +867,200 @@ mod tests {
         let engine_lock = state.engine.lock().unwrap();
         assert!(engine_lock.is_none());
     }
+
+    #[test]
+    fn test_session_state_starts_a_standard_game() {
+        let state = SessionState::new();
+        let snapshot = state.session.lock().unwrap().snapshot();
+        assert_eq!(snapshot.sfen, Position::startpos().to_sfen());
+        assert!(snapshot.moves.is_empty());
+        assert!(snapshot.result.is_none());
+    }
+
+    #[test]
+    fn test_create_engine_picks_backend() {
+        let mut mock = create_engine(None, None, None, None).unwrap();
+        mock.init().unwrap();
+        assert!(mock.is_ready());
+
+        let mut native = create_engine(Some(EngineBackend::Native), None, None, None).unwrap();
+        native.init().unwrap();
+        native
+            .set_position(crate::shogi::STARTPOS_SFEN, &[])
+            .unwrap();
+        assert!(native
+            .go(&GoLimits {
+                depth: Some(1),
+                ..Default::default()
+            })
+            .is_ok());
+
+        assert!(create_engine(Some(EngineBackend::Usi), None, None, None).is_err());
+        assert!(create_engine(None, Some("/nonexistent/engine"), None, Some(1)).is_err());
+    }
+
+    #[test]
+    fn test_run_mate_search() {
+        let engine: SharedEngine = Arc::new(Mutex::new(None));
+        let sfen = "4k4/9/4P4/9/9/9/9/9/4K4 b G 1";
+        assert!(run_mate_search(&engine, sfen, MateLimit::Infinite).is_err());
+
+        let mut mock = MockEngine::new();
+        mock.init().unwrap();
+        *engine.lock().unwrap() = Some(Box::new(mock));
+        assert_eq!(
+            run_mate_search(&engine, sfen, MateLimit::Time(1000)).unwrap(),
+            TsumeResult::Mate(vec!["G*5b".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_tsume_report() {
+        let pos = Position::from_sfen("4k4/9/4P4/9/9/9/9/9/9 b G 1").unwrap();
+        let report = TsumeReport::from(TsumeSolver::new().solve(&pos, TsumeLimits::default()));
+        assert_eq!(
+            report.answer,
+            TsumeAnswer::Mate {
+                moves: vec!["G*5b".to_string()]
+            }
+        );
+        assert!(report.nodes > 0);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["answer"]["type"], "mate");
+        assert_eq!(json["answer"]["moves"][0], "G*5b");
+    }
+
+    #[test]
+    fn test_apply_saved_options_skips_rejected_values() {
+        let saved = ["Threads=4", "Removed=1", "USI_Hash=512"].map(|s| {
+            let (name, value) = s.split_once('=').unwrap();
+            SavedOption {
+                name: name.to_string(),
+                value: value.to_string(),
+            }
+        });
+        let mut engine = ScriptedEngine::new().rejecting_option("Removed");
+        assert_eq!(apply_saved_options(&mut engine, &saved), 2);
+        assert_eq!(engine.options(), ["Threads=4", "USI_Hash=512"]);
+    }
+
+    #[test]
+    fn test_game_search_request_follows_the_session() {
+        let state = EngineState::new();
+        let mut session =
+            GameSession::new(None, None, GameRules::default(), GameInfo::default()).unwrap();
+        session.make_move("7g7f").unwrap();
+
+        let first = game_search_request(&state, &session, 100).unwrap();
+        assert!(first.new_game);
+        assert_eq!(first.sfen, Position::startpos().to_sfen());
+        assert_eq!(first.moves, ["7g7f"]);
+        assert_eq!(first.limits, GoLimits::byoyomi(100));
+
+        session.make_move("3c3d").unwrap();
+        let second = game_search_request(&state, &session, 100).unwrap();
+        assert!(!second.new_game);
+        assert_eq!(second.moves, ["7g7f", "3c3d"]);
+
+        // The engine hears "usinewgame" again once a new game begins
+        state.game.lock().unwrap().started = false;
+        assert!(game_search_request(&state, &session, 100).unwrap().new_game);
+    }
+
+    #[test]
+    fn test_set_ponder_needs_an_engine_that_ponders() {
+        let state = EngineState::new();
+        assert!(set_ponder(&state, false).is_ok());
+        assert_eq!(
+            set_ponder(&state, true).unwrap_err().usi_error,
+            Some(UsiError::NotStarted)
+        );
+
+        let mut engine: Box<dyn ShogiEngine> = Box::new(MockEngine::new());
+        engine.init().unwrap();
+        *state.engine.lock().unwrap() = Some(engine);
+        assert!(set_ponder(&state, false).is_ok());
+        assert!(set_ponder(&state, true).is_err());
+
+        *state.engine.lock().unwrap() = Some(Box::new(ScriptedEngine::new().pondering()));
+        assert!(set_ponder(&state, true).is_ok());
+    }
+
+    #[test]
+    fn test_take_engine_forgets_the_engine() {
+        let state = EngineState::new();
+        assert!(state.take_engine().unwrap().is_none());
+
+        *state.engine.lock().unwrap() = Some(Box::new(MockEngine::new()));
+        *state.info.lock().unwrap() = Some(EngineInfo::default());
+        *state.engine_key.lock().unwrap() = Some("mock".to_string());
+        *state.game.lock().unwrap() = EngineGame {
+            started: true,
+            ponder: true,
+        };
+        assert!(state.take_engine().unwrap().is_some());
+        assert!(state.engine.lock().unwrap().is_none());
+        assert!(state.info.lock().unwrap().is_none());
+        assert!(state.engine_key.lock().unwrap().is_none());
+        assert_eq!(*state.game.lock().unwrap(), EngineGame::default());
+    }
+
+    #[test]
+    fn test_stop_search_without_search() {
+        let state = EngineState::new();
+        assert_eq!(
+            tauri::async_runtime::block_on(state.stop_search()),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_search_ids_increase() {
+        let state = EngineState::new();
+        let first = state.next_search_id();
+        assert_eq!(state.next_search_id(), first + 1);
+    }
+
+    #[test]
+    fn test_engine_info_event_payload() {
+        let event = EngineInfoEvent {
+            search_id: 7,
+            info: ThinkingInfo {
+                depth: Some(3),
+                pv: vec!["7g7f".to_string()],
+                ..ThinkingInfo::new()
+            },
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["searchId"], 7);
+        assert_eq!(json["info"]["depth"], 3);
+        assert_eq!(json["info"]["pv"][0], "7g7f");
+    }
+
+    #[test]
+    fn test_invalid_sfen_error_message() {
+        let err = Position::from_sfen("9/9/9 b - 1").unwrap_err().to_string();
+        assert_eq!(err, "Invalid SFEN: board must have 9 ranks, found 3");
+    }
+
+    #[test]
+    fn test_ponder_hit_needs_a_ponder_search_on_the_position() {
+        let state = EngineState::new();
+        let mut session =
+            GameSession::new(None, None, GameRules::default(), GameInfo::default()).unwrap();
+        session.make_move("7g7f").unwrap();
+        let request = game_search_request(&state, &session, 100).unwrap();
+        assert!(ponder_hit(&state, &request).unwrap().is_none());
+
+        // A finished normal search is never continued
+        let mut engine: Box<dyn ShogiEngine> = Box::new(MockEngine::new());
+        engine.init().unwrap();
+        *state.engine.lock().unwrap() = Some(engine);
+        let handle =
+            spawn_search(Arc::clone(&state.engine), 1, request.clone(), None, |_| {}).unwrap();
+        handle.wait();
+        *state.search.lock().unwrap() = Some(handle);
+        assert!(ponder_hit(&state, &request).unwrap().is_none());
+    }
 }
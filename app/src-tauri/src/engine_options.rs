@@ -0,0 +1,130 @@
+// Engine option values saved per engine binary and persisted as JSON
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::usi::EngineInfo;
+
+/// File name of the option store inside the app data directory
+pub const ENGINE_OPTIONS_FILE: &str = "engine_options.json";
+
+/// One saved "setoption" value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedOption {
+    pub name: String,
+    pub value: String,
+}
+
+/// Saved option values of every engine the user has configured
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EngineOptionStore {
+    /// Options per engine key, in the order they were first set
+    engines: BTreeMap<String, Vec<SavedOption>>,
+}
+
+/// Identify an engine by its binary path and the name it reports,
+/// so replacing the binary at the same path does not reuse stale options
+pub fn engine_key(engine_path: Option<&str>, info: &EngineInfo) -> String {
+    format!(
+        "{}#{}",
+        engine_path.unwrap_or(""),
+        info.name.as_deref().unwrap_or("")
+    )
+}
+
+impl EngineOptionStore {
+    /// Load the store; a missing file is an empty store
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read engine options: {}", e))?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid engine options file: {}", e))
+    }
+
+    /// Write the store, creating the parent directory if needed
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| format!("Failed to write engine options: {}", e))
+    }
+
+    /// Saved options of one engine
+    pub fn options(&self, key: &str) -> &[SavedOption] {
+        self.engines
+            .get(key)
+            .map_or(&[], |options| options.as_slice())
+    }
+
+    /// Remember an option value, replacing an earlier value of the same option
+    pub fn set(&mut self, key: &str, name: &str, value: &str) {
+        let options = self.engines.entry(key.to_string()).or_default();
+        match options.iter_mut().find(|opt| opt.name == name) {
+            Some(opt) => opt.value = value.to_string(),
+            None => options.push(SavedOption {
+                name: name.to_string(),
+                value: value.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(name: &str) -> EngineInfo {
+        EngineInfo {
+            name: Some(name.to_string()),
+            ..EngineInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_engine_key_includes_path_and_name() {
+        let a = engine_key(Some("/engines/yane"), &info("YaneuraOu 7.10"));
+        let b = engine_key(Some("/engines/yane"), &info("YaneuraOu 8.00"));
+        assert_ne!(a, b);
+        assert_eq!(a, "/engines/yane#YaneuraOu 7.10");
+    }
+
+    #[test]
+    fn test_set_replaces_and_keeps_order() {
+        let mut store = EngineOptionStore::default();
+        store.set("e", "Threads", "4");
+        store.set("e", "USI_Hash", "1024");
+        store.set("e", "Threads", "8");
+
+        let names: Vec<_> = store.options("e").iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, vec!["Threads", "USI_Hash"]);
+        assert_eq!(store.options("e")[0].value, "8");
+        assert!(store.options("other").is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = std::env::temp_dir().join(format!("engine_options_test_{}", std::process::id()));
+        let path = dir.join(ENGINE_OPTIONS_FILE);
+
+        assert_eq!(
+            EngineOptionStore::load(&path).unwrap(),
+            EngineOptionStore::default()
+        );
+
+        let mut store = EngineOptionStore::default();
+        store.set("/engines/yane#YaneuraOu", "EvalDir", "eval/nnue");
+        store.save(&path).unwrap();
+        assert_eq!(EngineOptionStore::load(&path).unwrap(), store);
+
+        fs::write(&path, "not json").unwrap();
+        assert!(EngineOptionStore::load(&path).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,280 @@
+// Adjudication of games that would otherwise go on forever: a move limit,
+// impasses settled by counting points, and verdicts from the engines' scores
+
+use serde::{Deserialize, Serialize};
+
+use super::session::{EndReason, GameResult, GameRules, MoveRecord};
+use crate::shogi::{material_points, Player, Position, IMPASSE_POINTS};
+use crate::usi::Score;
+
+/// Centipawns a mate score counts as
+const MATE_CP: i32 = 100_000;
+
+/// Adjudicate a loss once both engines agree one side is lost
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResignThreshold {
+    /// Score in centipawns the winner must report at least, and the loser at
+    /// most its negation
+    pub score_cp: i32,
+    /// Consecutive moves of each side the scores must hold for
+    pub moves: u32,
+}
+
+/// Adjudicate a draw once both engines see a level game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DrawThreshold {
+    /// Plies to play before a draw may be adjudicated
+    pub after_ply: u32,
+    /// Largest score in centipawns, either way, that counts as level
+    pub score_cp: i32,
+    /// Consecutive moves of each side the scores must hold for
+    pub moves: u32,
+}
+
+/// Judge the game after its latest move by the adjudication rules
+/// `positions` holds every position of the game, the current one last
+pub fn adjudicate(
+    rules: &GameRules,
+    positions: &[Position],
+    moves: &[MoveRecord],
+) -> Option<GameResult> {
+    if let Some(winner) = rules.resign.and_then(|t| resign_winner(t, moves)) {
+        return Some(GameResult {
+            winner: Some(winner),
+            reason: EndReason::ResignAdjudication,
+        });
+    }
+    if rules.draw.is_some_and(|t| is_level(t, moves)) {
+        return Some(GameResult {
+            winner: None,
+            reason: EndReason::DrawAdjudication,
+        });
+    }
+    if let Some(winner) = rules
+        .impasse_plies
+        .and_then(|plies| settle_impasse(plies, positions))
+    {
+        return Some(GameResult {
+            winner,
+            reason: EndReason::Impasse,
+        });
+    }
+    rules
+        .max_plies
+        .filter(|&max_plies| moves.len() >= max_plies as usize)
+        .map(|_| GameResult {
+            winner: None,
+            reason: EndReason::MaxMoves,
+        })
+}
+
+/// Score of a move in centipawns from sente's point of view
+fn sente_cp(record: &MoveRecord) -> Option<i32> {
+    let cp = match record.score? {
+        Score::Cp(cp) => cp,
+        Score::Mate(plies) if plies > 0 => MATE_CP,
+        Score::Mate(_) => -MATE_CP,
+        Score::MateUnknown(true) => MATE_CP,
+        Score::MateUnknown(false) => -MATE_CP,
+    };
+    Some(match record.player {
+        Player::Sente => cp,
+        Player::Gote => -cp,
+    })
+}
+
+/// Sente's view of the scores of the last `moves` moves of each side, if all
+/// of them were scored
+fn recent_scores(moves: &[MoveRecord], moves_each: u32) -> Option<Vec<i32>> {
+    let count = 2 * moves_each as usize;
+    if count == 0 || moves.len() < count {
+        return None;
+    }
+    moves[moves.len() - count..].iter().map(sente_cp).collect()
+}
+
+fn resign_winner(threshold: ResignThreshold, moves: &[MoveRecord]) -> Option<Player> {
+    let scores = recent_scores(moves, threshold.moves)?;
+    if scores.iter().all(|&cp| cp >= threshold.score_cp) {
+        Some(Player::Sente)
+    } else if scores.iter().all(|&cp| cp <= -threshold.score_cp) {
+        Some(Player::Gote)
+    } else {
+        None
+    }
+}
+
+fn is_level(threshold: DrawThreshold, moves: &[MoveRecord]) -> bool {
+    moves.len() >= threshold.after_ply as usize
+        && recent_scores(moves, threshold.moves).is_some_and(|scores| {
+            scores
+                .iter()
+                .all(|&cp| (-threshold.score_cp..=threshold.score_cp).contains(&cp))
+        })
+}
+
+/// When both kings have stood in their promotion zones for `plies` plies, count
+/// points: a side short of 24 loses, otherwise the game is drawn
+/// Returns the winner, None for a draw, or nothing while the game goes on
+fn settle_impasse(plies: u32, positions: &[Position]) -> Option<Option<Player>> {
+    let count = plies as usize + 1;
+    if positions.len() < count {
+        return None;
+    }
+    let entered = |pos: &Position| {
+        [Player::Sente, Player::Gote].into_iter().all(|player| {
+            pos.king_square(player)
+                .is_some_and(|king| king.in_promotion_zone(player))
+        })
+    };
+    if !positions[positions.len() - count..].iter().all(entered) {
+        return None;
+    }
+
+    let pos = positions.last()?;
+    let short = |player| material_points(pos, player) < IMPASSE_POINTS;
+    Some(match (short(Player::Sente), short(Player::Gote)) {
+        (true, false) => Some(Player::Gote),
+        (false, true) => Some(Player::Sente),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameInfo, GameSession};
+    use std::time::Duration;
+
+    fn session(sfen: Option<&str>, rules: GameRules) -> GameSession {
+        GameSession::new(sfen, None, rules, GameInfo::default()).unwrap()
+    }
+
+    /// Both rooks walk along their ranks without repeating a position
+    const MOVES: [&str; 8] = [
+        "2h3h", "8b7b", "3h4h", "7b6b", "4h5h", "6b5b", "5h6h", "5b4b",
+    ];
+
+    fn session_with_scores(rules: GameRules, scores: &[i32]) -> GameSession {
+        let mut session = session(None, rules);
+        for (usi, &cp) in MOVES.iter().zip(scores) {
+            session
+                .play_scored(usi, Duration::ZERO, Some(Score::Cp(cp)))
+                .unwrap();
+        }
+        session
+    }
+
+    #[test]
+    fn test_max_plies() {
+        let rules = GameRules {
+            max_plies: Some(4),
+            ..GameRules::default()
+        };
+        let mut session = session(None, rules);
+        for usi in &MOVES[..3] {
+            session.play(usi, Duration::ZERO).unwrap();
+        }
+        assert!(!session.is_over());
+        session.play(MOVES[3], Duration::ZERO).unwrap();
+        assert_eq!(
+            session.result(),
+            Some(GameResult {
+                winner: None,
+                reason: EndReason::MaxMoves
+            })
+        );
+    }
+
+    #[test]
+    fn test_resign_threshold() {
+        let rules = GameRules {
+            resign: Some(ResignThreshold {
+                score_cp: 2000,
+                moves: 2,
+            }),
+            ..GameRules::default()
+        };
+        // Sente sees +2500 and gote -2500: both agree sente is winning
+        let session = session_with_scores(rules, &[2500, -2500, 2500]);
+        assert!(!session.is_over());
+        let session = session_with_scores(rules, &[2500, -2500, 2500, -2500]);
+        assert_eq!(
+            session.result(),
+            Some(GameResult {
+                winner: Some(Player::Sente),
+                reason: EndReason::ResignAdjudication
+            })
+        );
+
+        // Gote's engine disagrees, so the game goes on
+        let mut session = session_with_scores(rules, &[2500, 100, 2500, 100]);
+        assert!(!session.is_over());
+        // Human moves carry no score and are never adjudicated
+        session.play(MOVES[4], Duration::ZERO).unwrap();
+        assert!(!session.is_over());
+    }
+
+    #[test]
+    fn test_draw_threshold() {
+        let rules = GameRules {
+            draw: Some(DrawThreshold {
+                after_ply: 6,
+                score_cp: 50,
+                moves: 2,
+            }),
+            ..GameRules::default()
+        };
+        // Level from the start, but not before ply 6
+        let session = session_with_scores(rules, &[10, -20, 0, 30, -10]);
+        assert!(!session.is_over());
+        let session = session_with_scores(rules, &[10, -20, 0, 30, -10, 40]);
+        assert_eq!(
+            session.result(),
+            Some(GameResult {
+                winner: None,
+                reason: EndReason::DrawAdjudication
+            })
+        );
+
+        // One score outside the window keeps the game going
+        let session = session_with_scores(rules, &[10, -20, 0, 300, -10, 40]);
+        assert!(!session.is_over());
+    }
+
+    #[test]
+    fn test_impasse() {
+        let rules = GameRules {
+            impasse_plies: Some(2),
+            ..GameRules::default()
+        };
+        // Both kings have entered; sente has 28 points, gote only its king and
+        // two pawns
+        let sfen = "+R+B+B+RK4/PPPPPPPP1/9/9/9/9/9/pp7/4k4 b - 1";
+        let mut session = session(Some(sfen), rules);
+        session.play("5a4a", Duration::ZERO).unwrap();
+        assert!(!session.is_over());
+        session.play("5i6h", Duration::ZERO).unwrap();
+        assert_eq!(
+            session.result(),
+            Some(GameResult {
+                winner: Some(Player::Sente),
+                reason: EndReason::Impasse
+            })
+        );
+    }
+
+    #[test]
+    fn test_rules_deserialize_with_defaults() {
+        let rules: GameRules = serde_json::from_str(
+            r#"{ "maxPlies": 256, "resign": { "scoreCp": 3000, "moves": 3 } }"#,
+        )
+        .unwrap();
+        assert_eq!(rules.max_plies, Some(256));
+        assert_eq!(rules.resign.unwrap().score_cp, 3000);
+        assert_eq!(rules.declaration, crate::shogi::DeclarationRule::Points27);
+        assert!(rules.draw.is_none());
+    }
+}
@@ -0,0 +1,9 @@
+// Game sessions: the authoritative state of the game being played
+
+pub mod adjudication;
+pub mod repetition;
+pub mod session;
+
+pub use adjudication::*;
+pub use repetition::*;
+pub use session::*;
@@ -0,0 +1,157 @@
+// Sennichite: the same position (board, hands and side to move) occurring for
+// the fourth time ends the game, as a draw unless one side checked throughout
+
+use crate::shogi::{Player, Position};
+
+/// Occurrences of a position that end the game
+pub const SENNICHITE_OCCURRENCES: usize = 4;
+
+/// How a fourfold repetition is judged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sennichite {
+    Draw,
+    /// `checking` gave check with every move of the repetition and loses
+    PerpetualCheck {
+        checking: Player,
+    },
+}
+
+/// One position of the game as far as repetitions are concerned
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    key: u64,
+    side_to_move: Player,
+    /// The move that led here gave check
+    in_check: bool,
+}
+
+/// Zobrist keys of every position of a game, in order
+#[derive(Debug, Clone, Default)]
+pub struct RepetitionTracker {
+    entries: Vec<Entry>,
+}
+
+impl RepetitionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the position reached by the latest move (or the initial position)
+    pub fn push(&mut self, pos: &Position) {
+        let side_to_move = pos.side_to_move();
+        self.entries.push(Entry {
+            key: pos.key(),
+            side_to_move,
+            in_check: pos.in_check(side_to_move),
+        });
+    }
+
+    /// Forget the latest position, when its move is taken back
+    pub fn pop(&mut self) {
+        self.entries.pop();
+    }
+
+    /// How often the latest position has occurred
+    pub fn occurrences(&self) -> usize {
+        let Some(last) = self.entries.last() else {
+            return 0;
+        };
+        self.entries.iter().filter(|e| e.key == last.key).count()
+    }
+
+    /// Judge the latest position once it has occurred four times
+    /// The moves since its first occurrence decide perpetual check
+    pub fn sennichite(&self) -> Option<Sennichite> {
+        if self.occurrences() < SENNICHITE_OCCURRENCES {
+            return None;
+        }
+        let last = self.entries.last()?;
+        let first = self.entries.iter().position(|e| e.key == last.key)?;
+        let cycle = &self.entries[first + 1..];
+
+        // A position with `side_to_move` to play was reached by a move of its opponent
+        let checked_throughout = |player: Player| {
+            cycle
+                .iter()
+                .filter(|e| e.side_to_move == player.opponent())
+                .all(|e| e.in_check)
+        };
+        [Player::Sente, Player::Gote]
+            .into_iter()
+            .find(|&player| checked_throughout(player))
+            .map(|checking| Sennichite::PerpetualCheck { checking })
+            .or(Some(Sennichite::Draw))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shogi::Move;
+
+    fn track(sfen: &str, moves: &[&str]) -> RepetitionTracker {
+        let mut pos = Position::from_sfen(sfen).unwrap();
+        let mut tracker = RepetitionTracker::new();
+        tracker.push(&pos);
+        for usi in moves {
+            let mv = Move::from_usi(usi).unwrap();
+            assert!(pos.is_legal(mv), "{} is illegal", usi);
+            pos.do_move(mv);
+            tracker.push(&pos);
+        }
+        tracker
+    }
+
+    /// Both rooks shuffle back and forth, returning to the start every four moves
+    fn shuffle(cycles: usize) -> Vec<&'static str> {
+        ["2h3h", "8b7b", "3h2h", "7b8b"].repeat(cycles)
+    }
+
+    #[test]
+    fn test_fourth_occurrence_is_a_draw() {
+        let startpos = Position::startpos().to_sfen();
+
+        let tracker = track(&startpos, &shuffle(2));
+        assert_eq!(tracker.occurrences(), 3);
+        assert_eq!(tracker.sennichite(), None);
+
+        let tracker = track(&startpos, &shuffle(3));
+        assert_eq!(tracker.occurrences(), 4);
+        assert_eq!(tracker.sennichite(), Some(Sennichite::Draw));
+    }
+
+    #[test]
+    fn test_move_number_is_ignored() {
+        let a = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b - 1").unwrap();
+        let b = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b - 57").unwrap();
+        let mut tracker = RepetitionTracker::new();
+        tracker.push(&a);
+        tracker.push(&b);
+        assert_eq!(tracker.occurrences(), 2);
+
+        tracker.pop();
+        assert_eq!(tracker.occurrences(), 1);
+    }
+
+    #[test]
+    fn test_perpetual_check_loses() {
+        // Sente's rook follows the king between the 4 and 5 files, checking it
+        // with every move
+        let sfen = "5k3/9/9/9/4R4/9/9/9/K8 b - 1";
+        let moves = ["5e4e", "4a5a", "4e5e", "5a4a"].repeat(3);
+        let tracker = track(sfen, &moves);
+        assert_eq!(tracker.occurrences(), 4);
+        assert_eq!(
+            tracker.sennichite(),
+            Some(Sennichite::PerpetualCheck {
+                checking: Player::Sente
+            })
+        );
+
+        // Shuffling the kings without checks is a plain draw
+        let sfen = "5k3/9/9/9/9/9/9/9/K8 b - 1";
+        let moves = ["9i9h", "4a5a", "9h9i", "5a4a"].repeat(3);
+        let tracker = track(sfen, &moves);
+        assert_eq!(tracker.sennichite(), Some(Sennichite::Draw));
+    }
+}
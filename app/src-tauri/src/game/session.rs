@@ -0,0 +1,791 @@
+// Authoritative state of one game: position, moves, clocks, result and metadata
+// The UI, the engine and game records all work from the same GameSession
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::adjudication::{adjudicate, DrawThreshold, ResignThreshold};
+use super::repetition::{RepetitionTracker, Sennichite};
+use crate::shogi::{
+    judge_declaration, Declaration, DeclarationRule, Move, PieceType, Player, Position,
+};
+use crate::usi::{is_startpos, BestMove, GoLimits, Score};
+
+/// Time control of a timed game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeControl {
+    /// Main time of each player
+    pub main_ms: u64,
+    /// Time per move once the main time is used up
+    #[serde(default)]
+    pub byoyomi_ms: u64,
+    /// Time added after each move (Fischer)
+    #[serde(default)]
+    pub increment_ms: u64,
+}
+
+/// Rules chosen for a game
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GameRules {
+    /// How entering-king declarations are judged
+    pub declaration: DeclarationRule,
+    /// Draw once this many plies have been played (floodgate uses 256)
+    pub max_plies: Option<u32>,
+    /// Settle the game by counting points once both kings have stayed in their
+    /// promotion zones for this many plies
+    pub impasse_plies: Option<u32>,
+    /// Adjudicate a loss on the engines' scores
+    pub resign: Option<ResignThreshold>,
+    /// Adjudicate a draw on the engines' scores
+    pub draw: Option<DrawThreshold>,
+}
+
+/// Main time left to each player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Clocks {
+    pub sente_ms: u64,
+    pub gote_ms: u64,
+}
+
+impl Clocks {
+    pub fn remaining_ms(&self, player: Player) -> u64 {
+        match player {
+            Player::Sente => self.sente_ms,
+            Player::Gote => self.gote_ms,
+        }
+    }
+
+    fn remaining_mut(&mut self, player: Player) -> &mut u64 {
+        match player {
+            Player::Sente => &mut self.sente_ms,
+            Player::Gote => &mut self.gote_ms,
+        }
+    }
+}
+
+/// How a game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EndReason {
+    Checkmate,
+    Resignation,
+    /// The loser's time ran out
+    TimeUp,
+    /// The loser played an illegal move or declared a win without meeting the rules
+    IllegalMove,
+    /// A player declared by entering king (nyugyoku); a draw when the declaration
+    /// only reached the 24-point rule's drawing points
+    Declaration,
+    /// The same position occurred four times (sennichite); a draw
+    Repetition,
+    /// The loser gave check with every move of a fourfold repetition
+    PerpetualCheck,
+    /// The game reached the move limit; a draw
+    MaxMoves,
+    /// Both kings entered; decided by counting points, a draw when both sides
+    /// have 24
+    Impasse,
+    /// Both engines' scores agreed the loser was lost
+    ResignAdjudication,
+    /// Both engines' scores stayed level; a draw
+    DrawAdjudication,
+}
+
+/// Final result of a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameResult {
+    /// None for a draw
+    pub winner: Option<Player>,
+    pub reason: EndReason,
+}
+
+/// Who is playing and when the game started
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameInfo {
+    pub sente_name: Option<String>,
+    pub gote_name: Option<String>,
+    pub event: Option<String>,
+    /// Unix time in milliseconds
+    #[serde(default)]
+    pub started_at: u64,
+}
+
+/// A move played in the game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveRecord {
+    /// Move in USI notation
+    pub usi: String,
+    pub player: Player,
+    /// Piece taken by the move, as it stood on the board
+    pub captured: Option<PieceType>,
+    /// Time the player took
+    pub time_ms: u64,
+    /// Engine's score for the move, from the mover's point of view
+    pub score: Option<Score>,
+    /// Clocks before the move, restored by `undo`
+    #[serde(skip)]
+    clocks_before: Option<Clocks>,
+}
+
+/// Pieces in hand, one entry per piece, in the frontend's `CapturedPieces` shape
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Hands {
+    pub sente: Vec<PieceType>,
+    pub gote: Vec<PieceType>,
+}
+
+/// Everything the UI needs to show the game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameSnapshot {
+    pub initial_sfen: String,
+    /// Current position
+    pub sfen: String,
+    pub side_to_move: Player,
+    pub in_check: bool,
+    pub hands: Hands,
+    pub moves: Vec<MoveRecord>,
+    pub time_control: Option<TimeControl>,
+    pub rules: GameRules,
+    /// Main time left at the start of the current turn
+    pub clocks: Option<Clocks>,
+    pub result: Option<GameResult>,
+    pub info: GameInfo,
+    /// Increases with every change to the game
+    pub version: u64,
+}
+
+/// The single source of truth for a game in progress
+pub struct GameSession {
+    initial_sfen: String,
+    /// Position before each move, followed by the current position
+    positions: Vec<Position>,
+    moves: Vec<MoveRecord>,
+    repetitions: RepetitionTracker,
+    time_control: Option<TimeControl>,
+    rules: GameRules,
+    clocks: Option<Clocks>,
+    result: Option<GameResult>,
+    info: GameInfo,
+    version: u64,
+    /// When the side to move started thinking
+    turn_started: Instant,
+}
+
+impl GameSession {
+    /// Start a game from `initial_sfen` (the standard position when omitted)
+    pub fn new(
+        initial_sfen: Option<&str>,
+        time_control: Option<TimeControl>,
+        rules: GameRules,
+        mut info: GameInfo,
+    ) -> Result<Self, String> {
+        let (initial_sfen, position) = match initial_sfen.filter(|sfen| !is_startpos(sfen)) {
+            Some(sfen) => (
+                sfen.to_string(),
+                Position::from_sfen(sfen).map_err(|e| e.to_string())?,
+            ),
+            None => (Position::startpos().to_sfen(), Position::startpos()),
+        };
+        if info.started_at == 0 {
+            info.started_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64);
+        }
+
+        let mut repetitions = RepetitionTracker::new();
+        repetitions.push(&position);
+        let mut session = GameSession {
+            initial_sfen,
+            positions: vec![position],
+            moves: Vec::new(),
+            repetitions,
+            time_control,
+            rules,
+            clocks: time_control.map(|tc| Clocks {
+                sente_ms: tc.main_ms,
+                gote_ms: tc.main_ms,
+            }),
+            result: None,
+            info,
+            version: 0,
+            turn_started: Instant::now(),
+        };
+        session.check_game_end();
+        Ok(session)
+    }
+
+    pub fn position(&self) -> &Position {
+        self.positions
+            .last()
+            .expect("a session always has a position")
+    }
+
+    pub fn initial_sfen(&self) -> &str {
+        &self.initial_sfen
+    }
+
+    /// Moves played so far in USI notation
+    pub fn usi_moves(&self) -> Vec<String> {
+        self.moves.iter().map(|record| record.usi.clone()).collect()
+    }
+
+    pub fn moves(&self) -> &[MoveRecord] {
+        &self.moves
+    }
+
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.result.is_some()
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn rules(&self) -> GameRules {
+        self.rules
+    }
+
+    /// Limits for an engine playing the side to move: both players' remaining
+    /// main time with the byoyomi or increment, or `untimed_ms` per move when
+    /// the game has no time control
+    pub fn engine_limits(&self, untimed_ms: u32) -> GoLimits {
+        let (Some(tc), Some(clocks)) = (self.time_control, self.clocks) else {
+            return GoLimits::byoyomi(untimed_ms);
+        };
+        let ms = |value: u64| Some(u32::try_from(value).unwrap_or(u32::MAX));
+        let mut limits = GoLimits {
+            btime: ms(clocks.sente_ms),
+            wtime: ms(clocks.gote_ms),
+            ..Default::default()
+        };
+        // USI engines take either byoyomi or increments; sudden death is "byoyomi 0"
+        if tc.increment_ms > 0 {
+            limits.binc = ms(tc.increment_ms);
+            limits.winc = ms(tc.increment_ms);
+        } else {
+            limits.byoyomi = ms(tc.byoyomi_ms);
+        }
+        limits
+    }
+
+    /// Play `usi` for the side to move, charging it the time since its turn began
+    pub fn make_move(&mut self, usi: &str) -> Result<(), String> {
+        let elapsed = self.turn_started.elapsed();
+        self.play(usi, elapsed)
+    }
+
+    /// Play `usi` for the side to move as having taken `elapsed`, e.g. for moves
+    /// reported by a remote player or read from a record
+    /// A move made after the player's time ran out loses the game instead
+    pub fn play(&mut self, usi: &str, elapsed: Duration) -> Result<(), String> {
+        self.play_scored(usi, elapsed, None)
+    }
+
+    /// Play an engine's move along with the score it reported, which the
+    /// adjudication rules judge the game by
+    pub fn play_scored(
+        &mut self,
+        usi: &str,
+        elapsed: Duration,
+        score: Option<Score>,
+    ) -> Result<(), String> {
+        self.ensure_playing()?;
+        let mv = Move::from_usi(usi)?;
+        let position = self.position();
+        if !position.is_legal(mv) {
+            return Err(format!("Illegal move: {}", usi));
+        }
+        let player = position.side_to_move();
+
+        let clocks_before = self.clocks;
+        if !self.charge_time(player, elapsed) {
+            self.finish(Some(player.opponent()), EndReason::TimeUp);
+            return Ok(());
+        }
+
+        let mut next = self.position().clone();
+        let captured = next.do_move(mv).map(|piece| piece.piece_type);
+        self.repetitions.push(&next);
+        self.positions.push(next);
+        self.moves.push(MoveRecord {
+            usi: mv.to_usi(),
+            player,
+            captured,
+            time_ms: elapsed.as_millis() as u64,
+            score,
+            clocks_before,
+        });
+        self.turn_started = Instant::now();
+        self.version += 1;
+        self.check_game_end();
+        Ok(())
+    }
+
+    /// Take back the last move, resuming the game if it had ended
+    pub fn undo(&mut self) -> Result<(), String> {
+        let record = self.moves.pop().ok_or("No move to undo")?;
+        self.positions.pop();
+        self.repetitions.pop();
+        self.clocks = record.clocks_before;
+        self.result = None;
+        self.turn_started = Instant::now();
+        self.version += 1;
+        Ok(())
+    }
+
+    /// `player` resigns
+    pub fn resign(&mut self, player: Player) -> Result<(), String> {
+        self.ensure_playing()?;
+        self.finish(Some(player.opponent()), EndReason::Resignation);
+        Ok(())
+    }
+
+    /// The side to move declares by entering king, charging it the time since
+    /// its turn began
+    pub fn declare_win(&mut self) -> Result<(), String> {
+        let elapsed = self.turn_started.elapsed();
+        self.declare_win_after(elapsed)
+    }
+
+    /// Declare for the side to move as having taken `elapsed`
+    /// The position must meet the game's declaration rule; a declaration made
+    /// after the player's time ran out loses the game instead
+    pub fn declare_win_after(&mut self, elapsed: Duration) -> Result<(), String> {
+        self.ensure_playing()?;
+        let declaration = judge_declaration(self.position(), self.rules.declaration)
+            .ok_or("The declaration rules are not met")?;
+        let player = self.position().side_to_move();
+
+        if !self.charge_time(player, elapsed) {
+            self.finish(Some(player.opponent()), EndReason::TimeUp);
+            return Ok(());
+        }
+        let winner = match declaration {
+            Declaration::Win => Some(player),
+            Declaration::Draw => None,
+        };
+        self.finish(winner, EndReason::Declaration);
+        Ok(())
+    }
+
+    /// `player` loses for `reason`, e.g. an engine that played an illegal move
+    pub fn forfeit(&mut self, player: Player, reason: EndReason) -> Result<(), String> {
+        self.ensure_playing()?;
+        self.finish(Some(player.opponent()), reason);
+        Ok(())
+    }
+
+    /// Apply the engine's checked answer for the side to move, with the score
+    /// it reported
+    pub fn apply_best_move(
+        &mut self,
+        best_move: &BestMove,
+        score: Option<Score>,
+    ) -> Result<(), String> {
+        let player = self.position().side_to_move();
+        match best_move {
+            BestMove::Move(usi) => self.play_scored(usi, self.turn_started.elapsed(), score),
+            BestMove::Resign => self.resign(player),
+            BestMove::Win => self.declare_win(),
+        }
+    }
+
+    pub fn snapshot(&self) -> GameSnapshot {
+        let position = self.position();
+        let hand = |player| {
+            position
+                .hand(player)
+                .iter()
+                .flat_map(|(piece_type, count)| (0..count).map(move |_| piece_type))
+                .collect()
+        };
+        GameSnapshot {
+            initial_sfen: self.initial_sfen.clone(),
+            sfen: position.to_sfen(),
+            side_to_move: position.side_to_move(),
+            in_check: position.in_check(position.side_to_move()),
+            hands: Hands {
+                sente: hand(Player::Sente),
+                gote: hand(Player::Gote),
+            },
+            moves: self.moves.clone(),
+            time_control: self.time_control,
+            rules: self.rules,
+            clocks: self.clocks,
+            result: self.result,
+            info: self.info.clone(),
+            version: self.version,
+        }
+    }
+
+    fn ensure_playing(&self) -> Result<(), String> {
+        match self.result {
+            Some(_) => Err("The game is over".to_string()),
+            None => Ok(()),
+        }
+    }
+
+    /// Take `elapsed` off the player's clock; false when their time ran out
+    fn charge_time(&mut self, player: Player, elapsed: Duration) -> bool {
+        let (Some(tc), Some(clocks)) = (self.time_control, self.clocks.as_mut()) else {
+            return true;
+        };
+        let elapsed = elapsed.as_millis() as u64;
+        let remaining = clocks.remaining_mut(player);
+        if elapsed > *remaining + tc.byoyomi_ms {
+            return false;
+        }
+        *remaining = remaining.saturating_sub(elapsed) + tc.increment_ms;
+        true
+    }
+
+    /// End the game when the side to move is checkmated, the position has
+    /// occurred for the fourth time or the adjudication rules decide it
+    fn check_game_end(&mut self) {
+        let position = self.position();
+        if position.is_checkmate() {
+            let winner = position.side_to_move().opponent();
+            self.finish(Some(winner), EndReason::Checkmate);
+            return;
+        }
+        match self.repetitions.sennichite() {
+            Some(Sennichite::Draw) => self.finish(None, EndReason::Repetition),
+            Some(Sennichite::PerpetualCheck { checking }) => {
+                self.finish(Some(checking.opponent()), EndReason::PerpetualCheck)
+            }
+            None => {
+                if let Some(result) = adjudicate(&self.rules, &self.positions, &self.moves) {
+                    self.finish(result.winner, result.reason);
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, winner: Option<Player>, reason: EndReason) {
+        self.result = Some(GameResult { winner, reason });
+        self.version += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn untimed() -> GameSession {
+        GameSession::new(None, None, GameRules::default(), GameInfo::default()).unwrap()
+    }
+
+    fn play_all(session: &mut GameSession, moves: &[&str]) {
+        for usi in moves {
+            session.play(usi, Duration::ZERO).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_moves_update_position_and_history() {
+        let mut session = untimed();
+        play_all(&mut session, &["7g7f", "3c3d", "8h2b+"]);
+
+        let snapshot = session.snapshot();
+        assert_eq!(
+            snapshot.sfen,
+            "lnsgkgsnl/1r5+B1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/7R1/LNSGKGSNL w B 4"
+        );
+        assert_eq!(snapshot.side_to_move, Player::Gote);
+        assert_eq!(snapshot.hands.sente, vec![PieceType::Bishop]);
+        assert_eq!(snapshot.moves[2].captured, Some(PieceType::Bishop));
+        assert_eq!(snapshot.moves[2].player, Player::Sente);
+        assert_eq!(session.usi_moves(), vec!["7g7f", "3c3d", "8h2b+"]);
+        assert_eq!(snapshot.version, 3);
+    }
+
+    #[test]
+    fn test_illegal_moves_are_rejected() {
+        let mut session = untimed();
+        assert!(session.make_move("3c3d").is_err());
+        assert!(session.make_move("xx").is_err());
+        assert!(session.moves().is_empty());
+        assert_eq!(session.version(), 0);
+    }
+
+    #[test]
+    fn test_undo_restores_position_and_clocks() {
+        let tc = TimeControl {
+            main_ms: 60_000,
+            byoyomi_ms: 0,
+            increment_ms: 0,
+        };
+        let mut session =
+            GameSession::new(None, Some(tc), GameRules::default(), GameInfo::default()).unwrap();
+        session.play("7g7f", Duration::from_secs(10)).unwrap();
+        assert_eq!(session.snapshot().clocks.unwrap().sente_ms, 50_000);
+
+        session.undo().unwrap();
+        let snapshot = session.snapshot();
+        assert_eq!(snapshot.sfen, Position::startpos().to_sfen());
+        assert_eq!(snapshot.clocks.unwrap().sente_ms, 60_000);
+        assert!(session.undo().is_err());
+    }
+
+    #[test]
+    fn test_checkmate_ends_the_game() {
+        let mut session = GameSession::new(
+            Some("4k4/9/4P4/9/9/9/9/9/4K4 b G 1"),
+            None,
+            GameRules::default(),
+            GameInfo::default(),
+        )
+        .unwrap();
+        session.make_move("G*5b").unwrap();
+        assert_eq!(
+            session.result(),
+            Some(GameResult {
+                winner: Some(Player::Sente),
+                reason: EndReason::Checkmate
+            })
+        );
+        assert!(session.make_move("5a4a").is_err());
+
+        // Taking the mate back resumes the game
+        session.undo().unwrap();
+        assert!(!session.is_over());
+    }
+
+    #[test]
+    fn test_resign_and_forfeit() {
+        let mut session = untimed();
+        session.resign(Player::Sente).unwrap();
+        assert_eq!(session.result().unwrap().winner, Some(Player::Gote));
+        assert!(session.resign(Player::Gote).is_err());
+
+        let mut session = untimed();
+        session
+            .forfeit(Player::Gote, EndReason::IllegalMove)
+            .unwrap();
+        assert_eq!(
+            session.result(),
+            Some(GameResult {
+                winner: Some(Player::Sente),
+                reason: EndReason::IllegalMove
+            })
+        );
+    }
+
+    #[test]
+    fn test_byoyomi_and_time_up() {
+        let tc = TimeControl {
+            main_ms: 10_000,
+            byoyomi_ms: 5_000,
+            increment_ms: 0,
+        };
+        let mut session =
+            GameSession::new(None, Some(tc), GameRules::default(), GameInfo::default()).unwrap();
+        // Main time runs out, but the move is within the byoyomi
+        session.play("7g7f", Duration::from_secs(14)).unwrap();
+        assert_eq!(session.snapshot().clocks.unwrap().sente_ms, 0);
+
+        session.play("3c3d", Duration::from_secs(1)).unwrap();
+        session.play("2g2f", Duration::from_millis(5_001)).unwrap();
+        assert_eq!(
+            session.result(),
+            Some(GameResult {
+                winner: Some(Player::Gote),
+                reason: EndReason::TimeUp
+            })
+        );
+        assert_eq!(session.moves().len(), 2);
+    }
+
+    #[test]
+    fn test_increment() {
+        let tc = TimeControl {
+            main_ms: 10_000,
+            byoyomi_ms: 0,
+            increment_ms: 2_000,
+        };
+        let mut session =
+            GameSession::new(None, Some(tc), GameRules::default(), GameInfo::default()).unwrap();
+        session.play("7g7f", Duration::from_secs(3)).unwrap();
+        assert_eq!(session.snapshot().clocks.unwrap().sente_ms, 9_000);
+    }
+
+    #[test]
+    fn test_engine_limits_follow_the_clocks() {
+        assert_eq!(untimed().engine_limits(500), GoLimits::byoyomi(500));
+
+        let tc = TimeControl {
+            main_ms: 10_000,
+            byoyomi_ms: 5_000,
+            increment_ms: 0,
+        };
+        let mut session =
+            GameSession::new(None, Some(tc), GameRules::default(), GameInfo::default()).unwrap();
+        session.play("7g7f", Duration::from_secs(4)).unwrap();
+        let limits = session.engine_limits(500);
+        assert_eq!((limits.btime, limits.wtime), (Some(6_000), Some(10_000)));
+        assert_eq!(limits.byoyomi, Some(5_000));
+        assert_eq!((limits.binc, limits.winc), (None, None));
+
+        let tc = TimeControl {
+            main_ms: 60_000,
+            byoyomi_ms: 0,
+            increment_ms: 2_000,
+        };
+        let session =
+            GameSession::new(None, Some(tc), GameRules::default(), GameInfo::default()).unwrap();
+        let limits = session.engine_limits(500);
+        assert_eq!((limits.binc, limits.winc), (Some(2_000), Some(2_000)));
+        assert_eq!(limits.byoyomi, None);
+    }
+
+    #[test]
+    fn test_engine_answers() {
+        let mut session = untimed();
+        session
+            .apply_best_move(&BestMove::Move("7g7f".to_string()), None)
+            .unwrap();
+        assert_eq!(session.usi_moves(), vec!["7g7f"]);
+
+        assert!(session.apply_best_move(&BestMove::Win, None).is_err());
+        assert!(!session.is_over());
+
+        session.apply_best_move(&BestMove::Resign, None).unwrap();
+        assert_eq!(
+            session.result(),
+            Some(GameResult {
+                winner: Some(Player::Sente),
+                reason: EndReason::Resignation
+            })
+        );
+    }
+
+    #[test]
+    fn test_declare_win() {
+        let mut session = GameSession::new(
+            Some("+R+B+B+RK4/PPPPPPPP1/9/9/9/9/9/9/4k4 b - 1"),
+            None,
+            GameRules::default(),
+            GameInfo::default(),
+        )
+        .unwrap();
+        session.declare_win().unwrap();
+        assert_eq!(session.result().unwrap().reason, EndReason::Declaration);
+        assert_eq!(session.result().unwrap().winner, Some(Player::Sente));
+    }
+
+    #[test]
+    fn test_sennichite_is_a_draw() {
+        let mut session = untimed();
+        play_all(
+            &mut session,
+            &["2h3h", "8b7b", "3h2h", "7b8b"].repeat(3)[..11],
+        );
+        assert!(!session.is_over());
+
+        session.make_move("7b8b").unwrap();
+        assert_eq!(
+            session.result(),
+            Some(GameResult {
+                winner: None,
+                reason: EndReason::Repetition
+            })
+        );
+
+        // Taking the last move back resumes the game
+        session.undo().unwrap();
+        assert!(!session.is_over());
+    }
+
+    #[test]
+    fn test_perpetual_check_loses() {
+        let mut session = GameSession::new(
+            Some("5k3/9/9/9/4R4/9/9/9/K8 b - 1"),
+            None,
+            GameRules::default(),
+            GameInfo::default(),
+        )
+        .unwrap();
+        play_all(&mut session, &["5e4e", "4a5a", "4e5e", "5a4a"].repeat(3));
+        assert_eq!(
+            session.result(),
+            Some(GameResult {
+                winner: Some(Player::Gote),
+                reason: EndReason::PerpetualCheck
+            })
+        );
+    }
+
+    #[test]
+    fn test_24_point_declaration() {
+        // 28 points: a win under the 27-point rule, a draw under the 24-point one
+        let sfen = "+R+B+B+RK4/PPPPPPPP1/9/9/9/9/9/9/4k4 b - 1";
+        let rules = GameRules {
+            declaration: DeclarationRule::Points24,
+            ..GameRules::default()
+        };
+        let mut session = GameSession::new(Some(sfen), None, rules, GameInfo::default()).unwrap();
+        session.declare_win().unwrap();
+        assert_eq!(
+            session.result(),
+            Some(GameResult {
+                winner: None,
+                reason: EndReason::Declaration
+            })
+        );
+
+        // Too few points under either rule
+        let sfen = "+R+BK6/PPPPPPPPP/9/9/9/9/9/9/4k4 b - 1";
+        let mut session = GameSession::new(Some(sfen), None, rules, GameInfo::default()).unwrap();
+        assert!(session.declare_win().is_err());
+        assert!(!session.is_over());
+    }
+
+    #[test]
+    fn test_declaration_needs_time_left() {
+        let tc = TimeControl {
+            main_ms: 10_000,
+            byoyomi_ms: 0,
+            increment_ms: 0,
+        };
+        let sfen = "+R+B+B+RK4/PPPPPPPP1/9/9/9/9/9/9/4k4 b - 1";
+        let mut session = GameSession::new(
+            Some(sfen),
+            Some(tc),
+            GameRules::default(),
+            GameInfo::default(),
+        )
+        .unwrap();
+        session.declare_win_after(Duration::from_secs(11)).unwrap();
+        assert_eq!(
+            session.result(),
+            Some(GameResult {
+                winner: Some(Player::Gote),
+                reason: EndReason::TimeUp
+            })
+        );
+    }
+
+    #[test]
+    fn test_snapshot_serialization() {
+        let mut session = untimed();
+        session.make_move("7g7f").unwrap();
+        let json = serde_json::to_value(session.snapshot()).unwrap();
+        assert_eq!(json["sideToMove"], "gote");
+        assert_eq!(json["moves"][0]["usi"], "7g7f");
+        assert_eq!(json["moves"][0]["player"], "sente");
+        assert!(json["moves"][0].get("clocksBefore").is_none());
+        assert_eq!(json["hands"]["sente"], serde_json::json!([]));
+        assert!(json["result"].is_null());
+    }
+}
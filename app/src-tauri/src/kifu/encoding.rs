@@ -0,0 +1,84 @@
+// Text encodings of record files: Shift_JIS, as most existing files use, or
+// UTF-8 (.kifu and .ki2u)
+
+use encoding_rs::SHIFT_JIS;
+use serde::{Deserialize, Serialize};
+
+/// Encoding of a record file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TextEncoding {
+    Utf8,
+    ShiftJis,
+}
+
+impl TextEncoding {
+    /// Encoding a file is written in by its extension: UTF-8 for .kifu and
+    /// .ki2u, Shift_JIS for .kif, .ki2 and anything else
+    pub fn from_path(path: &str) -> TextEncoding {
+        let extension = path.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("kifu" | "ki2u") => TextEncoding::Utf8,
+            _ => TextEncoding::ShiftJis,
+        }
+    }
+}
+
+/// Decode a file, taking it as UTF-8 when it has a byte order mark or is valid
+/// UTF-8 throughout, and as Shift_JIS otherwise
+pub fn decode(bytes: &[u8]) -> Result<(String, TextEncoding), String> {
+    if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        let text = std::str::from_utf8(rest).map_err(|e| format!("Invalid UTF-8: {}", e))?;
+        return Ok((text.to_string(), TextEncoding::Utf8));
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Ok((text.to_string(), TextEncoding::Utf8));
+    }
+    let (text, had_errors) = SHIFT_JIS.decode_without_bom_handling(bytes);
+    if had_errors {
+        return Err("The file is neither UTF-8 nor Shift_JIS".to_string());
+    }
+    Ok((text.into_owned(), TextEncoding::ShiftJis))
+}
+
+/// Encode text for a file; Shift_JIS fails on characters it cannot represent
+pub fn encode(text: &str, encoding: TextEncoding) -> Result<Vec<u8>, String> {
+    match encoding {
+        TextEncoding::Utf8 => Ok(text.as_bytes().to_vec()),
+        TextEncoding::ShiftJis => {
+            let (bytes, _, had_errors) = SHIFT_JIS.encode(text);
+            if had_errors {
+                let bad = text
+                    .chars()
+                    .find(|c| SHIFT_JIS.encode(&c.to_string()).2)
+                    .unwrap_or('?');
+                return Err(format!("'{}' cannot be written in Shift_JIS", bad));
+            }
+            Ok(bytes.into_owned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = "先手：羽生善治\n▲７六歩";
+        for encoding in [TextEncoding::Utf8, TextEncoding::ShiftJis] {
+            let bytes = encode(text, encoding).unwrap();
+            assert_eq!(decode(&bytes).unwrap(), (text.to_string(), encoding));
+        }
+        let mut with_bom = b"\xEF\xBB\xBF".to_vec();
+        with_bom.extend(text.as_bytes());
+        assert_eq!(decode(&with_bom).unwrap().0, text);
+    }
+
+    #[test]
+    fn test_unmappable_characters() {
+        let err = encode("先手：😀", TextEncoding::ShiftJis).unwrap_err();
+        assert!(err.contains('😀'));
+        assert!(encode("先手：😀", TextEncoding::Utf8).is_ok());
+    }
+}
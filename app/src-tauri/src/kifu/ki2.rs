@@ -0,0 +1,130 @@
+// KI2: moves as spoken, several to a line and marked ▲ or △, with 右左上引寄直
+// standing in for the origin squares KIF writes
+
+use super::notation::{ki2_move, parse_notation, resolve};
+use super::record::{lines_to_write, pad, summary, write_headers, Kifu, KifuEnd, KifuReader};
+use crate::shogi::Player;
+
+/// Moves written per line
+const MOVES_PER_LINE: usize = 6;
+
+/// Width of a move with its padding, counting full-width characters as two
+const MOVE_WIDTH: usize = 14;
+
+/// Marks for sente and gote as written
+const SENTE_MARK: char = '▲';
+const GOTE_MARK: char = '△';
+
+/// Every mark read, including ☗ and ☖
+const MARKS: [char; 4] = ['▲', '△', '☗', '☖'];
+
+/// Read a KI2 record
+pub fn read_ki2(text: &str) -> Result<Kifu, String> {
+    let mut reader = KifuReader::new();
+    for line in text.lines() {
+        if reader.read_common(line)? {
+            continue;
+        }
+        let trimmed = line.trim();
+        if let Some(end) = KifuEnd::from_name(trimmed) {
+            reader.end(end)?;
+            continue;
+        }
+        if !trimmed.starts_with(MARKS) {
+            continue;
+        }
+
+        for item in trimmed.split(MARKS) {
+            let item = item.trim();
+            if item.is_empty() {
+                continue;
+            }
+            let (notation, rest) = parse_notation(item)?;
+            if !rest.trim().is_empty() {
+                return Err(format!("Unreadable move: {}", item));
+            }
+            let previous_to = reader.previous_to();
+            let mv = resolve(reader.position()?, &notation, previous_to)?;
+            reader.push_move(mv, None)?;
+        }
+    }
+    reader.finish()
+}
+
+/// Write a record as KI2; times are not part of the format
+pub fn write_ki2(kifu: &Kifu) -> Result<String, String> {
+    let mut lines = vec![write_headers(kifu)?];
+    lines.extend(kifu.comments.iter().map(|c| format!("*{}", c)));
+
+    for (index, line) in lines_to_write(kifu)?.into_iter().enumerate() {
+        lines.push(String::new());
+        if index > 0 {
+            lines.push(format!("変化：{}手", line.start_ply));
+        }
+        let mut row = Vec::new();
+        let mut previous_to = line.previous_to;
+        for (pos, mv, record) in &line.steps {
+            let mark = match pos.side_to_move() {
+                Player::Sente => SENTE_MARK,
+                Player::Gote => GOTE_MARK,
+            };
+            row.push(format!("{}{}", mark, ki2_move(pos, *mv, previous_to)?));
+            previous_to = Some(mv.to());
+            // A comment goes on its own line right after its move
+            if row.len() == MOVES_PER_LINE || !record.comments.is_empty() {
+                lines.push(write_row(&row));
+                row.clear();
+            }
+            lines.extend(record.comments.iter().map(|c| format!("*{}", c)));
+        }
+        if !row.is_empty() {
+            lines.push(write_row(&row));
+        }
+
+        if index == 0 {
+            lines.extend(summary(kifu)?);
+        }
+    }
+    lines.push(String::new());
+    Ok(lines.join("\n"))
+}
+
+fn write_row(moves: &[String]) -> String {
+    let (last, rest) = moves.split_last().expect("a row has a move");
+    let mut row: String = rest.iter().map(|m| pad(m, MOVE_WIDTH)).collect();
+    row.push_str(last);
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_moves() {
+        let text = "手合割：平手\n\
+                    ▲７六歩    △３四歩    ▲２二角成  △同　銀\n\
+                    *角交換\n\
+                    ☗４八銀 ☖３二金\n\
+                    まで6手で中断\n";
+        let kifu = read_ki2(text).unwrap();
+        let usi: Vec<&str> = kifu.moves.iter().map(|m| m.usi.as_str()).collect();
+        assert_eq!(usi, ["7g7f", "3c3d", "8h2b+", "3a2b", "3i4h", "4a3b"]);
+        assert_eq!(kifu.moves[3].comments, ["角交換"]);
+        assert!(kifu.moves.iter().all(|m| m.time_ms.is_none()));
+        assert_eq!(kifu.end, Some(KifuEnd::Interrupt));
+    }
+
+    #[test]
+    fn test_write_breaks_rows() {
+        let mut kifu =
+            read_ki2("▲７六歩△３四歩▲２六歩△８四歩▲２五歩△８五歩▲７八金△３二金").unwrap();
+        kifu.moves[6].comments.push("一局".to_string());
+        let text = write_ki2(&kifu).unwrap();
+        let rows: Vec<&str> = text.lines().filter(|l| l.starts_with(MARKS)).collect();
+        assert_eq!(rows.len(), 3);
+        assert!(rows[0].starts_with("▲７六歩      △３四歩"));
+        assert_eq!(rows[1], "▲７八金");
+        assert_eq!(read_ki2(&text).unwrap(), kifu);
+    }
+}
@@ -0,0 +1,152 @@
+// KIF: one numbered move per line with its origin square and the time taken,
+// as written by Kifu for Windows
+
+use super::notation::{kif_move, parse_notation, resolve};
+use super::record::{lines_to_write, pad, summary, write_headers, Kifu, KifuEnd, KifuReader};
+
+/// Width of a move with its padding, counting full-width characters as two
+const MOVE_WIDTH: usize = 13;
+
+/// Read a KIF record
+pub fn read_kif(text: &str) -> Result<Kifu, String> {
+    let mut reader = KifuReader::new();
+    for line in text.lines() {
+        if reader.read_common(line)? {
+            continue;
+        }
+        let trimmed = line.trim();
+        let digits = trimmed.len()
+            - trimmed
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        // Anything else, such as a header among the moves, is not for us
+        if digits == 0 {
+            continue;
+        }
+        let ply: usize = trimmed[..digits]
+            .parse()
+            .map_err(|_| format!("Unreadable move number: {}", trimmed))?;
+        if ply != reader.next_ply()? {
+            return Err(format!("Move {} is out of order: {}", ply, trimmed));
+        }
+        let text = trimmed[digits..].trim_start();
+
+        let word = text.split_whitespace().next().unwrap_or("");
+        if let Some(end) = KifuEnd::from_name(word) {
+            reader.end(end)?;
+            continue;
+        }
+        let (notation, rest) = parse_notation(text)?;
+        let previous_to = reader.previous_to();
+        let mv = resolve(reader.position()?, &notation, previous_to)?;
+        reader.push_move(mv, parse_time(rest))?;
+    }
+    reader.finish()
+}
+
+/// Time taken from "( 1:05/00:03:10)", in milliseconds
+fn parse_time(text: &str) -> Option<u64> {
+    let inside = text.trim().strip_prefix('(')?;
+    let (taken, _) = inside.split_once('/')?;
+    let (minutes, seconds) = taken.trim().split_once(':')?;
+    let seconds = minutes.trim().parse::<u64>().ok()? * 60 + seconds.parse::<u64>().ok()?;
+    Some(seconds * 1000)
+}
+
+/// "( 1:05/00:03:10)": time taken, then the mover's total
+fn format_time(taken_ms: u64, total_ms: u64) -> String {
+    let (taken, total) = (taken_ms / 1000, total_ms / 1000);
+    format!(
+        "({:>2}:{:02}/{:02}:{:02}:{:02})",
+        taken / 60,
+        taken % 60,
+        total / 3600,
+        total / 60 % 60,
+        total % 60
+    )
+}
+
+/// Write a record as KIF
+pub fn write_kif(kifu: &Kifu) -> Result<String, String> {
+    let mut lines = vec![
+        write_headers(kifu)?,
+        "手数----指手---------消費時間--".to_string(),
+    ];
+    lines.extend(kifu.comments.iter().map(|c| format!("*{}", c)));
+
+    for (index, line) in lines_to_write(kifu)?.into_iter().enumerate() {
+        if index > 0 {
+            lines.push(String::new());
+            lines.push(format!("変化：{}手", line.start_ply));
+        }
+        let mut previous_to = line.previous_to;
+        let mut totals = line.totals_before;
+        for (i, (pos, mv, record)) in line.steps.iter().enumerate() {
+            let notation = kif_move(pos, *mv, previous_to)?;
+            let mut text = format!("{:>4} {}", line.start_ply + i, notation);
+            if let Some(time_ms) = record.time_ms {
+                let total = &mut totals[pos.side_to_move().index()];
+                *total += time_ms;
+                text = format!(
+                    "{}{}",
+                    pad(&text, MOVE_WIDTH + 5),
+                    format_time(time_ms, *total)
+                );
+            }
+            if !record.variations.is_empty() {
+                text.push('+');
+            }
+            lines.push(text);
+            lines.extend(record.comments.iter().map(|c| format!("*{}", c)));
+            previous_to = Some(mv.to());
+        }
+
+        if index == 0 {
+            if let Some(end) = kifu.end {
+                lines.push(format!("{:>4} {}", kifu.moves.len() + 1, end.name()));
+            }
+            lines.extend(summary(kifu)?);
+        }
+    }
+    lines.push(String::new());
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_times() {
+        assert_eq!(parse_time("( 1:05/00:03:10)"), Some(65_000));
+        assert_eq!(parse_time("   ( 0:00/00:00:00)+"), Some(0));
+        assert_eq!(parse_time(""), None);
+        assert_eq!(format_time(65_400, 190_000), "( 1:05/00:03:10)");
+    }
+
+    #[test]
+    fn test_read_moves_and_end() {
+        let text = "手合割：平手\n\
+                    手数----指手---------消費時間--\n\
+                    \u{20}  1 ７六歩(77)   ( 0:03/00:00:03)\n\
+                    *角道を開ける\n\
+                    \u{20}  2 ３四歩(33)   ( 0:10/00:00:10)\n\
+                    \u{20}  3 ２二角成(88) ( 0:01/00:00:04)\n\
+                    \u{20}  4 同　銀(31)   ( 0:02/00:00:12)\n\
+                    \u{20}  5 投了\n\
+                    まで4手で後手の勝ち\n";
+        let kifu = read_kif(text).unwrap();
+        let usi: Vec<&str> = kifu.moves.iter().map(|m| m.usi.as_str()).collect();
+        assert_eq!(usi, ["7g7f", "3c3d", "8h2b+", "3a2b"]);
+        assert_eq!(kifu.moves[0].time_ms, Some(3000));
+        assert_eq!(kifu.moves[0].comments, ["角道を開ける"]);
+        assert_eq!(kifu.end, Some(KifuEnd::Resign));
+    }
+
+    #[test]
+    fn test_rejects_bad_moves() {
+        assert!(read_kif("   1 ７五歩(77)\n").is_err());
+        assert!(read_kif("   2 ７六歩(77)\n").is_err());
+        assert!(read_kif("   1 ７六歩(77)\n   2 投了\n   3 ３四歩(33)\n").is_err());
+    }
+}
@@ -0,0 +1,156 @@
+// Game records in the KIF and KI2 formats, read and written in Shift_JIS or
+// UTF-8, with comments, times and variations
+
+pub mod encoding;
+pub mod ki2;
+pub mod kif;
+pub mod notation;
+pub mod record;
+
+pub use encoding::*;
+pub use ki2::*;
+pub use kif::*;
+pub use record::*;
+
+/// First line of a UTF-8 KIF, so other programs do not take it for Shift_JIS
+const UTF8_KIF_HEADER: &str = "#KIF version=2.0 encoding=UTF-8";
+
+/// Parse a record from text
+pub fn parse_kifu(text: &str, format: KifuFormat) -> Result<Kifu, String> {
+    match format {
+        KifuFormat::Kif => read_kif(text),
+        KifuFormat::Ki2 => read_ki2(text),
+    }
+}
+
+/// Format a record as text
+pub fn format_kifu(kifu: &Kifu, format: KifuFormat) -> Result<String, String> {
+    match format {
+        KifuFormat::Kif => write_kif(kifu),
+        KifuFormat::Ki2 => write_ki2(kifu),
+    }
+}
+
+/// Read a record file in either encoding
+pub fn read_kifu(bytes: &[u8], format: KifuFormat) -> Result<Kifu, String> {
+    let (text, _) = decode(bytes)?;
+    parse_kifu(&text, format)
+}
+
+/// Write a record file
+pub fn write_kifu(
+    kifu: &Kifu,
+    format: KifuFormat,
+    encoding: TextEncoding,
+) -> Result<Vec<u8>, String> {
+    let mut text = format_kifu(kifu, format)?;
+    if format == KifuFormat::Kif && encoding == TextEncoding::Utf8 {
+        text = format!("{}\n{}", UTF8_KIF_HEADER, text);
+    }
+    // Kifu for Windows and most other programs expect CRLF
+    encode(&text.replace('\n', "\r\n"), encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_KIF: &[u8] = include_bytes!("samples/sample.kif");
+    const SAMPLE_KI2: &str = include_str!("samples/sample.ki2u");
+    const HANDICAP_KIF: &str = include_str!("samples/handicap.kifu");
+
+    fn usi(moves: &[KifuMove]) -> Vec<&str> {
+        moves.iter().map(|m| m.usi.as_str()).collect()
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(KifuFormat::from_path("a/game.KIF"), Some(KifuFormat::Kif));
+        assert_eq!(KifuFormat::from_path("game.ki2u"), Some(KifuFormat::Ki2));
+        assert_eq!(KifuFormat::from_path("game.csa"), None);
+        assert_eq!(TextEncoding::from_path("game.kifu"), TextEncoding::Utf8);
+        assert_eq!(TextEncoding::from_path("game.ki2"), TextEncoding::ShiftJis);
+    }
+
+    #[test]
+    fn test_read_shift_jis_sample() {
+        assert_eq!(decode(SAMPLE_KIF).unwrap().1, TextEncoding::ShiftJis);
+        let kifu = read_kifu(SAMPLE_KIF, KifuFormat::Kif).unwrap();
+        assert_eq!(kifu.header("先手"), Some("先手太郎"));
+        assert_eq!(kifu.header("開始日時"), Some("2024/03/09 10:00:00"));
+        assert_eq!(kifu.comments, ["相居飛車の一局"]);
+        assert_eq!(
+            usi(&kifu.moves[..6]),
+            ["7g7f", "3c3d", "2g2f", "4c4d", "2f2e", "2b3c"]
+        );
+        assert_eq!(kifu.moves[2].time_ms, Some(12_000));
+        assert_eq!(kifu.end, Some(KifuEnd::Resign));
+
+        // 変化：3手 replaces ▲２六歩, and 変化：4手 hangs off that variation
+        let variations = &kifu.moves[2].variations;
+        assert_eq!(variations.len(), 2);
+        assert_eq!(usi(&variations[0]), ["6g6f", "8c8d"]);
+        assert_eq!(usi(&variations[0][1].variations[0]), ["8b4b"]);
+        assert_eq!(usi(&variations[1]), ["1g1f"]);
+        assert_eq!(variations[1][0].comments, ["端歩"]);
+    }
+
+    #[test]
+    fn test_kif_round_trip() {
+        let kifu = read_kifu(SAMPLE_KIF, KifuFormat::Kif).unwrap();
+        for encoding in [TextEncoding::ShiftJis, TextEncoding::Utf8] {
+            let bytes = write_kifu(&kifu, KifuFormat::Kif, encoding).unwrap();
+            assert_eq!(read_kifu(&bytes, KifuFormat::Kif).unwrap(), kifu);
+        }
+        // Written the way it was read, apart from line endings
+        let (original, _) = decode(SAMPLE_KIF).unwrap();
+        let written = format_kifu(&kifu, KifuFormat::Kif).unwrap();
+        assert_eq!(written, original.replace("\r\n", "\n"));
+    }
+
+    #[test]
+    fn test_ki2_round_trip() {
+        let kifu = parse_kifu(SAMPLE_KI2, KifuFormat::Ki2).unwrap();
+        assert_eq!(kifu.end, Some(KifuEnd::Resign));
+        assert_eq!(kifu.moves[0].variations.len(), 1);
+        assert_eq!(format_kifu(&kifu, KifuFormat::Ki2).unwrap(), SAMPLE_KI2);
+
+        // The same game read from KIF matches apart from the times
+        let mut from_kif = read_kifu(SAMPLE_KIF, KifuFormat::Kif).unwrap();
+        fn clear_times(moves: &mut [KifuMove]) {
+            for m in moves {
+                m.time_ms = None;
+                m.variations.iter_mut().for_each(|v| clear_times(v));
+            }
+        }
+        clear_times(&mut from_kif.moves);
+        let ki2 = format_kifu(&from_kif, KifuFormat::Ki2).unwrap();
+        assert_eq!(parse_kifu(&ki2, KifuFormat::Ki2).unwrap(), from_kif);
+    }
+
+    #[test]
+    fn test_handicap_and_board_diagram() {
+        let kifu = parse_kifu(HANDICAP_KIF, KifuFormat::Kif).unwrap();
+        assert_eq!(
+            kifu.initial_sfen,
+            "lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1"
+        );
+        assert_eq!(kifu.header("上手"), Some("上手名人"));
+        assert_eq!(usi(&kifu.moves), ["6c6d", "7g7f", "5a4b"]);
+        assert_eq!(format_kifu(&kifu, KifuFormat::Kif).unwrap(), HANDICAP_KIF);
+
+        // A position no 手合割 names is written as a board diagram
+        let mut kifu = kifu;
+        kifu.initial_sfen = "8k/9/8P/9/9/9/9/9/K8 b G2r 1".to_string();
+        kifu.moves = vec![KifuMove {
+            usi: "G*1b".to_string(),
+            ..KifuMove::default()
+        }];
+        kifu.end = Some(KifuEnd::Checkmate);
+        let text = format_kifu(&kifu, KifuFormat::Kif).unwrap();
+        assert!(text.contains("| ・ ・ ・ ・ ・ ・ ・ ・v玉|一"));
+        assert!(text.contains("後手の持駒：飛二"));
+        assert!(text.contains("まで1手で詰み"));
+        assert_eq!(parse_kifu(&text, KifuFormat::Kif).unwrap(), kifu);
+    }
+}
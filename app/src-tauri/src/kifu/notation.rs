@@ -0,0 +1,564 @@
+// Japanese move notation shared by KIF and KI2: squares such as "７六", piece
+// names, 同/成/不成/打, and the 右左上引寄直 words that tell identical pieces apart
+
+use crate::shogi::{promotion_status, Move, PieceType, Player, Position, PromotionStatus, Square};
+
+const FILES: [char; 9] = ['１', '２', '３', '４', '５', '６', '７', '８', '９'];
+const RANKS: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+
+/// Written after 同 when the piece name is a single character
+const SAME_SPACER: char = '　';
+
+/// Square in notation, such as "７六"
+pub fn square_name(sq: Square) -> String {
+    let file = FILES[sq.file() as usize - 1];
+    let rank = RANKS[sq.rank() as usize - 1];
+    format!("{}{}", file, rank)
+}
+
+/// Kanji numeral for 1-18, used by ranks and hand counts
+pub fn kanji_number(n: u32) -> String {
+    match n {
+        1..=9 => RANKS[n as usize - 1].to_string(),
+        10 => "十".to_string(),
+        11..=18 => format!("十{}", RANKS[n as usize - 11]),
+        _ => n.to_string(),
+    }
+}
+
+/// Parse a kanji numeral written by `kanji_number`
+pub fn parse_kanji_number(s: &str) -> Option<u32> {
+    let mut chars = s.chars();
+    match (chars.next()?, chars.next()) {
+        ('十', None) => Some(10),
+        ('十', Some(c)) if chars.next().is_none() => Some(10 + kanji_digit(c)?),
+        (c, None) => kanji_digit(c),
+        _ => None,
+    }
+}
+
+fn kanji_digit(c: char) -> Option<u32> {
+    RANKS.iter().position(|&r| r == c).map(|i| i as u32 + 1)
+}
+
+/// File digit: full-width or ASCII
+fn file_digit(c: char) -> Option<u8> {
+    FILES
+        .iter()
+        .position(|&f| f == c)
+        .map(|i| i as u8 + 1)
+        .or_else(|| c.to_digit(10).filter(|&d| d > 0).map(|d| d as u8))
+}
+
+/// Rank digit: kanji, full-width or ASCII
+fn rank_digit(c: char) -> Option<u8> {
+    kanji_digit(c).map(|d| d as u8).or_else(|| file_digit(c))
+}
+
+/// Name of a piece in a move, such as "歩" or "成銀"
+pub fn piece_name(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::Pawn => "歩",
+        PieceType::Lance => "香",
+        PieceType::Knight => "桂",
+        PieceType::Silver => "銀",
+        PieceType::Gold => "金",
+        PieceType::Bishop => "角",
+        PieceType::Rook => "飛",
+        PieceType::King => "玉",
+        PieceType::ProPawn => "と",
+        PieceType::ProLance => "成香",
+        PieceType::ProKnight => "成桂",
+        PieceType::ProSilver => "成銀",
+        PieceType::Horse => "馬",
+        PieceType::Dragon => "龍",
+    }
+}
+
+/// Single-character name of a piece, as drawn on a board diagram
+pub fn piece_char(piece_type: PieceType) -> char {
+    match piece_type {
+        PieceType::ProLance => '杏',
+        PieceType::ProKnight => '圭',
+        PieceType::ProSilver => '全',
+        _ => piece_name(piece_type).chars().next().unwrap(),
+    }
+}
+
+/// Parse a piece name at the start of `s`; returns the piece and the bytes used
+/// Accepts the one-character promoted names and the alternatives 王 and 竜
+pub fn parse_piece(s: &str) -> Option<(PieceType, usize)> {
+    if let Some(rest) = s.strip_prefix('成') {
+        let piece_type = match rest.chars().next()? {
+            '香' => PieceType::ProLance,
+            '桂' => PieceType::ProKnight,
+            '銀' => PieceType::ProSilver,
+            _ => return None,
+        };
+        return Some((piece_type, '成'.len_utf8() * 2));
+    }
+    let c = s.chars().next()?;
+    let piece_type = match c {
+        '王' => PieceType::King,
+        '竜' => PieceType::Dragon,
+        '杏' => PieceType::ProLance,
+        '圭' => PieceType::ProKnight,
+        '全' => PieceType::ProSilver,
+        _ => *PieceType::ALL.iter().find(|&&pt| piece_char(pt) == c)?,
+    };
+    Some((piece_type, c.len_utf8()))
+}
+
+/// A move as written, before it is matched against the position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notation {
+    /// Destination; None for 同 (the previous move's destination)
+    pub to: Option<Square>,
+    pub piece_type: PieceType,
+    /// 右左上引寄直, in the order written
+    pub relative: String,
+    /// Some(true) for 成, Some(false) for 不成
+    pub promote: Option<bool>,
+    /// 打 was written
+    pub drop: bool,
+    /// KIF origin such as "(77)"
+    pub from: Option<Square>,
+}
+
+/// Parse a move such as "７六歩(77)", "同　角成" or "５八金右" at the start of
+/// `s`; returns the notation and the rest of the text
+pub fn parse_notation(s: &str) -> Result<(Notation, &str), String> {
+    let bad = || format!("Unreadable move: {}", s.trim());
+    let mut rest = s;
+
+    let to = if let Some(after) = rest.strip_prefix('同') {
+        rest = after.trim_start_matches([SAME_SPACER, ' ']);
+        None
+    } else {
+        let mut chars = rest.chars();
+        let file = chars.next().and_then(file_digit).ok_or_else(bad)?;
+        let rank = chars.next().and_then(rank_digit).ok_or_else(bad)?;
+        rest = chars.as_str();
+        Some(Square::new(file, rank).ok_or_else(bad)?)
+    };
+
+    let (piece_type, len) = parse_piece(rest).ok_or_else(bad)?;
+    rest = &rest[len..];
+
+    let mut relative = String::new();
+    while let Some(c) = rest.chars().next().filter(|c| "右左上引寄直".contains(*c)) {
+        relative.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    let mut promote = None;
+    let mut drop = false;
+    if let Some(after) = rest.strip_prefix("不成") {
+        promote = Some(false);
+        rest = after;
+    } else if let Some(after) = rest.strip_prefix('成') {
+        promote = Some(true);
+        rest = after;
+    } else if let Some(after) = rest.strip_prefix('打') {
+        drop = true;
+        rest = after;
+    }
+
+    let mut from = None;
+    if let Some(after) = rest.strip_prefix('(') {
+        let close = after.find(')').ok_or_else(bad)?;
+        let digits: Vec<char> = after[..close].chars().collect();
+        let [file, rank] = digits[..] else {
+            return Err(bad());
+        };
+        let file = file_digit(file).ok_or_else(bad)?;
+        let rank = rank_digit(rank).ok_or_else(bad)?;
+        from = Some(Square::new(file, rank).ok_or_else(bad)?);
+        rest = &after[close + 1..];
+    }
+
+    let notation = Notation {
+        to,
+        piece_type,
+        relative,
+        promote,
+        drop,
+        from,
+    };
+    Ok((notation, rest))
+}
+
+/// Direction of a move from the mover's point of view
+fn direction(from: Square, to: Square, player: Player) -> char {
+    let forward = to.relative_rank(player) as i8 - from.relative_rank(player) as i8;
+    match forward {
+        f if f < 0 => '上',
+        0 => '寄',
+        _ => '引',
+    }
+}
+
+/// How far right a square is from the player's point of view
+fn rightness(sq: Square, player: Player) -> i8 {
+    match player {
+        Player::Sente => -(sq.file() as i8),
+        Player::Gote => sq.file() as i8,
+    }
+}
+
+/// Squares of the other pieces like the one on `from` that can also reach `to`
+fn rivals(pos: &Position, from: Square, to: Square) -> Vec<Square> {
+    let piece = pos.piece_at(from);
+    let mut squares: Vec<Square> = pos
+        .legal_moves()
+        .into_iter()
+        .filter_map(|mv| match mv {
+            Move::Normal {
+                from: other,
+                to: dest,
+                ..
+            } if dest == to && other != from && pos.piece_at(other) == piece => Some(other),
+            _ => None,
+        })
+        .collect();
+    squares.sort();
+    squares.dedup();
+    squares
+}
+
+/// The 右左上引寄直 words that single out the board move `from`-`to` among
+/// identical pieces that can reach the same square, following the JSA rules:
+/// the direction alone if it is enough, else 直 for a straight advance, else
+/// 右 or 左, adding the direction when the side alone is not enough
+/// Dragons and horses use 右 and 左 rather than 直
+pub fn relative_words(pos: &Position, from: Square, to: Square) -> String {
+    let Some(piece) = pos.piece_at(from) else {
+        return String::new();
+    };
+    let rivals = rivals(pos, from, to);
+    if rivals.is_empty() {
+        return String::new();
+    }
+
+    let player = piece.owner;
+    let dir = direction(from, to, player);
+    let same_dir: Vec<Square> = rivals
+        .iter()
+        .copied()
+        .filter(|&sq| direction(sq, to, player) == dir)
+        .collect();
+    if same_dir.is_empty() {
+        return dir.to_string();
+    }
+
+    let big = matches!(piece.piece_type, PieceType::Dragon | PieceType::Horse);
+    if !big && dir == '上' && from.file() == to.file() {
+        return "直".to_string();
+    }
+
+    let side = |squares: &[Square]| {
+        let me = rightness(from, player);
+        if squares.iter().all(|&sq| rightness(sq, player) < me) {
+            Some('右')
+        } else if squares.iter().all(|&sq| rightness(sq, player) > me) {
+            Some('左')
+        } else {
+            None
+        }
+    };
+    if let Some(side) = side(&rivals) {
+        return side.to_string();
+    }
+    match side(&same_dir) {
+        Some(side) => format!("{}{}", side, dir),
+        None => dir.to_string(),
+    }
+}
+
+/// Whether a drop needs 打 because a piece on the board could also move there
+fn drop_needs_marker(pos: &Position, piece_type: PieceType, to: Square) -> bool {
+    pos.legal_moves().into_iter().any(|mv| match mv {
+        Move::Normal { from, to: dest, .. } => {
+            dest == to
+                && pos
+                    .piece_at(from)
+                    .is_some_and(|p| p.piece_type == piece_type)
+        }
+        Move::Drop { .. } => false,
+    })
+}
+
+/// 成 or 不成 for a board move, when promotion was a choice
+fn promotion_word(pos: &Position, from: Square, to: Square, promote: bool) -> &'static str {
+    let Some(piece) = pos.piece_at(from) else {
+        return "";
+    };
+    match (promotion_status(piece, from, to), promote) {
+        (PromotionStatus::Forbidden, _) => "",
+        (_, true) => "成",
+        (PromotionStatus::Optional, false) => "不成",
+        (PromotionStatus::Required, false) => "",
+    }
+}
+
+/// Destination as written: 同 when it repeats the previous move's destination
+fn destination(mv: Move, piece_type: PieceType, previous_to: Option<Square>) -> String {
+    if previous_to == Some(mv.to()) {
+        let spacer = if piece_name(piece_type).chars().count() == 1 {
+            SAME_SPACER.to_string()
+        } else {
+            String::new()
+        };
+        format!("同{}", spacer)
+    } else {
+        square_name(mv.to())
+    }
+}
+
+fn moving_piece(pos: &Position, mv: Move) -> Result<PieceType, String> {
+    match mv {
+        Move::Normal { from, .. } => pos
+            .piece_at(from)
+            .map(|p| p.piece_type)
+            .ok_or_else(|| format!("No piece on {}", from.to_usi())),
+        Move::Drop { piece_type, .. } => Ok(piece_type),
+    }
+}
+
+/// KIF notation of a legal move, such as "７六歩(77)" or "５五角打"
+pub fn kif_move(pos: &Position, mv: Move, previous_to: Option<Square>) -> Result<String, String> {
+    let piece_type = moving_piece(pos, mv)?;
+    let mut text = destination(mv, piece_type, previous_to);
+    text.push_str(piece_name(piece_type));
+    match mv {
+        Move::Normal { from, to, promote } => {
+            text.push_str(promotion_word(pos, from, to, promote));
+            text.push_str(&format!("({}{})", from.file(), from.rank()));
+        }
+        Move::Drop { .. } => text.push('打'),
+    }
+    Ok(text)
+}
+
+/// KI2 notation of a legal move without the ▲/△ mark, such as "７六歩" or
+/// "５八金右"
+pub fn ki2_move(pos: &Position, mv: Move, previous_to: Option<Square>) -> Result<String, String> {
+    let piece_type = moving_piece(pos, mv)?;
+    let mut text = destination(mv, piece_type, previous_to);
+    text.push_str(piece_name(piece_type));
+    match mv {
+        Move::Normal { from, to, promote } => {
+            text.push_str(&relative_words(pos, from, to));
+            text.push_str(promotion_word(pos, from, to, promote));
+        }
+        Move::Drop { piece_type, to } => {
+            if drop_needs_marker(pos, piece_type, to) {
+                text.push('打');
+            }
+        }
+    }
+    Ok(text)
+}
+
+/// Find the legal move `notation` stands for
+/// With a KIF origin the move is taken as written; otherwise (KI2) the piece,
+/// the 打 marker and the 右左上引寄直 words pick it out
+pub fn resolve(
+    pos: &Position,
+    notation: &Notation,
+    previous_to: Option<Square>,
+) -> Result<Move, String> {
+    let to = notation
+        .to
+        .or(previous_to)
+        .ok_or("同 without a previous move")?;
+    let promote = notation.promote == Some(true);
+
+    let mv = if let Some(from) = notation.from {
+        Move::Normal { from, to, promote }
+    } else if notation.drop {
+        Move::Drop {
+            piece_type: notation.piece_type,
+            to,
+        }
+    } else {
+        let board_moves: Vec<Move> = pos
+            .legal_moves()
+            .into_iter()
+            .filter(|&mv| match mv {
+                Move::Normal {
+                    from,
+                    to: dest,
+                    promote: p,
+                } => {
+                    dest == to
+                        && p == promote
+                        && pos
+                            .piece_at(from)
+                            .is_some_and(|piece| piece.piece_type == notation.piece_type)
+                }
+                Move::Drop { .. } => false,
+            })
+            .collect();
+        match board_moves[..] {
+            [] => Move::Drop {
+                piece_type: notation.piece_type,
+                to,
+            },
+            [mv] => mv,
+            _ => *board_moves
+                .iter()
+                .find(|mv| match mv {
+                    Move::Normal { from, .. } => {
+                        relative_words(pos, *from, to) == notation.relative
+                    }
+                    Move::Drop { .. } => false,
+                })
+                .ok_or_else(|| format!("Ambiguous move: {}", square_name(to)))?,
+        }
+    };
+
+    if !pos.is_legal(mv) {
+        return Err(format!("Illegal move: {}", mv.to_usi()));
+    }
+    if moving_piece(pos, mv)? != notation.piece_type {
+        return Err(format!(
+            "No {} can move to {}",
+            piece_name(notation.piece_type),
+            square_name(to)
+        ));
+    }
+    Ok(mv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sq(usi: &str) -> Square {
+        Square::from_usi(usi).unwrap()
+    }
+
+    fn mv(usi: &str) -> Move {
+        Move::from_usi(usi).unwrap()
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(square_name(sq("7f")), "７六");
+        assert_eq!(kanji_number(3), "三");
+        assert_eq!(kanji_number(18), "十八");
+        assert_eq!(parse_kanji_number("十八"), Some(18));
+        assert_eq!(parse_kanji_number("十"), Some(10));
+        assert_eq!(parse_piece("成銀右"), Some((PieceType::ProSilver, 6)));
+        assert_eq!(parse_piece("竜"), Some((PieceType::Dragon, 3)));
+        assert_eq!(parse_piece("全"), Some((PieceType::ProSilver, 3)));
+    }
+
+    #[test]
+    fn test_parse_notation() {
+        let (n, rest) = parse_notation("７六歩(77)   ( 0:01/00:00:01)").unwrap();
+        assert_eq!(n.to, Some(sq("7f")));
+        assert_eq!(n.piece_type, PieceType::Pawn);
+        assert_eq!(n.from, Some(sq("7g")));
+        assert_eq!(rest.trim(), "( 0:01/00:00:01)");
+
+        let (n, _) = parse_notation("同　角成(88)").unwrap();
+        assert_eq!((n.to, n.promote), (None, Some(true)));
+
+        let (n, _) = parse_notation("３三桂不成").unwrap();
+        assert_eq!(n.promote, Some(false));
+
+        let (n, _) = parse_notation("５八金左上").unwrap();
+        assert_eq!(n.relative, "左上");
+
+        let (n, _) = parse_notation("５五角打").unwrap();
+        assert!(n.drop);
+
+        assert!(parse_notation("投了").is_err());
+    }
+
+    #[test]
+    fn test_kif_and_ki2_moves() {
+        let pos = Position::startpos();
+        assert_eq!(kif_move(&pos, mv("7g7f"), None).unwrap(), "７六歩(77)");
+        assert_eq!(ki2_move(&pos, mv("7g7f"), None).unwrap(), "７六歩");
+
+        // Bishop exchange: 同 with a promotion, then 同 by the silver
+        let pos = Position::from_sfen(
+            "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3",
+        )
+        .unwrap();
+        let text = kif_move(&pos, mv("8h2b+"), None).unwrap();
+        assert_eq!(text, "２二角成(88)");
+        let pos = pos.after_move(mv("8h2b+"));
+        assert_eq!(
+            ki2_move(&pos, mv("3a2b"), Some(sq("2b"))).unwrap(),
+            "同　銀"
+        );
+
+        // A promoted piece's name needs no spacer after 同
+        let pos = Position::from_sfen("4k4/9/4+S4/9/9/9/9/9/4K4 b - 1").unwrap();
+        assert_eq!(
+            ki2_move(&pos, mv("5c5b"), Some(sq("5b"))).unwrap(),
+            "同成銀"
+        );
+    }
+
+    #[test]
+    fn test_relative_words() {
+        // Golds on 6i, 5i and 4i can all reach 5h
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/9/9/3GGG2K b - 1").unwrap();
+        assert_eq!(relative_words(&pos, sq("6i"), sq("5h")), "左");
+        assert_eq!(relative_words(&pos, sq("5i"), sq("5h")), "直");
+        assert_eq!(relative_words(&pos, sq("4i"), sq("5h")), "右");
+
+        // One gold comes up, the other sideways
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/9/5G3/3G4K b - 1").unwrap();
+        assert_eq!(relative_words(&pos, sq("6i"), sq("5h")), "上");
+        assert_eq!(relative_words(&pos, sq("4h"), sq("5h")), "寄");
+
+        // Silvers on 6i and 4i come up and a third on 6g drops back, so 左
+        // alone would not tell 6i from 6g
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/3S5/9/3S1S2K b - 1").unwrap();
+        assert_eq!(relative_words(&pos, sq("6g"), sq("5h")), "引");
+        assert_eq!(relative_words(&pos, sq("6i"), sq("5h")), "左上");
+        assert_eq!(relative_words(&pos, sq("4i"), sq("5h")), "右");
+
+        // Gote's right is sente's left
+        let pos = Position::from_sfen("k2g1g3/9/9/9/9/9/9/9/4K4 w - 1").unwrap();
+        assert_eq!(relative_words(&pos, sq("6a"), sq("5b")), "右");
+        assert_eq!(relative_words(&pos, sq("4a"), sq("5b")), "左");
+
+        // A lone piece needs nothing
+        assert_eq!(
+            relative_words(&Position::startpos(), sq("7g"), sq("7f")),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_resolve_ki2() {
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/9/9/3GGG2K b G 1").unwrap();
+        for usi in ["6i5h", "5i5h", "4i5h"] {
+            let text = ki2_move(&pos, mv(usi), None).unwrap();
+            let (notation, _) = parse_notation(&text).unwrap();
+            assert_eq!(resolve(&pos, &notation, None).unwrap(), mv(usi));
+        }
+
+        // 打 tells the drop from the board moves
+        let text = ki2_move(&pos, mv("G*5h"), None).unwrap();
+        assert_eq!(text, "５八金打");
+        let (notation, _) = parse_notation(&text).unwrap();
+        assert_eq!(resolve(&pos, &notation, None).unwrap(), mv("G*5h"));
+
+        // Without rivals on the board, a drop needs no 打
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b G 1").unwrap();
+        assert_eq!(ki2_move(&pos, mv("G*5e"), None).unwrap(), "５五金");
+        let (notation, _) = parse_notation("５五金").unwrap();
+        assert_eq!(resolve(&pos, &notation, None).unwrap(), mv("G*5e"));
+
+        let (notation, _) = parse_notation("５五飛").unwrap();
+        assert!(resolve(&pos, &notation, None).is_err());
+    }
+}
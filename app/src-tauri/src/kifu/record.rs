@@ -0,0 +1,968 @@
+// Game records as read from and written to KIF and KI2: headers, the initial
+// position, moves with times and comments, variations and how the game ended
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::notation::{kanji_number, parse_kanji_number, parse_piece, piece_char};
+use crate::game::{EndReason, GameInfo, GameRules, GameSession};
+use crate::shogi::{Move, Piece, PieceType, Player, Position, Square, STARTPOS_SFEN};
+
+/// Record file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KifuFormat {
+    /// One numbered move per line with origin squares and times
+    Kif,
+    /// Moves as spoken, several per line, told apart by 右左上引寄直
+    Ki2,
+}
+
+impl KifuFormat {
+    /// Format named by a file extension (.kif/.kifu or .ki2/.ki2u)
+    pub fn from_path(path: &str) -> Option<KifuFormat> {
+        let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+        match extension.as_str() {
+            "kif" | "kifu" => Some(KifuFormat::Kif),
+            "ki2" | "ki2u" => Some(KifuFormat::Ki2),
+            _ => None,
+        }
+    }
+}
+
+/// How a recorded game ended, as written in place of a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KifuEnd {
+    /// 投了: the side to move resigned
+    Resign,
+    /// 中断: the game was stopped
+    Interrupt,
+    /// 千日手
+    Repetition,
+    /// 持将棋: drawn by impasse
+    Impasse,
+    /// 切れ負け: the side to move ran out of time
+    TimeUp,
+    /// 反則勝ち: the side to move wins by the opponent's illegal move
+    IllegalWin,
+    /// 反則負け: the side to move loses by its illegal move
+    IllegalLoss,
+    /// 入玉勝ち: the side to move declared a win
+    Declaration,
+    /// 詰み: the side to move is checkmated
+    Checkmate,
+}
+
+const END_NAMES: [(KifuEnd, &str); 9] = [
+    (KifuEnd::Resign, "投了"),
+    (KifuEnd::Interrupt, "中断"),
+    (KifuEnd::Repetition, "千日手"),
+    (KifuEnd::Impasse, "持将棋"),
+    (KifuEnd::TimeUp, "切れ負け"),
+    (KifuEnd::IllegalWin, "反則勝ち"),
+    (KifuEnd::IllegalLoss, "反則負け"),
+    (KifuEnd::Declaration, "入玉勝ち"),
+    (KifuEnd::Checkmate, "詰み"),
+];
+
+impl KifuEnd {
+    pub fn name(self) -> &'static str {
+        END_NAMES.iter().find(|(end, _)| *end == self).unwrap().1
+    }
+
+    pub fn from_name(name: &str) -> Option<KifuEnd> {
+        END_NAMES
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(end, _)| *end)
+    }
+}
+
+/// A recorded move
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KifuMove {
+    /// Move in USI notation
+    pub usi: String,
+    /// Time the move took; KIF keeps whole seconds, KI2 no times at all
+    pub time_ms: Option<u64>,
+    pub comments: Vec<String>,
+    /// Other moves played instead of this one, each the start of its own line
+    pub variations: Vec<Vec<KifuMove>>,
+}
+
+/// A game record
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Kifu {
+    /// Header fields in file order, such as ("先手", "名前"); the starting
+    /// position (手合割 or a board diagram) is kept in `initial_sfen` instead
+    pub headers: Vec<(String, String)>,
+    pub initial_sfen: String,
+    /// Comments before the first move
+    pub comments: Vec<String>,
+    /// Main line
+    pub moves: Vec<KifuMove>,
+    pub end: Option<KifuEnd>,
+}
+
+/// Starting positions named by 手合割; handicap games start with gote (上手)
+const HANDICAPS: [(&str, &str); 11] = [
+    ("平手", STARTPOS_SFEN),
+    (
+        "香落ち",
+        "lnsgkgsn1/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "右香落ち",
+        "1nsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "角落ち",
+        "lnsgkgsnl/1r7/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "飛車落ち",
+        "lnsgkgsnl/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "飛香落ち",
+        "lnsgkgsn1/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "二枚落ち",
+        "lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "四枚落ち",
+        "1nsgkgsn1/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "六枚落ち",
+        "2sgkgs2/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "八枚落ち",
+        "3gkg3/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "十枚落ち",
+        "4k4/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+];
+
+/// 手合割 of a starting position, if it is one of the standard ones
+fn handicap_name(sfen: &str) -> Option<&'static str> {
+    // The move number does not matter
+    let board = |sfen: &str| sfen.rsplit_once(' ').map(|(s, _)| s.to_string());
+    HANDICAPS
+        .iter()
+        .find(|(_, handicap)| board(handicap) == board(sfen))
+        .map(|(name, _)| *name)
+}
+
+/// Header keys for each side; handicap records use 下手 and 上手
+fn player_keys(player: Player) -> [&'static str; 2] {
+    match player {
+        Player::Sente => ["先手", "下手"],
+        Player::Gote => ["後手", "上手"],
+    }
+}
+
+fn player_name(player: Player) -> &'static str {
+    player_keys(player)[0]
+}
+
+impl Kifu {
+    /// Value of the first header named `key`
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Record of a game session's main line
+    pub fn from_session(session: &GameSession) -> Kifu {
+        let snapshot = session.snapshot();
+        let info = &snapshot.info;
+        let mut headers = Vec::new();
+        if info.started_at > 0 {
+            headers.push(("開始日時".to_string(), format_date(info.started_at)));
+        }
+        if let Some(event) = &info.event {
+            headers.push(("棋戦".to_string(), event.clone()));
+        }
+        if let Some(name) = &info.sente_name {
+            headers.push(("先手".to_string(), name.clone()));
+        }
+        if let Some(name) = &info.gote_name {
+            headers.push(("後手".to_string(), name.clone()));
+        }
+
+        let moves = session
+            .moves()
+            .iter()
+            .map(|record| KifuMove {
+                usi: record.usi.clone(),
+                time_ms: Some(record.time_ms),
+                ..KifuMove::default()
+            })
+            .collect();
+
+        let side_to_move = snapshot.side_to_move;
+        let end = snapshot.result.map(|result| {
+            let stm_wins = result.winner == Some(side_to_move);
+            match result.reason {
+                EndReason::Checkmate => KifuEnd::Checkmate,
+                EndReason::Resignation => KifuEnd::Resign,
+                EndReason::TimeUp => KifuEnd::TimeUp,
+                EndReason::IllegalMove | EndReason::PerpetualCheck if stm_wins => {
+                    KifuEnd::IllegalWin
+                }
+                EndReason::IllegalMove | EndReason::PerpetualCheck => KifuEnd::IllegalLoss,
+                EndReason::Declaration if result.winner.is_some() => KifuEnd::Declaration,
+                EndReason::Repetition => KifuEnd::Repetition,
+                // Other draws have no word of their own
+                _ if result.winner.is_none() => KifuEnd::Impasse,
+                _ => KifuEnd::Interrupt,
+            }
+        });
+
+        Kifu {
+            headers,
+            initial_sfen: session.initial_sfen().to_string(),
+            comments: Vec::new(),
+            moves,
+            end,
+        }
+    }
+
+    /// Replay the main line as a new game session, ending it as recorded
+    pub fn to_session(&self, rules: GameRules) -> Result<GameSession, String> {
+        let header = |player| player_keys(player).iter().find_map(|key| self.header(key));
+        let info = GameInfo {
+            sente_name: header(Player::Sente).map(str::to_string),
+            gote_name: header(Player::Gote).map(str::to_string),
+            event: self.header("棋戦").map(str::to_string),
+            started_at: self.header("開始日時").and_then(parse_date).unwrap_or(0),
+        };
+        let mut session = GameSession::new(Some(&self.initial_sfen), None, rules, info)?;
+        for record in &self.moves {
+            let elapsed = Duration::from_millis(record.time_ms.unwrap_or(0));
+            session.play(&record.usi, elapsed)?;
+        }
+
+        let side_to_move = session.position().side_to_move();
+        match self.end {
+            Some(KifuEnd::Resign) => session.resign(side_to_move)?,
+            Some(KifuEnd::TimeUp) => session.forfeit(side_to_move, EndReason::TimeUp)?,
+            Some(KifuEnd::IllegalLoss) => session.forfeit(side_to_move, EndReason::IllegalMove)?,
+            Some(KifuEnd::IllegalWin) => {
+                session.forfeit(side_to_move.opponent(), EndReason::IllegalMove)?
+            }
+            // The session judges the declaration by its own rule
+            Some(KifuEnd::Declaration) => session.declare_win_after(Duration::ZERO)?,
+            // Checkmate and repetition are found by the session itself; impasse
+            // and interruption leave the game open
+            _ => {}
+        }
+        Ok(session)
+    }
+}
+
+/// "2024/01/31 10:00:00" for a Unix time in milliseconds, taken as UTC
+pub fn format_date(ms: u64) -> String {
+    let secs = ms / 1000;
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let time = secs % 86_400;
+    format!(
+        "{:04}/{:02}/{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Parse a date written as by `format_date`; the time of day, a weekday in
+/// parentheses and the seconds may be missing
+pub fn parse_date(text: &str) -> Option<u64> {
+    let numbers: Vec<u64> = text
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().ok())
+        .collect::<Option<_>>()?;
+    let [year, month, day, ref time @ ..] = numbers[..] else {
+        return None;
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || time.len() > 3 {
+        return None;
+    }
+    let days = days_from_civil(year as i64, month as u32, day as u32);
+    let secs = time
+        .iter()
+        .zip([3600, 60, 1])
+        .map(|(n, unit)| n * unit)
+        .sum::<u64>();
+    Some((days as u64 * 86_400 + secs) * 1000)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date of a day counted from 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Header line such as "先手：名前"
+pub(super) fn parse_header(line: &str) -> Option<(String, String)> {
+    let (key, value) = line.split_once('：')?;
+    Some((key.trim().to_string(), value.trim().to_string()))
+}
+
+/// Variation start such as "変化：12手"
+pub(super) fn parse_variation(line: &str) -> Option<usize> {
+    line.strip_prefix("変化：")?
+        .trim()
+        .strip_suffix('手')?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Pieces in hand as written on a board diagram, such as "角　歩三"
+fn format_hand(pos: &Position, player: Player) -> String {
+    let pieces: Vec<String> = PieceType::HAND
+        .iter()
+        .filter_map(|&pt| {
+            let count = pos.hand(player).count(pt) as u32;
+            match count {
+                0 => None,
+                1 => Some(piece_char(pt).to_string()),
+                _ => Some(format!("{}{}", piece_char(pt), kanji_number(count))),
+            }
+        })
+        .collect();
+    if pieces.is_empty() {
+        "なし".to_string()
+    } else {
+        pieces.join("　")
+    }
+}
+
+fn parse_hand(text: &str, player: Player, pos: &mut Position) -> Result<(), String> {
+    let text = text.trim();
+    if text == "なし" || text.is_empty() {
+        return Ok(());
+    }
+    for item in text.split(['　', ' ']).filter(|s| !s.is_empty()) {
+        let (piece_type, len) =
+            parse_piece(item).ok_or_else(|| format!("Unreadable piece in hand: {}", item))?;
+        let count = match &item[len..] {
+            "" => 1,
+            n => parse_kanji_number(n).ok_or_else(|| format!("Unreadable count: {}", item))?,
+        };
+        let hand = pos.hand_mut(player);
+        hand.set(piece_type, hand.count(piece_type) + count as u8);
+    }
+    Ok(())
+}
+
+/// Board diagram (BOD) of a position that is not a standard starting position
+pub(super) fn board_diagram(pos: &Position) -> String {
+    let mut lines = vec![
+        format!("後手の持駒：{}", format_hand(pos, Player::Gote)),
+        "  ９ ８ ７ ６ ５ ４ ３ ２ １".to_string(),
+        "+---------------------------+".to_string(),
+    ];
+    for rank in 1..=9 {
+        let mut row = String::from("|");
+        for file in (1..=9).rev() {
+            match pos.piece_at(Square::new(file, rank).unwrap()) {
+                Some(piece) => {
+                    row.push(if piece.owner == Player::Gote {
+                        'v'
+                    } else {
+                        ' '
+                    });
+                    row.push(piece_char(piece.piece_type));
+                }
+                None => row.push_str(" ・"),
+            }
+        }
+        row.push('|');
+        row.push_str(&kanji_number(rank as u32));
+        lines.push(row);
+    }
+    lines.push("+---------------------------+".to_string());
+    lines.push(format!("先手の持駒：{}", format_hand(pos, Player::Sente)));
+    if pos.side_to_move() == Player::Gote {
+        lines.push("後手番".to_string());
+    }
+    lines.join("\n")
+}
+
+/// A line of play while a record is read; variations point at the line they
+/// branch from
+struct Line {
+    parent: Option<usize>,
+    /// Ply of the first move (1 for the first move of the game)
+    start_ply: usize,
+    /// Position before each move, then the position after the last one
+    positions: Vec<Position>,
+    /// Destination of the move before the line, for 同
+    previous_to: Option<Square>,
+    moves: Vec<KifuMove>,
+    tos: Vec<Square>,
+    ended: bool,
+}
+
+impl Line {
+    fn has_ply(&self, ply: usize) -> bool {
+        ply >= self.start_ply && ply < self.start_ply + self.moves.len()
+    }
+}
+
+/// Everything KIF and KI2 have in common while reading: headers, board
+/// diagrams, comments, variations and the end of the game
+/// The format's own move lines are fed in through `push_move`
+pub(super) struct KifuReader {
+    headers: Vec<(String, String)>,
+    handicap: Option<String>,
+    board_rows: Vec<String>,
+    hands: Vec<(Player, String)>,
+    gote_to_move: bool,
+    comments: Vec<String>,
+    lines: Vec<Line>,
+    current: usize,
+    end: Option<KifuEnd>,
+}
+
+impl KifuReader {
+    pub fn new() -> Self {
+        KifuReader {
+            headers: Vec::new(),
+            handicap: None,
+            board_rows: Vec::new(),
+            hands: Vec::new(),
+            gote_to_move: false,
+            comments: Vec::new(),
+            lines: Vec::new(),
+            current: 0,
+            end: None,
+        }
+    }
+
+    /// Handle a line every format shares; false when the line is the format's
+    /// own (moves, or anything unknown)
+    pub fn read_common(&mut self, line: &str) -> Result<bool, String> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with(['#', '&']) {
+            return Ok(true);
+        }
+        if trimmed.starts_with("まで") {
+            // KI2 has no end line of its own; the summary tells how it ended
+            if self.current == 0 && self.end.is_none() {
+                self.end = parse_summary(trimmed);
+            }
+            return Ok(true);
+        }
+        if let Some(comment) = trimmed.strip_prefix('*') {
+            self.comment(comment.to_string());
+            return Ok(true);
+        }
+        if let Some(ply) = parse_variation(trimmed) {
+            self.start_variation(ply)?;
+            return Ok(true);
+        }
+        if trimmed.starts_with('|') {
+            self.board_rows.push(trimmed.to_string());
+            return Ok(true);
+        }
+        if trimmed.starts_with('+') || trimmed.starts_with("９ ８") || trimmed.starts_with("手数")
+        {
+            return Ok(true);
+        }
+        if trimmed == "後手番" || trimmed == "上手番" {
+            self.gote_to_move = true;
+            return Ok(true);
+        }
+        if trimmed == "先手番" || trimmed == "下手番" {
+            return Ok(true);
+        }
+        if let Some((key, value)) = parse_header(trimmed) {
+            match key.as_str() {
+                "手合割" => self.handicap = Some(value),
+                "先手の持駒" | "下手の持駒" => self.hands.push((Player::Sente, value)),
+                "後手の持駒" | "上手の持駒" => self.hands.push((Player::Gote, value)),
+                _ if self.lines.is_empty() => self.headers.push((key, value)),
+                // Header-like text among the moves is not a move either
+                _ => return Ok(false),
+            }
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Position the next move is played from
+    pub fn position(&mut self) -> Result<&Position, String> {
+        self.start()?;
+        Ok(self.lines[self.current].positions.last().unwrap())
+    }
+
+    /// Destination of the latest move, for 同
+    pub fn previous_to(&self) -> Option<Square> {
+        let line = self.lines.get(self.current)?;
+        line.tos.last().copied().or(line.previous_to)
+    }
+
+    /// Ply of the next move
+    pub fn next_ply(&mut self) -> Result<usize, String> {
+        self.start()?;
+        let line = &self.lines[self.current];
+        Ok(line.start_ply + line.moves.len())
+    }
+
+    /// Add a legal move to the current line
+    pub fn push_move(&mut self, mv: Move, time_ms: Option<u64>) -> Result<(), String> {
+        self.start()?;
+        let line = &mut self.lines[self.current];
+        if line.ended {
+            return Err(format!("Move after the end of the game: {}", mv.to_usi()));
+        }
+        let next = line.positions.last().unwrap().after_move(mv);
+        line.positions.push(next);
+        line.tos.push(mv.to());
+        line.moves.push(KifuMove {
+            usi: mv.to_usi(),
+            time_ms,
+            ..KifuMove::default()
+        });
+        Ok(())
+    }
+
+    /// End the current line; only the main line's end is kept
+    pub fn end(&mut self, end: KifuEnd) -> Result<(), String> {
+        self.start()?;
+        self.lines[self.current].ended = true;
+        if self.current == 0 {
+            self.end = Some(end);
+        }
+        Ok(())
+    }
+
+    fn comment(&mut self, text: String) {
+        match self.lines.get_mut(self.current) {
+            Some(line) if !line.moves.is_empty() => {
+                line.moves.last_mut().unwrap().comments.push(text)
+            }
+            _ if self.current == 0 => self.comments.push(text),
+            // A comment before a variation's first move has nothing to go with
+            _ => {}
+        }
+    }
+
+    /// Set up the initial position once the first move or variation arrives
+    fn start(&mut self) -> Result<(), String> {
+        if !self.lines.is_empty() {
+            return Ok(());
+        }
+        let position = if self.board_rows.is_empty() {
+            let name = self.handicap.as_deref().unwrap_or("平手");
+            let sfen = HANDICAPS
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, sfen)| *sfen)
+                .ok_or_else(|| format!("Unknown handicap: {}", name))?;
+            Position::from_sfen(sfen).map_err(|e| e.to_string())?
+        } else {
+            self.diagram_position()?
+        };
+        self.lines.push(Line {
+            parent: None,
+            start_ply: 1,
+            positions: vec![position],
+            previous_to: None,
+            moves: Vec::new(),
+            tos: Vec::new(),
+            ended: false,
+        });
+        Ok(())
+    }
+
+    fn diagram_position(&self) -> Result<Position, String> {
+        if self.board_rows.len() != 9 {
+            return Err(format!(
+                "A board diagram needs 9 ranks, not {}",
+                self.board_rows.len()
+            ));
+        }
+        let mut pos = Position::empty();
+        for (rank, row) in self.board_rows.iter().enumerate() {
+            let cells: Vec<char> = row.trim_start_matches('|').chars().collect();
+            for file_index in 0..9 {
+                let (Some(&mark), Some(&c)) =
+                    (cells.get(file_index * 2), cells.get(file_index * 2 + 1))
+                else {
+                    return Err(format!("Short board row: {}", row));
+                };
+                if c == '・' {
+                    continue;
+                }
+                let (piece_type, _) = parse_piece(&c.to_string())
+                    .ok_or_else(|| format!("Unreadable piece on the board: {}", c))?;
+                let owner = if mark == 'v' {
+                    Player::Gote
+                } else {
+                    Player::Sente
+                };
+                let sq = Square::new(9 - file_index as u8, rank as u8 + 1).unwrap();
+                pos.set_piece(sq, Some(Piece::new(piece_type, owner)));
+            }
+        }
+        for (player, hand) in &self.hands {
+            parse_hand(hand, *player, &mut pos)?;
+        }
+        if self.gote_to_move {
+            pos.set_side_to_move(Player::Gote);
+        }
+        // Validate through SFEN, which rejects impossible positions
+        Position::from_sfen(&pos.to_sfen()).map_err(|e| e.to_string())
+    }
+
+    /// Branch off at `ply`, from the nearest line on the way back to the main
+    /// line that has a move at that ply
+    fn start_variation(&mut self, ply: usize) -> Result<(), String> {
+        self.start()?;
+        let mut index = Some(self.current);
+        let mut parent = loop {
+            let Some(i) = index else {
+                return Err(format!(
+                    "Variation at move {} has nothing to branch from",
+                    ply
+                ));
+            };
+            if self.lines[i].has_ply(ply) {
+                break i;
+            }
+            index = self.lines[i].parent;
+        };
+        // Another alternative to a variation's first move is an alternative to
+        // the move that variation replaces
+        while self.lines[parent].start_ply == ply {
+            match self.lines[parent].parent {
+                Some(up) => parent = up,
+                None => break,
+            }
+        }
+
+        let line = &self.lines[parent];
+        let offset = ply - line.start_ply;
+        let previous_to = match offset {
+            0 => line.previous_to,
+            _ => Some(line.tos[offset - 1]),
+        };
+        self.lines.push(Line {
+            parent: Some(parent),
+            start_ply: ply,
+            positions: vec![line.positions[offset].clone()],
+            previous_to,
+            moves: Vec::new(),
+            tos: Vec::new(),
+            ended: false,
+        });
+        self.current = self.lines.len() - 1;
+        Ok(())
+    }
+
+    /// The record read so far, with variations in the order they appeared
+    pub fn finish(mut self) -> Result<Kifu, String> {
+        self.start()?;
+        let initial_sfen = self.lines[0].positions[0].to_sfen();
+        let mut lines: Vec<Option<Vec<KifuMove>>> = Vec::new();
+        // Children come after their parents, so attach from the last line back
+        let parents: Vec<(Option<usize>, usize)> =
+            self.lines.iter().map(|l| (l.parent, l.start_ply)).collect();
+        for line in &mut self.lines {
+            lines.push(Some(std::mem::take(&mut line.moves)));
+        }
+        for child in (1..lines.len()).rev() {
+            let (Some(parent), ply) = parents[child] else {
+                continue;
+            };
+            let moves = lines[child].take().unwrap();
+            if moves.is_empty() {
+                continue;
+            }
+            let offset = ply - parents[parent].1;
+            let target = lines[parent].as_mut().unwrap();
+            // Walking backwards reverses siblings; insert at the front to undo it
+            target[offset].variations.insert(0, moves);
+        }
+
+        Ok(Kifu {
+            headers: self.headers,
+            initial_sfen,
+            comments: self.comments,
+            moves: lines[0].take().unwrap(),
+            end: self.end,
+        })
+    }
+}
+
+/// A line of play to write, with the position before each move
+pub(super) struct WrittenLine<'a> {
+    pub start_ply: usize,
+    pub steps: Vec<(Position, Move, &'a KifuMove)>,
+    /// Destination of the move before the line, for 同
+    pub previous_to: Option<Square>,
+    /// Time each side had used before the line
+    pub totals_before: [u64; 2],
+}
+
+/// The main line followed by every variation, in the order KIF and KI2 list
+/// them: after each line, its branches from the last move back to the first,
+/// each followed by its own branches
+pub(super) fn lines_to_write(kifu: &Kifu) -> Result<Vec<WrittenLine<'_>>, String> {
+    let initial = Position::from_sfen(&kifu.initial_sfen).map_err(|e| e.to_string())?;
+    let mut lines = Vec::new();
+    collect_lines(&kifu.moves, 1, initial, None, [0, 0], &mut lines)?;
+    Ok(lines)
+}
+
+fn collect_lines<'a>(
+    moves: &'a [KifuMove],
+    start_ply: usize,
+    initial: Position,
+    previous_to: Option<Square>,
+    totals_before: [u64; 2],
+    out: &mut Vec<WrittenLine<'a>>,
+) -> Result<(), String> {
+    let mut steps = Vec::new();
+    let mut pos = initial;
+    let mut totals = vec![totals_before];
+    for record in moves {
+        let mv = Move::from_usi(&record.usi)?;
+        if !pos.is_legal(mv) {
+            return Err(format!("Illegal move in the record: {}", record.usi));
+        }
+        let next = pos.after_move(mv);
+        let mut total = *totals.last().unwrap();
+        total[pos.side_to_move().index()] += record.time_ms.unwrap_or(0);
+        totals.push(total);
+        steps.push((pos, mv, record));
+        pos = next;
+    }
+    let tos: Vec<Square> = steps.iter().map(|(_, mv, _)| mv.to()).collect();
+    let branches: Vec<(usize, Position)> = steps
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, _, record))| !record.variations.is_empty())
+        .map(|(i, (pos, _, _))| (i, pos.clone()))
+        .collect();
+    out.push(WrittenLine {
+        start_ply,
+        steps,
+        previous_to,
+        totals_before,
+    });
+
+    for (offset, pos) in branches.into_iter().rev() {
+        let previous_to = match offset {
+            0 => previous_to,
+            _ => Some(tos[offset - 1]),
+        };
+        for variation in &moves[offset].variations {
+            collect_lines(
+                variation,
+                start_ply + offset,
+                pos.clone(),
+                previous_to,
+                totals[offset],
+                out,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// The record's header block: headers, then 手合割 or a board diagram, with
+/// the players' names last
+pub(super) fn write_headers(kifu: &Kifu) -> Result<String, String> {
+    let is_name = |key: &str| ["先手", "後手", "下手", "上手"].contains(&key);
+    let mut lines: Vec<String> = kifu
+        .headers
+        .iter()
+        .filter(|(key, _)| !is_name(key))
+        .map(|(key, value)| format!("{}：{}", key, value))
+        .collect();
+    match handicap_name(&kifu.initial_sfen) {
+        Some(name) => lines.push(format!("手合割：{}", name)),
+        None => {
+            let pos = Position::from_sfen(&kifu.initial_sfen).map_err(|e| e.to_string())?;
+            lines.push(board_diagram(&pos));
+        }
+    }
+    lines.extend(
+        kifu.headers
+            .iter()
+            .filter(|(key, _)| is_name(key))
+            .map(|(key, value)| format!("{}：{}", key, value)),
+    );
+    Ok(lines.join("\n"))
+}
+
+/// Closing line such as "まで64手で先手の勝ち"
+pub(super) fn summary(kifu: &Kifu) -> Result<Option<String>, String> {
+    let Some(end) = kifu.end else {
+        return Ok(None);
+    };
+    let mut pos = Position::from_sfen(&kifu.initial_sfen).map_err(|e| e.to_string())?;
+    for record in &kifu.moves {
+        pos.do_move(Move::from_usi(&record.usi)?);
+    }
+    let side = pos.side_to_move();
+    let (mover, other) = (player_name(side), player_name(side.opponent()));
+    let outcome = match end {
+        KifuEnd::Resign => format!("{}の勝ち", other),
+        KifuEnd::TimeUp => format!("時間切れにより{}の勝ち", other),
+        KifuEnd::IllegalWin => format!("{}の反則勝ち", mover),
+        KifuEnd::IllegalLoss => format!("{}の反則負け", mover),
+        KifuEnd::Declaration => format!("{}の入玉勝ち", mover),
+        KifuEnd::Checkmate | KifuEnd::Repetition | KifuEnd::Impasse | KifuEnd::Interrupt => {
+            end.name().to_string()
+        }
+    };
+    Ok(Some(format!("まで{}手で{}", kifu.moves.len(), outcome)))
+}
+
+/// How the game ended, from a summary written by `summary`
+fn parse_summary(line: &str) -> Option<KifuEnd> {
+    let (_, outcome) = line.split_once("手で")?;
+    let outcome = outcome.trim();
+    let ends = [
+        ("反則勝ち", KifuEnd::IllegalWin),
+        ("反則負け", KifuEnd::IllegalLoss),
+        ("入玉勝ち", KifuEnd::Declaration),
+        ("時間切れ", KifuEnd::TimeUp),
+        ("勝ち", KifuEnd::Resign),
+    ];
+    ends.iter()
+        .find(|(word, _)| outcome.contains(word))
+        .map(|(_, end)| *end)
+        .or_else(|| KifuEnd::from_name(outcome))
+}
+
+/// Width of text in a fixed-width font, counting full-width characters as two
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum()
+}
+
+/// Pad text with spaces to `width` columns, keeping at least one space
+pub(super) fn pad(text: &str, width: usize) -> String {
+    let spaces = width.saturating_sub(display_width(text)).max(1);
+    format!("{}{}", text, " ".repeat(spaces))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameInfo, GameRules};
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(format_date(0), "1970/01/01 00:00:00");
+        let ms = parse_date("2024/02/29 13:45:06").unwrap();
+        assert_eq!(format_date(ms), "2024/02/29 13:45:06");
+        assert_eq!(parse_date("2024/02/29(木) 13:45"), Some(ms - 6000));
+        assert_eq!(parse_date("2024/02/29"), parse_date("2024/02/29 00:00:00"));
+        assert_eq!(parse_date("昭和"), None);
+    }
+
+    #[test]
+    fn test_handicap_names() {
+        assert_eq!(handicap_name(STARTPOS_SFEN), Some("平手"));
+        assert_eq!(
+            handicap_name("lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1"),
+            Some("二枚落ち")
+        );
+        assert_eq!(handicap_name("4k4/9/9/9/9/9/9/9/4K4 b - 1"), None);
+        for (_, sfen) in HANDICAPS {
+            assert!(Position::from_sfen(sfen).is_ok(), "{}", sfen);
+        }
+    }
+
+    #[test]
+    fn test_board_diagram_round_trip() {
+        let sfen = "ln1g3nl/1r1s1k3/p1ppppbpp/6p2/9/4P4/PP1P1PPPP/1BK1GS1R1/LN3G1NL w G2Ps 24";
+        let pos = Position::from_sfen(sfen).unwrap();
+        let diagram = board_diagram(&pos);
+        assert!(diagram.contains("後手の持駒：銀"));
+        assert!(diagram.contains("先手の持駒：金　歩二"));
+        assert!(diagram.ends_with("後手番"));
+
+        let mut reader = KifuReader::new();
+        for line in diagram.lines() {
+            assert!(reader.read_common(line).unwrap(), "{}", line);
+        }
+        let kifu = reader.finish().unwrap();
+        // The move number is not part of a diagram
+        assert_eq!(kifu.initial_sfen, sfen.replace(" 24", " 1"));
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let info = GameInfo {
+            sente_name: Some("先手さん".to_string()),
+            gote_name: Some("後手さん".to_string()),
+            event: None,
+            started_at: parse_date("2024/01/02 03:04:05").unwrap(),
+        };
+        let mut session = GameSession::new(None, None, GameRules::default(), info).unwrap();
+        for usi in ["7g7f", "3c3d", "8h2b+"] {
+            session.play(usi, Duration::from_secs(2)).unwrap();
+        }
+        session.resign(Player::Gote).unwrap();
+
+        let kifu = Kifu::from_session(&session);
+        assert_eq!(kifu.header("先手"), Some("先手さん"));
+        assert_eq!(kifu.header("開始日時"), Some("2024/01/02 03:04:05"));
+        assert_eq!(kifu.end, Some(KifuEnd::Resign));
+        assert_eq!(summary(&kifu).unwrap().unwrap(), "まで3手で先手の勝ち");
+        assert_eq!(parse_summary("まで3手で先手の勝ち"), Some(KifuEnd::Resign));
+        assert_eq!(parse_summary("まで80手で千日手"), Some(KifuEnd::Repetition));
+        assert_eq!(
+            parse_summary("まで9手で後手の反則負け"),
+            Some(KifuEnd::IllegalLoss)
+        );
+
+        let replayed = kifu.to_session(GameRules::default()).unwrap();
+        assert_eq!(replayed.usi_moves(), session.usi_moves());
+        assert_eq!(replayed.result(), session.result());
+        assert_eq!(replayed.snapshot().info, session.snapshot().info);
+    }
+}
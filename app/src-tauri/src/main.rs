@@ -2,6 +2,11 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod commands;
+mod engine_options;
+mod game;
+mod kifu;
+mod search;
+mod shogi;
 mod usi;
 
 use commands::*;
@@ -10,11 +15,32 @@ fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .manage(EngineState::new())
+        .manage(SessionState::new())
         .invoke_handler(tauri::generate_handler![
             init_engine,
             get_ai_move,
+            start_search,
+            wait_search,
+            stop_search,
+            set_engine_ponder,
+            solve_tsume,
+            solve_tsume_native,
             shutdown_engine,
-            is_engine_ready
+            is_engine_ready,
+            get_engine_info,
+            set_engine_option,
+            get_saved_engine_options,
+            new_game,
+            make_move,
+            undo_move,
+            resign_game,
+            declare_win,
+            get_game_state,
+            play_engine_move,
+            save_kifu,
+            load_kifu,
+            export_kifu,
+            import_kifu
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
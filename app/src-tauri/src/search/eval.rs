@@ -0,0 +1,78 @@
+// Static evaluation: material on the board and in hand
+
+use crate::shogi::{PieceType, Player, Position};
+
+/// Material value of a piece in centipawns
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Lance => 300,
+        PieceType::Knight => 400,
+        PieceType::Silver => 500,
+        PieceType::Gold => 600,
+        PieceType::Bishop => 800,
+        PieceType::Rook => 1000,
+        PieceType::King => 0,
+        PieceType::ProPawn => 600,
+        PieceType::ProLance => 600,
+        PieceType::ProKnight => 600,
+        PieceType::ProSilver => 600,
+        PieceType::Horse => 1100,
+        PieceType::Dragon => 1300,
+    }
+}
+
+/// Value of a piece held in hand (slightly more than on the board, since it can be dropped anywhere)
+fn hand_value(piece_type: PieceType) -> i32 {
+    piece_value(piece_type) * 11 / 10
+}
+
+/// Material balance (board and hands) from the player's point of view
+pub fn material_balance(pos: &Position, player: Player) -> i32 {
+    let sign = |owner: Player| if owner == player { 1 } else { -1 };
+
+    let board: i32 = pos
+        .pieces()
+        .map(|(_, p)| sign(p.owner) * piece_value(p.piece_type))
+        .sum();
+    let hands: i32 = [Player::Sente, Player::Gote]
+        .iter()
+        .flat_map(|&owner| {
+            pos.hand(owner)
+                .iter()
+                .map(move |(pt, n)| sign(owner) * hand_value(pt) * n as i32)
+        })
+        .sum();
+
+    board + hands
+}
+
+/// Static evaluation from the side to move's point of view
+pub fn evaluate(pos: &Position) -> i32 {
+    material_balance(pos, pos.side_to_move())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_startpos_is_balanced() {
+        assert_eq!(evaluate(&Position::startpos()), 0);
+    }
+
+    #[test]
+    fn test_evaluation_is_symmetric() {
+        let black = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b R 1").unwrap();
+        let white = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 w R 1").unwrap();
+        assert!(evaluate(&black) > 0);
+        assert_eq!(evaluate(&black), -evaluate(&white));
+    }
+
+    #[test]
+    fn test_promoted_piece_worth_more() {
+        let pawn = Position::from_sfen("4k4/9/9/4P4/9/9/9/9/4K4 b - 1").unwrap();
+        let tokin = Position::from_sfen("4k4/9/9/4+P4/9/9/9/9/4K4 b - 1").unwrap();
+        assert!(evaluate(&tokin) > evaluate(&pawn));
+    }
+}
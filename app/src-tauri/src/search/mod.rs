@@ -0,0 +1,11 @@
+// Built-in alpha-beta search and tsume solver, used when no external USI engine is available
+
+pub mod eval;
+pub mod searcher;
+pub mod tsume;
+pub mod tt;
+
+pub use eval::*;
+pub use searcher::*;
+pub use tsume::*;
+pub use tt::*;
@@ -0,0 +1,530 @@
+// Iterative deepening alpha-beta search with quiescence, TT and move ordering
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::eval::{evaluate, piece_value};
+use super::tt::{Bound, TranspositionTable, TtEntry};
+use crate::shogi::{Move, Position};
+use crate::usi::{Score, ThinkingInfo};
+
+/// Score of a mate at the root; mate in n plies scores MATE_SCORE - n
+pub const MATE_SCORE: i32 = 30000;
+/// Scores beyond this are mate scores
+pub const MATE_THRESHOLD: i32 = MATE_SCORE - 1000;
+/// Maximum search depth in plies
+pub const MAX_PLY: usize = 64;
+
+const INFINITY: i32 = MATE_SCORE + 1;
+/// History rows: one per origin square plus one per droppable piece type
+const HISTORY_ROWS: usize = 81 + 7;
+
+/// Limits for one search
+#[derive(Debug, Clone, Copy)]
+pub struct SearchLimits {
+    /// Time available for this move in milliseconds
+    pub time_ms: Option<u64>,
+    /// Maximum iterative deepening depth
+    pub max_depth: u32,
+    /// Node budget
+    pub nodes: Option<u64>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits {
+            time_ms: None,
+            max_depth: MAX_PLY as u32 - 1,
+            nodes: None,
+        }
+    }
+}
+
+/// Outcome of a search
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    /// None when the side to move has no legal move
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+}
+
+/// Alpha-beta searcher; keeps its transposition table and history between searches
+pub struct Searcher {
+    tt: TranspositionTable,
+    killers: [[Option<Move>; 2]; MAX_PLY],
+    history: Vec<[i32; 81]>,
+    pv_table: Vec<Vec<Move>>,
+    nodes: u64,
+    start: Instant,
+    hard_deadline: Option<Instant>,
+    node_limit: Option<u64>,
+    stop: Arc<AtomicBool>,
+    aborted: bool,
+}
+
+impl Searcher {
+    /// Create a searcher with a transposition table of `tt_mb` megabytes
+    pub fn new(tt_mb: usize) -> Self {
+        Searcher {
+            tt: TranspositionTable::new(tt_mb),
+            killers: [[None; 2]; MAX_PLY],
+            history: vec![[0; 81]; HISTORY_ROWS],
+            pv_table: vec![Vec::new(); MAX_PLY + 1],
+            nodes: 0,
+            start: Instant::now(),
+            hard_deadline: None,
+            node_limit: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            aborted: false,
+        }
+    }
+
+    /// Flag that makes a running search return as soon as possible
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// Forget everything learned in previous searches (new game)
+    pub fn clear(&mut self) {
+        self.tt.clear();
+        self.killers = [[None; 2]; MAX_PLY];
+        self.history.iter_mut().for_each(|row| *row = [0; 81]);
+    }
+
+    /// Search the position, reporting each completed iteration through `on_info`
+    pub fn search<F: FnMut(&ThinkingInfo)>(
+        &mut self,
+        pos: &Position,
+        limits: SearchLimits,
+        mut on_info: F,
+    ) -> SearchResult {
+        self.stop.store(false, Ordering::SeqCst);
+        self.aborted = false;
+        self.nodes = 0;
+        self.start = Instant::now();
+        self.node_limit = limits.nodes;
+        self.killers = [[None; 2]; MAX_PLY];
+
+        // Keep a safety margin for move transmission; do not start an
+        // iteration that is unlikely to finish before the deadline
+        let (soft_limit, hard_deadline) = match limits.time_ms {
+            Some(time_ms) => {
+                let margin = (time_ms / 10).min(100);
+                let hard = Duration::from_millis(time_ms.saturating_sub(margin).max(1));
+                (Some(hard / 2), Some(self.start + hard))
+            }
+            None => (None, None),
+        };
+        self.hard_deadline = hard_deadline;
+
+        let legal = pos.legal_moves();
+        let mut result = SearchResult {
+            best_move: legal.first().copied(),
+            score: if legal.is_empty() { -MATE_SCORE } else { 0 },
+            depth: 0,
+            nodes: 0,
+            pv: legal.first().copied().into_iter().collect(),
+        };
+        if legal.len() <= 1 {
+            return result;
+        }
+
+        let max_depth = limits.max_depth.clamp(1, MAX_PLY as u32 - 1);
+        for depth in 1..=max_depth {
+            let score = self.alpha_beta(pos, depth as i32, -INFINITY, INFINITY, 0);
+            if self.aborted {
+                break;
+            }
+
+            let pv = self.pv_table[0].clone();
+            if let Some(&best) = pv.first() {
+                result = SearchResult {
+                    best_move: Some(best),
+                    score,
+                    depth,
+                    nodes: self.nodes,
+                    pv,
+                };
+            }
+            on_info(&self.thinking_info(&result));
+
+            if score.abs() >= MATE_THRESHOLD {
+                break;
+            }
+            if soft_limit.is_some_and(|limit| self.start.elapsed() >= limit) {
+                break;
+            }
+        }
+
+        result.nodes = self.nodes;
+        result
+    }
+
+    fn thinking_info(&self, result: &SearchResult) -> ThinkingInfo {
+        let elapsed_ms = self.start.elapsed().as_millis() as u64;
+        ThinkingInfo {
+            depth: Some(result.depth),
+            score: Some(usi_score(result.score)),
+            nodes: Some(self.nodes),
+            nps: Some(self.nodes * 1000 / elapsed_ms.max(1)),
+            hashfull: Some(self.tt.hashfull()),
+            time: Some(elapsed_ms as u32),
+            pv: result.pv.iter().map(|m| m.to_usi()).collect(),
+            ..ThinkingInfo::new()
+        }
+    }
+
+    /// Check the stop flag, deadline and node budget every 1024 nodes
+    fn check_abort(&mut self) -> bool {
+        if self.aborted {
+            return true;
+        }
+        if self.nodes & 1023 == 0 {
+            let timed_out = self.hard_deadline.is_some_and(|d| Instant::now() >= d);
+            let out_of_nodes = self.node_limit.is_some_and(|n| self.nodes >= n);
+            if timed_out || out_of_nodes || self.stop.load(Ordering::Relaxed) {
+                self.aborted = true;
+            }
+        }
+        self.aborted
+    }
+
+    fn alpha_beta(
+        &mut self,
+        pos: &Position,
+        depth: i32,
+        mut alpha: i32,
+        beta: i32,
+        ply: usize,
+    ) -> i32 {
+        self.pv_table[ply].clear();
+        if self.check_abort() {
+            return 0;
+        }
+
+        let side = pos.side_to_move();
+        let in_check = pos.in_check(side);
+        // Extend checks so mating sequences are not cut off at the horizon
+        let depth = if in_check { depth + 1 } else { depth };
+
+        if depth <= 0 {
+            return self.quiescence(pos, alpha, beta, ply);
+        }
+        if ply >= MAX_PLY - 1 {
+            return evaluate(pos);
+        }
+
+        self.nodes += 1;
+        let key = pos.key();
+        let mut tt_move = None;
+        if let Some(entry) = self.tt.probe(key) {
+            tt_move = entry.best_move;
+            if ply > 0 && entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+        }
+
+        let mut moves = pos.pseudo_legal_moves();
+        self.order_moves(pos, &mut moves, tt_move, ply);
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        let mut legal_count = 0;
+
+        for mv in moves {
+            let next = pos.after_move(mv);
+            if next.in_check(side) || pos.is_uchifuzume(mv) {
+                continue;
+            }
+            legal_count += 1;
+
+            let score = -self.alpha_beta(&next, depth - 1, -beta, -alpha, ply + 1);
+            if self.aborted {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(mv);
+                if score > alpha {
+                    alpha = score;
+                    let (head, tail) = self.pv_table.split_at_mut(ply + 1);
+                    head[ply].clear();
+                    head[ply].push(mv);
+                    head[ply].extend_from_slice(&tail[0]);
+
+                    if score >= beta {
+                        if !is_capture(pos, mv) {
+                            self.record_quiet_cutoff(mv, depth, ply);
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+
+        // In shogi having no legal move is a loss whether or not in check
+        if legal_count == 0 {
+            return -MATE_SCORE + ply as i32;
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(TtEntry {
+            key,
+            depth,
+            score: score_to_tt(best_score, ply),
+            bound,
+            best_move,
+        });
+
+        best_score
+    }
+
+    fn quiescence(&mut self, pos: &Position, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        self.pv_table[ply].clear();
+        self.nodes += 1;
+        if self.check_abort() {
+            return 0;
+        }
+
+        let stand_pat = evaluate(pos);
+        if stand_pat >= beta || ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let side = pos.side_to_move();
+        let mut moves = pos.pseudo_legal_captures();
+        self.order_moves(pos, &mut moves, None, ply);
+
+        for mv in moves {
+            let next = pos.after_move(mv);
+            if next.in_check(side) {
+                continue;
+            }
+            let score = -self.quiescence(&next, -beta, -alpha, ply + 1);
+            if self.aborted {
+                return 0;
+            }
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+
+        alpha
+    }
+
+    /// Order moves: TT move, captures by MVV-LVA, killers, then history
+    fn order_moves(&self, pos: &Position, moves: &mut [Move], tt_move: Option<Move>, ply: usize) {
+        moves.sort_by_cached_key(|&mv| {
+            let score = if Some(mv) == tt_move {
+                1_000_000
+            } else if let Some(victim) = captured_piece_value(pos, mv) {
+                let attacker = mv
+                    .from()
+                    .and_then(|sq| pos.piece_at(sq))
+                    .map_or(0, |p| piece_value(p.piece_type));
+                100_000 + victim * 10 - attacker / 10
+            } else if self.killers[ply][0] == Some(mv) {
+                90_000
+            } else if self.killers[ply][1] == Some(mv) {
+                89_000
+            } else {
+                self.history[history_row(mv)][mv.to().index()].min(80_000)
+            };
+            let promotion_bonus = if mv.is_promotion() { 1 } else { 0 };
+            std::cmp::Reverse(score * 2 + promotion_bonus)
+        });
+    }
+
+    fn record_quiet_cutoff(&mut self, mv: Move, depth: i32, ply: usize) {
+        if self.killers[ply][0] != Some(mv) {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = Some(mv);
+        }
+        let entry = &mut self.history[history_row(mv)][mv.to().index()];
+        *entry = (*entry + depth * depth).min(80_000);
+    }
+}
+
+impl Default for Searcher {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+fn is_capture(pos: &Position, mv: Move) -> bool {
+    !mv.is_drop() && pos.piece_at(mv.to()).is_some()
+}
+
+fn captured_piece_value(pos: &Position, mv: Move) -> Option<i32> {
+    if mv.is_drop() {
+        return None;
+    }
+    pos.piece_at(mv.to()).map(|p| piece_value(p.piece_type))
+}
+
+fn history_row(mv: Move) -> usize {
+    match mv {
+        Move::Normal { from, .. } => from.index(),
+        Move::Drop { piece_type, .. } => 81 + piece_type.hand_index().unwrap_or(0),
+    }
+}
+
+/// Convert a search score to a USI score, turning mate scores into ply distances
+pub fn usi_score(score: i32) -> Score {
+    if score >= MATE_THRESHOLD {
+        Score::Mate(MATE_SCORE - score)
+    } else if score <= -MATE_THRESHOLD {
+        Score::Mate(-(MATE_SCORE + score))
+    } else {
+        Score::Cp(score)
+    }
+}
+
+/// Mate scores are stored relative to the node so they stay valid at other plies
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_THRESHOLD {
+        score + ply as i32
+    } else if score <= -MATE_THRESHOLD {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_THRESHOLD {
+        score - ply as i32
+    } else if score <= -MATE_THRESHOLD {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search_depth(sfen: &str, depth: u32) -> SearchResult {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let mut searcher = Searcher::new(1);
+        let limits = SearchLimits {
+            max_depth: depth,
+            ..SearchLimits::default()
+        };
+        searcher.search(&pos, limits, |_| {})
+    }
+
+    #[test]
+    fn test_finds_mate_in_one() {
+        // Gold drop on 5b is protected by the silver on 5c
+        let result = search_depth("4k4/9/4S4/9/9/9/9/9/K8 b G 1", 3);
+        assert_eq!(result.best_move.unwrap().to_usi(), "G*5b");
+        assert_eq!(result.score, MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn test_captures_hanging_rook() {
+        let result = search_depth("4k4/9/9/9/4r4/9/9/4R4/K8 b - 1", 3);
+        assert_eq!(result.best_move.unwrap().to_usi(), "5h5e");
+        assert!(result.score > 500);
+    }
+
+    #[test]
+    fn test_avoids_losing_rook() {
+        // Gote's rook is attacked by the bishop and must not stay en prise
+        let result = search_depth("4k4/9/9/9/4r4/9/2B6/9/K8 w - 1", 2);
+        let best = result.best_move.unwrap();
+        assert_eq!(best.from().unwrap().to_usi(), "5e");
+    }
+
+    #[test]
+    fn test_no_legal_moves_returns_none() {
+        let result = search_depth("4k4/4G4/4S4/9/9/9/9/9/K8 w - 1", 3);
+        assert!(result.best_move.is_none());
+        assert_eq!(result.score, -MATE_SCORE);
+    }
+
+    #[test]
+    fn test_info_reports_each_depth() {
+        let pos = Position::startpos();
+        let mut searcher = Searcher::new(1);
+        let mut depths = Vec::new();
+        let limits = SearchLimits {
+            max_depth: 3,
+            ..SearchLimits::default()
+        };
+        let result = searcher.search(&pos, limits, |info| {
+            depths.push(info.depth.unwrap());
+            assert!(!info.pv.is_empty());
+        });
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert!(pos.is_legal(result.best_move.unwrap()));
+        assert_eq!(result.pv[0], result.best_move.unwrap());
+    }
+
+    #[test]
+    fn test_respects_time_limit() {
+        let pos = Position::startpos();
+        let mut searcher = Searcher::new(1);
+        let limits = SearchLimits {
+            time_ms: Some(200),
+            ..SearchLimits::default()
+        };
+        let start = Instant::now();
+        let result = searcher.search(&pos, limits, |_| {});
+        assert!(start.elapsed() < Duration::from_millis(400));
+        assert!(pos.is_legal(result.best_move.unwrap()));
+    }
+
+    #[test]
+    fn test_stop_flag_aborts_search() {
+        let pos = Position::startpos();
+        let mut searcher = Searcher::new(1);
+        let stop = searcher.stop_flag();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            stop.store(true, Ordering::SeqCst);
+        });
+        let start = Instant::now();
+        let result = searcher.search(&pos, SearchLimits::default(), |_| {});
+        handle.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn test_usi_score() {
+        assert_eq!(usi_score(MATE_SCORE - 3), Score::Mate(3));
+        assert_eq!(usi_score(-MATE_SCORE + 2), Score::Mate(-2));
+        assert_eq!(usi_score(-150), Score::Cp(-150));
+    }
+
+    #[test]
+    fn test_mate_score_tt_round_trip() {
+        let score = MATE_SCORE - 5;
+        assert_eq!(score_from_tt(score_to_tt(score, 3), 3), score);
+        assert_eq!(score_from_tt(score_to_tt(-score, 3), 3), -score);
+        assert_eq!(score_to_tt(120, 3), 120);
+    }
+}
@@ -0,0 +1,457 @@
+// Df-pn (depth-first proof-number) search for tsume-shogi (mating problems)
+// The attacker, who is to move at the root, may only play checks; the defender
+// answers with every legal evasion. Iterative deepening on the mate length makes
+// the first mate found the shortest one
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::shogi::{Move, Position};
+
+/// Proof and disproof numbers at or above this are infinite
+const INFINITE: u32 = u32::MAX / 4;
+
+/// Default node budget of one solve
+pub const DEFAULT_TSUME_NODES: u64 = 1_000_000;
+/// Default longest mate looked for, in plies
+pub const DEFAULT_TSUME_PLIES: u32 = 31;
+
+/// Limits for one solve
+#[derive(Debug, Clone, Copy)]
+pub struct TsumeLimits {
+    /// Positions expanded before giving up
+    pub max_nodes: u64,
+    /// Longest mate to look for, in plies
+    pub max_plies: u32,
+    /// Time available in milliseconds
+    pub time_ms: Option<u64>,
+}
+
+impl Default for TsumeLimits {
+    fn default() -> Self {
+        TsumeLimits {
+            max_nodes: DEFAULT_TSUME_NODES,
+            max_plies: DEFAULT_TSUME_PLIES,
+            time_ms: None,
+        }
+    }
+}
+
+/// Answer to a mating problem
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TsumeOutcome {
+    /// Shortest mate: the attacker's checks and the defender's longest resistance
+    Mate(Vec<Move>),
+    /// The attacker has no forced mate
+    NoMate,
+    /// No mate within the given number of plies; a longer one may exist
+    NoMateWithin(u32),
+    /// The node budget or time ran out, or the solve was stopped
+    Unknown,
+}
+
+/// Outcome of a solve
+#[derive(Debug, Clone)]
+pub struct TsumeSolution {
+    pub outcome: TsumeOutcome,
+    /// Positions expanded
+    pub nodes: u64,
+}
+
+/// Proof and disproof numbers of a position searched with a number of plies left
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    pn: u32,
+    dn: u32,
+    /// Plies to mate once proven
+    mate_len: u32,
+    /// First move of the mating line once proven
+    best_move: Option<Move>,
+}
+
+impl Entry {
+    const UNKNOWN: Entry = Entry {
+        pn: 1,
+        dn: 1,
+        mate_len: 0,
+        best_move: None,
+    };
+    const DISPROVEN: Entry = Entry {
+        pn: INFINITE,
+        dn: 0,
+        mate_len: 0,
+        best_move: None,
+    };
+    const MATED: Entry = Entry {
+        pn: 0,
+        dn: INFINITE,
+        mate_len: 0,
+        best_move: None,
+    };
+}
+
+/// Proof numbers of a position derived from its children
+struct Evaluation {
+    pn: u32,
+    dn: u32,
+    /// Child to expand next
+    best: usize,
+    /// Runner-up's proof number (attacker) or disproof number (defender)
+    second: u32,
+    /// Child continuing the mating line, and its mate length, once proven
+    mate: Option<(usize, u32)>,
+}
+
+/// Df-pn solver; keeps nothing between solves
+pub struct TsumeSolver {
+    table: HashMap<(u64, u32), Entry>,
+    path: Vec<u64>,
+    nodes: u64,
+    node_limit: u64,
+    deadline: Option<Instant>,
+    stop: Arc<AtomicBool>,
+    aborted: bool,
+    /// Set when a position was given up on because no plies were left
+    depth_cut: bool,
+}
+
+impl TsumeSolver {
+    pub fn new() -> Self {
+        Self::with_stop_flag(Arc::new(AtomicBool::new(false)))
+    }
+
+    /// Solver that ends when `stop` is set, e.g. shared with a `Searcher`
+    pub fn with_stop_flag(stop: Arc<AtomicBool>) -> Self {
+        TsumeSolver {
+            table: HashMap::new(),
+            path: Vec::new(),
+            nodes: 0,
+            node_limit: DEFAULT_TSUME_NODES,
+            deadline: None,
+            stop,
+            aborted: false,
+            depth_cut: false,
+        }
+    }
+
+    /// Flag that makes a running solve return as soon as possible
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// Solve the problem with the side to move as the attacker
+    pub fn solve(&mut self, pos: &Position, limits: TsumeLimits) -> TsumeSolution {
+        self.stop.store(false, Ordering::SeqCst);
+        self.aborted = false;
+        self.nodes = 0;
+        self.node_limit = limits.max_nodes;
+        self.deadline = limits
+            .time_ms
+            .map(|time_ms| Instant::now() + Duration::from_millis(time_ms));
+
+        let outcome = self.deepen(pos, limits.max_plies);
+        self.table = HashMap::new();
+        TsumeSolution {
+            outcome,
+            nodes: self.nodes,
+        }
+    }
+
+    /// Look for mates of 1, 3, 5... plies until one is found or disproven outright
+    fn deepen(&mut self, pos: &Position, max_plies: u32) -> TsumeOutcome {
+        let root = pos.key();
+        for plies in (1..=max_plies).step_by(2) {
+            self.table.clear();
+            self.path.clear();
+            self.depth_cut = false;
+            self.mid(pos, plies, true, INFINITE, INFINITE);
+
+            let entry = self.entry(root, plies);
+            if entry.pn == 0 {
+                return TsumeOutcome::Mate(self.mating_line(pos, plies));
+            }
+            if self.aborted || entry.dn != 0 {
+                return TsumeOutcome::Unknown;
+            }
+            // A disproof that never ran out of plies holds for any length
+            if !self.depth_cut {
+                return TsumeOutcome::NoMate;
+            }
+        }
+        TsumeOutcome::NoMateWithin(max_plies)
+    }
+
+    /// Expand `pos` until its proof or disproof number reaches its threshold
+    fn mid(&mut self, pos: &Position, plies: u32, attacker: bool, th_pn: u32, th_dn: u32) {
+        let key = pos.key();
+        self.nodes += 1;
+        if self.out_of_budget() {
+            self.aborted = true;
+            return;
+        }
+        if attacker && plies == 0 {
+            self.depth_cut = true;
+            self.table.insert((key, plies), Entry::DISPROVEN);
+            return;
+        }
+
+        let moves = if attacker {
+            pos.checking_moves()
+        } else {
+            pos.legal_moves()
+        };
+        if moves.is_empty() {
+            // An attacker without checks has failed; a defender without moves is mated
+            let entry = if attacker {
+                Entry::DISPROVEN
+            } else {
+                Entry::MATED
+            };
+            self.table.insert((key, plies), entry);
+            return;
+        }
+        if plies == 0 {
+            self.depth_cut = true;
+            self.table.insert((key, plies), Entry::DISPROVEN);
+            return;
+        }
+
+        let children: Vec<(Move, Position)> = moves
+            .into_iter()
+            .map(|mv| (mv, pos.after_move(mv)))
+            .collect();
+        let keys: Vec<u64> = children.iter().map(|(_, child)| child.key()).collect();
+
+        self.path.push(key);
+        let eval = loop {
+            let eval = self.evaluate(&keys, plies - 1, attacker);
+            if eval.pn >= th_pn || eval.dn >= th_dn || self.aborted {
+                break eval;
+            }
+
+            let child = self.entry_at(keys[eval.best], plies - 1);
+            let (child_pn, child_dn) = if attacker {
+                (
+                    th_pn.min(eval.second.saturating_add(1)),
+                    add(th_dn - eval.dn, child.dn),
+                )
+            } else {
+                (
+                    add(th_pn - eval.pn, child.pn),
+                    th_dn.min(eval.second.saturating_add(1)),
+                )
+            };
+            let next = &children[eval.best].1;
+            self.mid(next, plies - 1, !attacker, child_pn, child_dn);
+        };
+        self.path.pop();
+
+        let (mate_len, best_move) = match eval.mate {
+            Some((index, len)) if eval.pn == 0 => (len + 1, Some(children[index].0)),
+            _ => (0, None),
+        };
+        let entry = Entry {
+            pn: eval.pn,
+            dn: eval.dn,
+            mate_len,
+            best_move,
+        };
+        self.table.insert((key, plies), entry);
+    }
+
+    /// Combine the children's numbers: the attacker needs one mating check,
+    /// the defender must be mated after every evasion
+    fn evaluate(&self, keys: &[u64], plies: u32, attacker: bool) -> Evaluation {
+        let mut eval = Evaluation {
+            pn: if attacker { INFINITE } else { 0 },
+            dn: if attacker { 0 } else { INFINITE },
+            best: 0,
+            second: INFINITE,
+            mate: None,
+        };
+
+        for (index, &key) in keys.iter().enumerate() {
+            let child = self.entry_at(key, plies);
+            // The attacker looks for the quickest proven mate, the defender for the slowest
+            if child.pn == 0 {
+                let better = match eval.mate {
+                    None => true,
+                    Some((_, len)) if attacker => child.mate_len < len,
+                    Some((_, len)) => child.mate_len > len,
+                };
+                if better {
+                    eval.mate = Some((index, child.mate_len));
+                }
+            }
+
+            let (own, other) = if attacker {
+                (child.pn, child.dn)
+            } else {
+                (child.dn, child.pn)
+            };
+            let best = if attacker { eval.pn } else { eval.dn };
+            if own < best {
+                eval.second = best;
+                eval.best = index;
+            } else if own < eval.second {
+                eval.second = own;
+            }
+            if attacker {
+                eval.pn = eval.pn.min(own);
+                eval.dn = add(eval.dn, other);
+            } else {
+                eval.dn = eval.dn.min(own);
+                eval.pn = add(eval.pn, other);
+            }
+        }
+        eval
+    }
+
+    /// Numbers of a child position; repeating a position never leads to mate
+    fn entry_at(&self, key: u64, plies: u32) -> Entry {
+        if self.path.contains(&key) {
+            return Entry::DISPROVEN;
+        }
+        self.entry(key, plies)
+    }
+
+    fn entry(&self, key: u64, plies: u32) -> Entry {
+        self.table
+            .get(&(key, plies))
+            .copied()
+            .unwrap_or(Entry::UNKNOWN)
+    }
+
+    /// Follow the proven moves from the root
+    fn mating_line(&self, pos: &Position, plies: u32) -> Vec<Move> {
+        let mut line = Vec::new();
+        let mut pos = pos.clone();
+        let mut plies = plies;
+        while let Some(mv) = self.entry(pos.key(), plies).best_move {
+            line.push(mv);
+            pos.do_move(mv);
+            plies -= 1;
+        }
+        line
+    }
+
+    /// Check the node budget, and the deadline and stop flag every 256 nodes
+    fn out_of_budget(&self) -> bool {
+        if self.aborted || self.nodes > self.node_limit {
+            return true;
+        }
+        if self.nodes & 255 == 0 {
+            let timed_out = self.deadline.is_some_and(|d| Instant::now() >= d);
+            return timed_out || self.stop.load(Ordering::Relaxed);
+        }
+        false
+    }
+}
+
+impl Default for TsumeSolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Add proof numbers, saturating at infinity
+fn add(a: u32, b: u32) -> u32 {
+    a.saturating_add(b).min(INFINITE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shogi::STARTPOS_SFEN;
+
+    /// Problems bundled with the solver: (sfen, plies to mate or None, first move)
+    fn problems() -> Vec<(&'static str, Option<u32>, &'static str)> {
+        include_str!("tsume_problems.txt")
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+                (fields[0], fields[1].parse().ok(), fields[2])
+            })
+            .collect()
+    }
+
+    /// Play the line and check it is legal, all checks, and ends in mate
+    fn assert_mates(sfen: &str, line: &[Move]) {
+        let mut pos = Position::from_sfen(sfen).unwrap();
+        for (ply, &mv) in line.iter().enumerate() {
+            assert!(pos.is_legal(mv), "{}: illegal {}", sfen, mv.to_usi());
+            pos.do_move(mv);
+            if ply % 2 == 0 {
+                assert!(
+                    pos.in_check(pos.side_to_move()),
+                    "{}: {}",
+                    sfen,
+                    mv.to_usi()
+                );
+            }
+        }
+        assert!(pos.is_checkmate(), "{}: line does not mate", sfen);
+    }
+
+    #[test]
+    fn test_bundled_problems() {
+        let mut solver = TsumeSolver::new();
+        for (sfen, plies, first_move) in problems() {
+            let pos = Position::from_sfen(sfen).unwrap();
+            let solution = solver.solve(&pos, TsumeLimits::default());
+            match (plies, solution.outcome) {
+                (Some(plies), TsumeOutcome::Mate(line)) => {
+                    assert_eq!(line.len() as u32, plies, "{}", sfen);
+                    assert_eq!(line[0].to_usi(), first_move, "{}", sfen);
+                    assert_mates(sfen, &line);
+                }
+                (None, TsumeOutcome::NoMate) => {}
+                (_, outcome) => panic!("{}: unexpected {:?}", sfen, outcome),
+            }
+        }
+    }
+
+    #[test]
+    fn test_shortest_mate_is_found() {
+        // A second gold allows more checks, but G*5b mates at once
+        let pos = Position::from_sfen("4k4/9/4P4/9/9/9/9/9/9 b 2G 1").unwrap();
+        let solution = TsumeSolver::new().solve(&pos, TsumeLimits::default());
+        assert_eq!(
+            solution.outcome,
+            TsumeOutcome::Mate(vec![Move::from_usi("G*5b").unwrap()])
+        );
+    }
+
+    #[test]
+    fn test_no_mate_within_plies() {
+        let pos = Position::from_sfen("6k2/9/9/6B2/9/9/9/9/9 b RS 1").unwrap();
+        let limits = TsumeLimits {
+            max_plies: 5,
+            ..TsumeLimits::default()
+        };
+        let solution = TsumeSolver::new().solve(&pos, limits);
+        assert_eq!(solution.outcome, TsumeOutcome::NoMateWithin(5));
+    }
+
+    #[test]
+    fn test_node_budget() {
+        let pos = Position::from_sfen("6k2/9/9/6B2/9/9/9/9/9 b RS 1").unwrap();
+        let limits = TsumeLimits {
+            max_nodes: 50,
+            ..TsumeLimits::default()
+        };
+        let solution = TsumeSolver::new().solve(&pos, limits);
+        assert_eq!(solution.outcome, TsumeOutcome::Unknown);
+        assert!(solution.nodes <= 51);
+    }
+
+    #[test]
+    fn test_attacker_without_checks() {
+        let pos = Position::from_sfen(STARTPOS_SFEN).unwrap();
+        let solution = TsumeSolver::new().solve(&pos, TsumeLimits::default());
+        assert_eq!(solution.outcome, TsumeOutcome::NoMate);
+    }
+}
@@ -0,0 +1,111 @@
+// Transposition table keyed by Zobrist hash
+
+use crate::shogi::Move;
+
+/// Kind of score stored in an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TtEntry {
+    pub key: u64,
+    pub depth: i32,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<Move>,
+}
+
+/// Fixed-size, always-replace-if-deeper hash table
+pub struct TranspositionTable {
+    entries: Vec<Option<TtEntry>>,
+    mask: usize,
+}
+
+impl TranspositionTable {
+    /// Create a table using roughly `size_mb` megabytes
+    pub fn new(size_mb: usize) -> Self {
+        let entry_size = std::mem::size_of::<Option<TtEntry>>();
+        let wanted = (size_mb.max(1) * 1024 * 1024 / entry_size).max(1);
+        // Round down to a power of two so the index is a mask
+        let len = 1usize << (usize::BITS - 1 - wanted.leading_zeros());
+        TranspositionTable {
+            entries: vec![None; len],
+            mask: len - 1,
+        }
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        self.entries[key as usize & self.mask].filter(|e| e.key == key)
+    }
+
+    pub fn store(&mut self, entry: TtEntry) {
+        let slot = &mut self.entries[entry.key as usize & self.mask];
+        let replace = match slot {
+            None => true,
+            Some(old) => old.key != entry.key || entry.depth >= old.depth,
+        };
+        if replace {
+            *slot = Some(entry);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|e| *e = None);
+    }
+
+    /// Table usage in permille, sampled from the first thousand slots
+    pub fn hashfull(&self) -> u32 {
+        let sample = self.entries.len().min(1000);
+        let used = self.entries[..sample]
+            .iter()
+            .filter(|e| e.is_some())
+            .count();
+        (used * 1000 / sample) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_probe() {
+        let mut tt = TranspositionTable::new(1);
+        let mv = Move::from_usi("7g7f").unwrap();
+        tt.store(TtEntry {
+            key: 12345,
+            depth: 3,
+            score: 50,
+            bound: Bound::Exact,
+            best_move: Some(mv),
+        });
+
+        let entry = tt.probe(12345).unwrap();
+        assert_eq!(entry.score, 50);
+        assert_eq!(entry.best_move, Some(mv));
+        assert!(tt.probe(54321).is_none());
+    }
+
+    #[test]
+    fn test_shallower_entry_does_not_replace() {
+        let mut tt = TranspositionTable::new(1);
+        let entry = |depth, score| TtEntry {
+            key: 7,
+            depth,
+            score,
+            bound: Bound::Exact,
+            best_move: None,
+        };
+        tt.store(entry(5, 100));
+        tt.store(entry(2, -100));
+        assert_eq!(tt.probe(7).unwrap().score, 100);
+
+        tt.clear();
+        assert!(tt.probe(7).is_none());
+        assert_eq!(tt.hashfull(), 0);
+    }
+}
@@ -0,0 +1,225 @@
+// Entering-king (nyugyoku) declarations under the CSA 27-point rule or the
+// 24-point rule
+// The side to move may declare instead of moving when its king has entered the
+// promotion zone with enough material behind it; the clock is the caller's concern
+
+use serde::{Deserialize, Serialize};
+
+use super::position::Position;
+use super::types::{PieceType, Player};
+
+/// Pieces other than the king the declaring side needs in the promotion zone
+pub const DECLARATION_MIN_PIECES: usize = 10;
+
+/// Points sente needs to declare under the 27-point rule; gote, moving second,
+/// needs one fewer
+const SENTE_POINTS: u32 = 28;
+const GOTE_POINTS: u32 = 27;
+
+/// Points either side needs to declare under the 24-point rule, and to win
+/// rather than draw
+const DRAW_POINTS: u32 = 24;
+const WIN_POINTS: u32 = 31;
+
+/// Points each side needs when an impasse (jishogi) is settled by counting
+pub const IMPASSE_POINTS: u32 = 24;
+
+/// Declaration rule of a game
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeclarationRule {
+    /// CSA rule, used by USI "bestmove win": 28 points for sente, 27 for gote
+    #[default]
+    Points27,
+    /// 31 points win and 24 to 30 points draw, for either side
+    Points24,
+}
+
+/// What a valid declaration achieves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Declaration {
+    Win,
+    Draw,
+}
+
+/// Points of a piece: 5 for rooks and bishops, promoted or not, 1 for the
+/// other pieces, and nothing for the king
+pub fn declaration_value(piece_type: PieceType) -> u32 {
+    match piece_type.unpromote() {
+        PieceType::Rook | PieceType::Bishop => 5,
+        PieceType::King => 0,
+        _ => 1,
+    }
+}
+
+/// Points `player` counts for a declaration: their pieces in the promotion
+/// zone plus every piece in hand
+pub fn declaration_points(pos: &Position, player: Player) -> u32 {
+    let on_board: u32 = pos
+        .pieces()
+        .filter(|(sq, p)| p.owner == player && sq.in_promotion_zone(player))
+        .map(|(_, p)| declaration_value(p.piece_type))
+        .sum();
+    on_board + hand_points(pos, player)
+}
+
+/// Points of all `player`'s pieces, wherever they stand, plus every piece in
+/// hand; an impasse is settled by these
+pub fn material_points(pos: &Position, player: Player) -> u32 {
+    let on_board: u32 = pos
+        .pieces()
+        .filter(|(_, p)| p.owner == player)
+        .map(|(_, p)| declaration_value(p.piece_type))
+        .sum();
+    on_board + hand_points(pos, player)
+}
+
+fn hand_points(pos: &Position, player: Player) -> u32 {
+    pos.hand(player)
+        .iter()
+        .map(|(pt, count)| declaration_value(pt) * count as u32)
+        .sum()
+}
+
+/// Judge a declaration by the side to move: its king must be in the promotion
+/// zone and not in check, with at least ten other pieces in the zone and the
+/// points `rule` asks for
+/// None when the declaration is not allowed
+pub fn judge_declaration(pos: &Position, rule: DeclarationRule) -> Option<Declaration> {
+    let player = pos.side_to_move();
+    let king = pos.king_square(player)?;
+    if !king.in_promotion_zone(player) || pos.in_check(player) {
+        return None;
+    }
+
+    let in_zone = pos
+        .pieces()
+        .filter(|(sq, p)| {
+            p.owner == player && p.piece_type != PieceType::King && sq.in_promotion_zone(player)
+        })
+        .count();
+    if in_zone < DECLARATION_MIN_PIECES {
+        return None;
+    }
+
+    let points = declaration_points(pos, player);
+    match rule {
+        DeclarationRule::Points27 => {
+            let needed = match player {
+                Player::Sente => SENTE_POINTS,
+                Player::Gote => GOTE_POINTS,
+            };
+            (points >= needed).then_some(Declaration::Win)
+        }
+        DeclarationRule::Points24 if points >= WIN_POINTS => Some(Declaration::Win),
+        DeclarationRule::Points24 if points >= DRAW_POINTS => Some(Declaration::Draw),
+        DeclarationRule::Points24 => None,
+    }
+}
+
+/// Whether the side to move may declare and win under `rule`
+pub fn can_declare_win(pos: &Position, rule: DeclarationRule) -> bool {
+    judge_declaration(pos, rule) == Some(Declaration::Win)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sente king on 5a with two dragons, two horses and eight pawns in the zone:
+    /// 4 big pieces (20) + 8 small (8) = 28 points from 12 pieces
+    const DECLARABLE: &str = "+R+B+B+RK4/PPPPPPPP1/9/9/9/9/9/9/4k4 b - 1";
+
+    #[test]
+    fn test_declaration_points() {
+        let pos = Position::from_sfen(DECLARABLE).unwrap();
+        assert_eq!(declaration_points(&pos, Player::Sente), 28);
+        assert_eq!(declaration_points(&pos, Player::Gote), 0);
+
+        // Hands count wherever the king stands
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b RB3Pg 1").unwrap();
+        assert_eq!(declaration_points(&pos, Player::Sente), 13);
+        assert_eq!(declaration_points(&pos, Player::Gote), 1);
+    }
+
+    #[test]
+    fn test_material_points() {
+        // The standard position is worth 27 points to each side
+        let pos = Position::startpos();
+        assert_eq!(material_points(&pos, Player::Sente), 27);
+        assert_eq!(material_points(&pos, Player::Gote), 27);
+
+        let pos = Position::from_sfen(DECLARABLE).unwrap();
+        assert_eq!(material_points(&pos, Player::Sente), 28);
+    }
+
+    #[test]
+    fn test_can_declare_win() {
+        assert!(can_declare_win(
+            &Position::from_sfen(DECLARABLE).unwrap(),
+            DeclarationRule::Points27
+        ));
+
+        // One point short
+        let pos = Position::from_sfen("+R+B+B+RK4/PPPPPPP2/9/9/9/9/9/9/4k4 b - 1").unwrap();
+        assert!(!can_declare_win(&pos, DeclarationRule::Points27));
+
+        // Enough points, but only nine pieces in the zone
+        let pos = Position::from_sfen("+R+B+B+RK4/PPPPP4/9/9/9/9/9/9/4k4 b 3P 1").unwrap();
+        assert_eq!(declaration_points(&pos, Player::Sente), 28);
+        assert!(!can_declare_win(&pos, DeclarationRule::Points27));
+    }
+
+    #[test]
+    fn test_king_outside_zone_or_in_check() {
+        let pos = Position::from_sfen("+R+B+B+R5/PPPPPPPP1/9/4K4/9/9/9/9/4k4 b - 1").unwrap();
+        assert!(!can_declare_win(&pos, DeclarationRule::Points27));
+
+        // Enough points and pieces, but a gote gold on 5b checks the king
+        let pos = Position::from_sfen("+R+B+B+RK4/PPPPgPPPP/9/9/9/9/9/9/4k4 b - 1").unwrap();
+        assert_eq!(declaration_points(&pos, Player::Sente), 28);
+        assert!(pos.in_check(Player::Sente));
+        assert!(!can_declare_win(&pos, DeclarationRule::Points27));
+    }
+
+    #[test]
+    fn test_gote_needs_27_points() {
+        // Mirror of a 27-point sente setup, which is enough for gote only
+        let pos = Position::from_sfen("4K4/9/9/9/9/9/9/ppppppp2/+r+b+b+rk4 w - 1").unwrap();
+        assert_eq!(declaration_points(&pos, Player::Gote), 27);
+        assert!(can_declare_win(&pos, DeclarationRule::Points27));
+
+        let pos = Position::from_sfen("+R+B+B+RK4/PPPPPPP2/9/9/9/9/9/9/4k4 b - 1").unwrap();
+        assert_eq!(declaration_points(&pos, Player::Sente), 27);
+        assert!(!can_declare_win(&pos, DeclarationRule::Points27));
+    }
+
+    #[test]
+    fn test_24_point_rule() {
+        let rule = DeclarationRule::Points24;
+        // 28 points: a draw under the 24-point rule
+        let pos = Position::from_sfen(DECLARABLE).unwrap();
+        assert_eq!(judge_declaration(&pos, rule), Some(Declaration::Draw));
+        assert!(!can_declare_win(&pos, rule));
+
+        // 31 points with three pawns in hand
+        let pos = Position::from_sfen("+R+B+B+RK4/PPPPPPPP1/9/9/9/9/9/9/4k4 b 3P 1").unwrap();
+        assert_eq!(judge_declaration(&pos, rule), Some(Declaration::Win));
+
+        // 23 points is one short of a draw; one more pawn in hand makes it
+        let pos = Position::from_sfen("+R+BK6/PPPPPPPPP/9/9/9/9/9/9/4k4 b 4P 1").unwrap();
+        assert_eq!(declaration_points(&pos, Player::Sente), 23);
+        assert_eq!(judge_declaration(&pos, rule), None);
+        let pos = Position::from_sfen("+R+BK6/PPPPPPPPP/9/9/9/9/9/9/4k4 b 5P 1").unwrap();
+        assert_eq!(judge_declaration(&pos, rule), Some(Declaration::Draw));
+        assert_eq!(judge_declaration(&pos, DeclarationRule::Points27), None);
+    }
+
+    #[test]
+    fn test_rule_serialization() {
+        let rule: DeclarationRule = serde_json::from_str("\"points24\"").unwrap();
+        assert_eq!(rule, DeclarationRule::Points24);
+        assert_eq!(DeclarationRule::default(), DeclarationRule::Points27);
+    }
+}
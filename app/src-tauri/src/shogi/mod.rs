@@ -0,0 +1,16 @@
+// Shogi rules core: positions, moves and legal move generation
+
+pub mod declaration;
+pub mod movegen;
+pub mod moves;
+pub mod position;
+pub mod sfen;
+pub mod types;
+pub mod zobrist;
+
+pub use declaration::*;
+pub use movegen::*;
+pub use moves::*;
+pub use position::*;
+pub use sfen::*;
+pub use types::*;
@@ -0,0 +1,562 @@
+// Pseudo-legal and legal move generation, including drops and special rules
+
+use serde::{Deserialize, Serialize};
+
+use super::moves::Move;
+use super::position::{oriented, piece_directions, Position};
+use super::types::{Piece, PieceType, Player, Square};
+
+/// Whether a piece may or must promote on a move (mirrors the frontend `PromotionStatus`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PromotionStatus {
+    Required,
+    Optional,
+    Forbidden,
+}
+
+/// Whether a piece of this type on this square would have no further moves
+/// (pawn/lance on the last rank, knight on the last two ranks)
+pub fn is_dead_square(piece_type: PieceType, sq: Square, player: Player) -> bool {
+    let rank = sq.relative_rank(player);
+    match piece_type {
+        PieceType::Pawn | PieceType::Lance => rank == 1,
+        PieceType::Knight => rank <= 2,
+        _ => false,
+    }
+}
+
+/// Promotion status of a piece moving from `from` to `to`
+pub fn promotion_status(piece: Piece, from: Square, to: Square) -> PromotionStatus {
+    if !piece.piece_type.can_promote() {
+        return PromotionStatus::Forbidden;
+    }
+    if !from.in_promotion_zone(piece.owner) && !to.in_promotion_zone(piece.owner) {
+        return PromotionStatus::Forbidden;
+    }
+    if is_dead_square(piece.piece_type, to, piece.owner) {
+        PromotionStatus::Required
+    } else {
+        PromotionStatus::Optional
+    }
+}
+
+impl Position {
+    /// Destination squares reachable by the piece on `from`, ignoring checks
+    pub fn piece_targets(&self, from: Square) -> Vec<Square> {
+        let Some(piece) = self.piece_at(from) else {
+            return Vec::new();
+        };
+
+        let (steps, slides) = piece_directions(piece.piece_type);
+        let mut targets = Vec::new();
+
+        for &dir in steps {
+            let (dfile, drank) = oriented(dir, piece.owner);
+            if let Some(to) = from.offset(dfile, drank) {
+                if self.piece_at(to).is_none_or(|p| p.owner != piece.owner) {
+                    targets.push(to);
+                }
+            }
+        }
+
+        for &dir in slides {
+            let (dfile, drank) = oriented(dir, piece.owner);
+            let mut current = from;
+            while let Some(to) = current.offset(dfile, drank) {
+                match self.piece_at(to) {
+                    None => targets.push(to),
+                    Some(p) => {
+                        if p.owner != piece.owner {
+                            targets.push(to);
+                        }
+                        break;
+                    }
+                }
+                current = to;
+            }
+        }
+
+        targets
+    }
+
+    /// Whether dropping a pawn on the file would be nifu (two unpromoted pawns on one file)
+    pub fn is_nifu(&self, file: u8, player: Player) -> bool {
+        (1..=9).any(|rank| {
+            self.piece_at(Square::new(file, rank).unwrap())
+                == Some(Piece::new(PieceType::Pawn, player))
+        })
+    }
+
+    /// Moves of the side to move that obey piece movement, promotion and drop
+    /// rules, but may leave the king in check or be uchifuzume
+    pub fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let side = self.side_to_move();
+        let mut moves = Vec::new();
+
+        for (from, piece) in self.pieces() {
+            if piece.owner != side {
+                continue;
+            }
+            for to in self.piece_targets(from) {
+                match promotion_status(piece, from, to) {
+                    PromotionStatus::Required => {
+                        moves.push(Move::Normal {
+                            from,
+                            to,
+                            promote: true,
+                        });
+                    }
+                    PromotionStatus::Optional => {
+                        moves.push(Move::Normal {
+                            from,
+                            to,
+                            promote: true,
+                        });
+                        moves.push(Move::Normal {
+                            from,
+                            to,
+                            promote: false,
+                        });
+                    }
+                    PromotionStatus::Forbidden => {
+                        moves.push(Move::Normal {
+                            from,
+                            to,
+                            promote: false,
+                        });
+                    }
+                }
+            }
+        }
+
+        let hand = self.hand(side);
+        for (piece_type, _) in hand.iter() {
+            for to in Square::all() {
+                if self.piece_at(to).is_some() || is_dead_square(piece_type, to, side) {
+                    continue;
+                }
+                if piece_type == PieceType::Pawn && self.is_nifu(to.file(), side) {
+                    continue;
+                }
+                moves.push(Move::Drop { piece_type, to });
+            }
+        }
+
+        moves
+    }
+
+    /// Pseudo-legal moves that capture an opponent piece (no drops)
+    pub fn pseudo_legal_captures(&self) -> Vec<Move> {
+        let side = self.side_to_move();
+        let mut moves = Vec::new();
+
+        for (from, piece) in self.pieces() {
+            if piece.owner != side {
+                continue;
+            }
+            for to in self.piece_targets(from) {
+                if self.piece_at(to).is_none() {
+                    continue;
+                }
+                let status = promotion_status(piece, from, to);
+                if status != PromotionStatus::Forbidden {
+                    moves.push(Move::Normal {
+                        from,
+                        to,
+                        promote: true,
+                    });
+                }
+                if status != PromotionStatus::Required {
+                    moves.push(Move::Normal {
+                        from,
+                        to,
+                        promote: false,
+                    });
+                }
+            }
+        }
+
+        moves
+    }
+
+    /// Whether a pseudo-legal move is legal: it does not leave the mover's
+    /// king in check and is not a checkmating pawn drop (uchifuzume)
+    pub fn is_legal_pseudo(&self, mv: Move) -> bool {
+        let side = self.side_to_move();
+        let next = self.after_move(mv);
+        if next.in_check(side) {
+            return false;
+        }
+        !self.is_uchifuzume(mv)
+    }
+
+    /// Whether the move is a pawn drop that checkmates the opponent (打ち歩詰め)
+    pub fn is_uchifuzume(&self, mv: Move) -> bool {
+        let Move::Drop {
+            piece_type: PieceType::Pawn,
+            to,
+        } = mv
+        else {
+            return false;
+        };
+        let side = self.side_to_move();
+        let opponent = side.opponent();
+
+        // A pawn only gives check when dropped directly in front of the king
+        let (_, forward) = oriented((0, -1), side);
+        if to.offset(0, forward) != self.king_square(opponent) {
+            return false;
+        }
+
+        let next = self.after_move(mv);
+        !next.has_legal_move()
+    }
+
+    /// All legal moves for the side to move
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.pseudo_legal_moves()
+            .into_iter()
+            .filter(|&mv| self.is_legal_pseudo(mv))
+            .collect()
+    }
+
+    /// Legal moves that give check (the attacker's moves in tsume)
+    pub fn checking_moves(&self) -> Vec<Move> {
+        let opponent = self.side_to_move().opponent();
+        self.pseudo_legal_moves()
+            .into_iter()
+            .filter(|&mv| self.after_move(mv).in_check(opponent) && self.is_legal_pseudo(mv))
+            .collect()
+    }
+
+    /// Whether the side to move has at least one legal move
+    pub fn has_legal_move(&self) -> bool {
+        self.pseudo_legal_moves()
+            .into_iter()
+            .any(|mv| self.is_legal_pseudo(mv))
+    }
+
+    /// Whether the move is legal in this position
+    pub fn is_legal(&self, mv: Move) -> bool {
+        self.pseudo_legal_moves().contains(&mv) && self.is_legal_pseudo(mv)
+    }
+
+    /// Whether the side to move is checkmated
+    pub fn is_checkmate(&self) -> bool {
+        self.in_check(self.side_to_move()) && !self.has_legal_move()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sq(file: u8, rank: u8) -> Square {
+        Square::new(file, rank).unwrap()
+    }
+
+    fn put(pos: &mut Position, file: u8, rank: u8, piece_type: PieceType, owner: Player) {
+        pos.set_piece(sq(file, rank), Some(Piece::new(piece_type, owner)));
+    }
+
+    fn targets(pos: &Position, file: u8, rank: u8) -> Vec<Square> {
+        let mut t = pos.piece_targets(sq(file, rank));
+        t.sort();
+        t
+    }
+
+    #[test]
+    fn test_startpos_has_30_moves() {
+        let pos = Position::startpos();
+        assert_eq!(pos.legal_moves().len(), 30);
+    }
+
+    #[test]
+    fn test_pawn_moves_forward() {
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 5, PieceType::Pawn, Player::Sente);
+        put(&mut pos, 4, 5, PieceType::Pawn, Player::Gote);
+        assert_eq!(targets(&pos, 5, 5), vec![sq(5, 4)]);
+        assert_eq!(targets(&pos, 4, 5), vec![sq(4, 6)]);
+    }
+
+    #[test]
+    fn test_knight_jumps() {
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 5, PieceType::Knight, Player::Sente);
+        put(&mut pos, 4, 4, PieceType::Gold, Player::Gote);
+        assert_eq!(targets(&pos, 5, 5), vec![sq(4, 3), sq(6, 3)]);
+    }
+
+    #[test]
+    fn test_silver_and_gold_patterns() {
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 5, PieceType::Silver, Player::Sente);
+        assert_eq!(targets(&pos, 5, 5).len(), 5);
+        assert!(!targets(&pos, 5, 5).contains(&sq(5, 6)));
+
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 5, PieceType::Gold, Player::Gote);
+        let t = targets(&pos, 5, 5);
+        assert_eq!(t.len(), 6);
+        assert!(t.contains(&sq(5, 4)));
+        assert!(!t.contains(&sq(4, 4)));
+    }
+
+    #[test]
+    fn test_sliding_pieces_blocked() {
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 5, PieceType::Rook, Player::Sente);
+        put(&mut pos, 5, 3, PieceType::Pawn, Player::Sente);
+        put(&mut pos, 3, 5, PieceType::Pawn, Player::Gote);
+        let t = targets(&pos, 5, 5);
+        assert!(t.contains(&sq(5, 4)));
+        assert!(!t.contains(&sq(5, 3)));
+        assert!(t.contains(&sq(3, 5)));
+        assert!(!t.contains(&sq(2, 5)));
+        assert_eq!(t.len(), 1 + 2 + 4 + 4);
+    }
+
+    #[test]
+    fn test_promoted_pieces() {
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 5, PieceType::Horse, Player::Sente);
+        assert_eq!(targets(&pos, 5, 5).len(), 16 + 4);
+
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 5, PieceType::Dragon, Player::Sente);
+        assert_eq!(targets(&pos, 5, 5).len(), 16 + 4);
+    }
+
+    #[test]
+    fn test_promotion_status() {
+        let pawn = Piece::new(PieceType::Pawn, Player::Sente);
+        assert_eq!(
+            promotion_status(pawn, sq(5, 5), sq(5, 4)),
+            PromotionStatus::Forbidden
+        );
+        assert_eq!(
+            promotion_status(pawn, sq(5, 4), sq(5, 3)),
+            PromotionStatus::Optional
+        );
+        assert_eq!(
+            promotion_status(pawn, sq(5, 2), sq(5, 1)),
+            PromotionStatus::Required
+        );
+
+        let knight = Piece::new(PieceType::Knight, Player::Gote);
+        assert_eq!(
+            promotion_status(knight, sq(5, 6), sq(4, 8)),
+            PromotionStatus::Required
+        );
+        assert_eq!(
+            promotion_status(knight, sq(5, 5), sq(4, 7)),
+            PromotionStatus::Optional
+        );
+
+        let silver = Piece::new(PieceType::Silver, Player::Sente);
+        // Moving out of the zone still allows promotion
+        assert_eq!(
+            promotion_status(silver, sq(5, 3), sq(4, 4)),
+            PromotionStatus::Optional
+        );
+
+        let gold = Piece::new(PieceType::Gold, Player::Sente);
+        assert_eq!(
+            promotion_status(gold, sq(5, 4), sq(5, 3)),
+            PromotionStatus::Forbidden
+        );
+    }
+
+    #[test]
+    fn test_forced_promotion_in_generated_moves() {
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 2, PieceType::Pawn, Player::Sente);
+        let moves = pos.legal_moves();
+        assert_eq!(
+            moves,
+            vec![Move::Normal {
+                from: sq(5, 2),
+                to: sq(5, 1),
+                promote: true
+            }]
+        );
+    }
+
+    #[test]
+    fn test_must_not_leave_king_in_check() {
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 9, PieceType::King, Player::Sente);
+        put(&mut pos, 5, 8, PieceType::Gold, Player::Sente);
+        put(&mut pos, 5, 1, PieceType::Rook, Player::Gote);
+
+        // The gold is pinned along the file and may only move on it
+        let gold_moves: Vec<_> = pos
+            .legal_moves()
+            .into_iter()
+            .filter(|m| m.from() == Some(sq(5, 8)))
+            .collect();
+        assert_eq!(
+            gold_moves,
+            vec![Move::Normal {
+                from: sq(5, 8),
+                to: sq(5, 7),
+                promote: false
+            }]
+        );
+    }
+
+    #[test]
+    fn test_drops_respect_dead_squares() {
+        let mut pos = Position::empty();
+        pos.hand_mut(Player::Sente).add(PieceType::Knight);
+        let drops = pos.legal_moves();
+        assert_eq!(drops.len(), 81 - 18);
+        assert!(drops.iter().all(|m| m.to().rank() >= 3));
+
+        let mut pos = Position::empty();
+        pos.set_side_to_move(Player::Gote);
+        pos.hand_mut(Player::Gote).add(PieceType::Lance);
+        assert!(pos.legal_moves().iter().all(|m| m.to().rank() <= 8));
+    }
+
+    #[test]
+    fn test_pseudo_legal_captures() {
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 5, PieceType::Rook, Player::Sente);
+        put(&mut pos, 5, 2, PieceType::Pawn, Player::Gote);
+        put(&mut pos, 2, 5, PieceType::Gold, Player::Gote);
+        pos.hand_mut(Player::Sente).add(PieceType::Pawn);
+
+        let mut captures: Vec<String> = pos
+            .pseudo_legal_captures()
+            .iter()
+            .map(|m| m.to_usi())
+            .collect();
+        captures.sort();
+        assert_eq!(captures, vec!["5e2e", "5e5b", "5e5b+"]);
+    }
+
+    #[test]
+    fn test_nifu() {
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 7, PieceType::Pawn, Player::Sente);
+        put(&mut pos, 4, 7, PieceType::ProPawn, Player::Sente);
+        put(&mut pos, 3, 3, PieceType::Pawn, Player::Gote);
+        pos.hand_mut(Player::Sente).add(PieceType::Pawn);
+
+        assert!(pos.is_nifu(5, Player::Sente));
+        assert!(!pos.is_nifu(4, Player::Sente));
+        assert!(!pos.is_nifu(3, Player::Sente));
+
+        let drop_5e = Move::Drop {
+            piece_type: PieceType::Pawn,
+            to: sq(5, 5),
+        };
+        let drop_4e = Move::Drop {
+            piece_type: PieceType::Pawn,
+            to: sq(4, 5),
+        };
+        assert!(!pos.is_legal(drop_5e));
+        assert!(pos.is_legal(drop_4e));
+    }
+
+    #[test]
+    fn test_uchifuzume_is_illegal() {
+        // Gote king in the corner; the gold covers 2a/2b and the knight protects 1b
+        let mut pos = Position::empty();
+        put(&mut pos, 1, 1, PieceType::King, Player::Gote);
+        put(&mut pos, 3, 2, PieceType::Gold, Player::Sente);
+        put(&mut pos, 2, 4, PieceType::Knight, Player::Sente);
+        put(&mut pos, 9, 9, PieceType::King, Player::Sente);
+        pos.hand_mut(Player::Sente).add(PieceType::Pawn);
+
+        let drop = Move::Drop {
+            piece_type: PieceType::Pawn,
+            to: sq(1, 2),
+        };
+        assert!(pos.is_uchifuzume(drop));
+        assert!(!pos.is_legal(drop));
+        assert!(!pos.legal_moves().contains(&drop));
+
+        // The same drop is fine once the king has an escape square
+        pos.set_piece(sq(3, 2), None);
+        assert!(!pos.is_uchifuzume(drop));
+        assert!(pos.is_legal(drop));
+    }
+
+    #[test]
+    fn test_pawn_drop_that_can_be_captured_is_not_uchifuzume() {
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 1, PieceType::King, Player::Gote);
+        put(&mut pos, 6, 1, PieceType::Gold, Player::Gote);
+        put(&mut pos, 5, 9, PieceType::King, Player::Sente);
+        pos.hand_mut(Player::Sente).add(PieceType::Pawn);
+
+        let drop = Move::Drop {
+            piece_type: PieceType::Pawn,
+            to: sq(5, 2),
+        };
+        assert!(!pos.is_uchifuzume(drop));
+        assert!(pos.is_legal(drop));
+    }
+
+    #[test]
+    fn test_moving_pawn_mate_is_allowed() {
+        let mut pos = Position::empty();
+        put(&mut pos, 1, 1, PieceType::King, Player::Gote);
+        put(&mut pos, 3, 2, PieceType::Gold, Player::Sente);
+        put(&mut pos, 2, 4, PieceType::Knight, Player::Sente);
+        put(&mut pos, 1, 3, PieceType::Pawn, Player::Sente);
+
+        let push = Move::Normal {
+            from: sq(1, 3),
+            to: sq(1, 2),
+            promote: false,
+        };
+        assert!(pos.is_legal(push));
+        assert!(pos.after_move(push).is_checkmate());
+    }
+
+    #[test]
+    fn test_checkmate_detection() {
+        // Head-gold mate: gold on 5b protected by a silver
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 1, PieceType::King, Player::Gote);
+        put(&mut pos, 5, 2, PieceType::Gold, Player::Sente);
+        put(&mut pos, 5, 3, PieceType::Silver, Player::Sente);
+        pos.set_side_to_move(Player::Gote);
+        assert!(pos.is_checkmate());
+
+        pos.set_piece(sq(5, 3), None);
+        assert!(!pos.is_checkmate());
+    }
+
+    #[test]
+    fn test_checking_moves() {
+        let mut pos = Position::empty();
+        put(&mut pos, 5, 1, PieceType::King, Player::Gote);
+        put(&mut pos, 5, 3, PieceType::Pawn, Player::Sente);
+        put(&mut pos, 9, 9, PieceType::King, Player::Sente);
+        pos.hand_mut(Player::Sente).add(PieceType::Gold);
+
+        let mut checks: Vec<String> = pos.checking_moves().iter().map(|mv| mv.to_usi()).collect();
+        checks.sort();
+        // Gold drops next to the king, and the pawn push with or without promotion
+        assert_eq!(
+            checks,
+            vec!["5c5b", "5c5b+", "G*4a", "G*4b", "G*5b", "G*6a", "G*6b"]
+        );
+    }
+
+    #[test]
+    fn test_is_legal_rejects_garbage() {
+        let pos = Position::startpos();
+        assert!(pos.is_legal(Move::from_usi("7g7f").unwrap()));
+        assert!(!pos.is_legal(Move::from_usi("7g7e").unwrap()));
+        assert!(!pos.is_legal(Move::from_usi("7g7f+").unwrap()));
+        assert!(!pos.is_legal(Move::from_usi("3c3d").unwrap()));
+        assert!(!pos.is_legal(Move::from_usi("P*5e").unwrap()));
+    }
+}
@@ -0,0 +1,170 @@
+// Move representation with USI notation round-tripping
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::types::{PieceType, Square};
+
+/// A move: either a board move or a drop from hand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Move {
+    Normal {
+        from: Square,
+        to: Square,
+        promote: bool,
+    },
+    Drop {
+        piece_type: PieceType,
+        to: Square,
+    },
+}
+
+impl Move {
+    /// Destination square
+    pub fn to(self) -> Square {
+        match self {
+            Move::Normal { to, .. } | Move::Drop { to, .. } => to,
+        }
+    }
+
+    /// Origin square (None for drops)
+    pub fn from(self) -> Option<Square> {
+        match self {
+            Move::Normal { from, .. } => Some(from),
+            Move::Drop { .. } => None,
+        }
+    }
+
+    pub fn is_drop(self) -> bool {
+        matches!(self, Move::Drop { .. })
+    }
+
+    pub fn is_promotion(self) -> bool {
+        matches!(self, Move::Normal { promote: true, .. })
+    }
+
+    /// Parse a USI move such as "7g7f", "8h2b+" or "P*5e"
+    pub fn from_usi(s: &str) -> Result<Move, String> {
+        let invalid = || format!("Invalid USI move: {}", s);
+
+        if let Some((piece, square)) = s.split_once('*') {
+            let mut chars = piece.chars();
+            let piece_type = match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_uppercase() => {
+                    PieceType::from_usi_char(c).ok_or_else(invalid)?
+                }
+                _ => return Err(invalid()),
+            };
+            if piece_type == PieceType::King {
+                return Err(invalid());
+            }
+            let to = Square::from_usi(square).ok_or_else(invalid)?;
+            return Ok(Move::Drop { piece_type, to });
+        }
+
+        let (body, promote) = match s.strip_suffix('+') {
+            Some(body) => (body, true),
+            None => (s, false),
+        };
+        if body.len() != 4 || !body.is_ascii() {
+            return Err(invalid());
+        }
+        let from = Square::from_usi(&body[0..2]).ok_or_else(invalid)?;
+        let to = Square::from_usi(&body[2..4]).ok_or_else(invalid)?;
+        if from == to {
+            return Err(invalid());
+        }
+
+        Ok(Move::Normal { from, to, promote })
+    }
+
+    /// USI notation of the move
+    pub fn to_usi(self) -> String {
+        match self {
+            Move::Normal { from, to, promote } => {
+                format!(
+                    "{}{}{}",
+                    from.to_usi(),
+                    to.to_usi(),
+                    if promote { "+" } else { "" }
+                )
+            }
+            Move::Drop { piece_type, to } => {
+                format!("{}*{}", piece_type.usi_char(), to.to_usi())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_usi())
+    }
+}
+
+impl FromStr for Move {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Move::from_usi(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_normal_move() {
+        let mv = Move::from_usi("7g7f").unwrap();
+        assert_eq!(
+            mv,
+            Move::Normal {
+                from: Square::new(7, 7).unwrap(),
+                to: Square::new(7, 6).unwrap(),
+                promote: false,
+            }
+        );
+        assert_eq!(mv.to_usi(), "7g7f");
+    }
+
+    #[test]
+    fn test_parse_promotion() {
+        let mv = Move::from_usi("7g7f+").unwrap();
+        assert!(mv.is_promotion());
+        assert_eq!(mv.to_string(), "7g7f+");
+    }
+
+    #[test]
+    fn test_parse_drop() {
+        let mv: Move = "P*5e".parse().unwrap();
+        assert_eq!(
+            mv,
+            Move::Drop {
+                piece_type: PieceType::Pawn,
+                to: Square::new(5, 5).unwrap(),
+            }
+        );
+        assert_eq!(mv.to_usi(), "P*5e");
+        assert!(mv.is_drop());
+        assert_eq!(mv.from(), None);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for s in [
+            "", "7g", "7g7", "7g7g", "0a1a", "7g7j", "K*5e", "p*5e", "P*", "PP*5e", "7g7f++",
+            "resign",
+        ] {
+            assert!(Move::from_usi(s).is_err(), "{} should be rejected", s);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_all_drops() {
+        for c in ['R', 'B', 'G', 'S', 'N', 'L', 'P'] {
+            let s = format!("{}*1a", c);
+            assert_eq!(Move::from_usi(&s).unwrap().to_usi(), s);
+        }
+    }
+}
@@ -0,0 +1,412 @@
+// Position: board, pieces in hand, side to move and move number
+
+use super::moves::Move;
+use super::types::{Piece, PieceType, Player, Square};
+
+/// Pieces in hand for one player, counted per hand piece type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Hand {
+    counts: [u8; 7],
+}
+
+impl Hand {
+    pub fn new() -> Self {
+        Hand { counts: [0; 7] }
+    }
+
+    /// Number of pieces of the given type (promoted types count as their base type)
+    pub fn count(&self, piece_type: PieceType) -> u8 {
+        piece_type
+            .unpromote()
+            .hand_index()
+            .map_or(0, |i| self.counts[i])
+    }
+
+    /// Set the number of pieces of the given type
+    pub fn set(&mut self, piece_type: PieceType, count: u8) {
+        if let Some(i) = piece_type.unpromote().hand_index() {
+            self.counts[i] = count;
+        }
+    }
+
+    /// Add one piece (a captured piece goes to hand unpromoted)
+    pub fn add(&mut self, piece_type: PieceType) {
+        if let Some(i) = piece_type.unpromote().hand_index() {
+            self.counts[i] += 1;
+        }
+    }
+
+    /// Remove one piece, returning false if none was held
+    pub fn remove(&mut self, piece_type: PieceType) -> bool {
+        match piece_type.unpromote().hand_index() {
+            Some(i) if self.counts[i] > 0 => {
+                self.counts[i] -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.iter().all(|&c| c == 0)
+    }
+
+    /// Piece types held, with counts, in SFEN order
+    pub fn iter(&self) -> impl Iterator<Item = (PieceType, u8)> + '_ {
+        PieceType::HAND
+            .iter()
+            .zip(self.counts.iter())
+            .filter(|(_, &c)| c > 0)
+            .map(|(&pt, &c)| (pt, c))
+    }
+}
+
+/// Direction as (file delta, rank delta)
+pub(crate) type Direction = (i8, i8);
+
+/// Movement of a piece in sente's frame: single steps and sliding directions
+/// ("forward" for sente is rank - 1)
+pub(crate) fn piece_directions(
+    piece_type: PieceType,
+) -> (&'static [Direction], &'static [Direction]) {
+    const GOLD: &[(i8, i8)] = &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (0, 1)];
+    const SILVER: &[(i8, i8)] = &[(-1, -1), (0, -1), (1, -1), (-1, 1), (1, 1)];
+    const KING: &[(i8, i8)] = &[
+        (-1, -1),
+        (0, -1),
+        (1, -1),
+        (-1, 0),
+        (1, 0),
+        (-1, 1),
+        (0, 1),
+        (1, 1),
+    ];
+    const DIAGONAL: &[(i8, i8)] = &[(-1, -1), (1, -1), (-1, 1), (1, 1)];
+    const ORTHOGONAL: &[(i8, i8)] = &[(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+    match piece_type {
+        PieceType::Pawn => (&[(0, -1)], &[]),
+        PieceType::Lance => (&[], &[(0, -1)]),
+        PieceType::Knight => (&[(-1, -2), (1, -2)], &[]),
+        PieceType::Silver => (SILVER, &[]),
+        PieceType::Gold
+        | PieceType::ProPawn
+        | PieceType::ProLance
+        | PieceType::ProKnight
+        | PieceType::ProSilver => (GOLD, &[]),
+        PieceType::Bishop => (&[], DIAGONAL),
+        PieceType::Rook => (&[], ORTHOGONAL),
+        PieceType::King => (KING, &[]),
+        PieceType::Horse => (ORTHOGONAL, DIAGONAL),
+        PieceType::Dragon => (DIAGONAL, ORTHOGONAL),
+    }
+}
+
+/// Orient a sente-frame direction for the given player
+pub(crate) fn oriented((dfile, drank): Direction, player: Player) -> Direction {
+    match player {
+        Player::Sente => (dfile, drank),
+        Player::Gote => (dfile, -drank),
+    }
+}
+
+/// A complete game position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    board: [Option<Piece>; 81],
+    hands: [Hand; 2],
+    side_to_move: Player,
+    ply: u32,
+}
+
+impl Position {
+    /// An empty board with sente to move
+    pub fn empty() -> Self {
+        Position {
+            board: [None; 81],
+            hands: [Hand::new(); 2],
+            side_to_move: Player::Sente,
+            ply: 1,
+        }
+    }
+
+    /// The standard starting position
+    pub fn startpos() -> Self {
+        let mut pos = Position::empty();
+        let back_rank = [
+            PieceType::Lance,
+            PieceType::Knight,
+            PieceType::Silver,
+            PieceType::Gold,
+            PieceType::King,
+            PieceType::Gold,
+            PieceType::Silver,
+            PieceType::Knight,
+            PieceType::Lance,
+        ];
+
+        for (i, &pt) in back_rank.iter().enumerate() {
+            let file = i as u8 + 1;
+            pos.set_piece(
+                Square::new(file, 1).unwrap(),
+                Some(Piece::new(pt, Player::Gote)),
+            );
+            pos.set_piece(
+                Square::new(file, 9).unwrap(),
+                Some(Piece::new(pt, Player::Sente)),
+            );
+            pos.set_piece(
+                Square::new(file, 3).unwrap(),
+                Some(Piece::new(PieceType::Pawn, Player::Gote)),
+            );
+            pos.set_piece(
+                Square::new(file, 7).unwrap(),
+                Some(Piece::new(PieceType::Pawn, Player::Sente)),
+            );
+        }
+
+        pos.set_piece(
+            Square::new(8, 2).unwrap(),
+            Some(Piece::new(PieceType::Rook, Player::Gote)),
+        );
+        pos.set_piece(
+            Square::new(2, 2).unwrap(),
+            Some(Piece::new(PieceType::Bishop, Player::Gote)),
+        );
+        pos.set_piece(
+            Square::new(2, 8).unwrap(),
+            Some(Piece::new(PieceType::Rook, Player::Sente)),
+        );
+        pos.set_piece(
+            Square::new(8, 8).unwrap(),
+            Some(Piece::new(PieceType::Bishop, Player::Sente)),
+        );
+
+        pos
+    }
+
+    pub fn piece_at(&self, sq: Square) -> Option<Piece> {
+        self.board[sq.index()]
+    }
+
+    pub fn set_piece(&mut self, sq: Square, piece: Option<Piece>) {
+        self.board[sq.index()] = piece;
+    }
+
+    pub fn hand(&self, player: Player) -> &Hand {
+        &self.hands[player.index()]
+    }
+
+    pub fn hand_mut(&mut self, player: Player) -> &mut Hand {
+        &mut self.hands[player.index()]
+    }
+
+    pub fn side_to_move(&self) -> Player {
+        self.side_to_move
+    }
+
+    pub fn set_side_to_move(&mut self, player: Player) {
+        self.side_to_move = player;
+    }
+
+    /// Move number as written in SFEN (1 for the first move of the game)
+    pub fn ply(&self) -> u32 {
+        self.ply
+    }
+
+    pub fn set_ply(&mut self, ply: u32) {
+        self.ply = ply;
+    }
+
+    /// Iterate over occupied squares
+    pub fn pieces(&self) -> impl Iterator<Item = (Square, Piece)> + '_ {
+        Square::all().filter_map(move |sq| self.piece_at(sq).map(|p| (sq, p)))
+    }
+
+    /// Square of the player's king, if present
+    pub fn king_square(&self, player: Player) -> Option<Square> {
+        self.pieces()
+            .find(|(_, p)| p.piece_type == PieceType::King && p.owner == player)
+            .map(|(sq, _)| sq)
+    }
+
+    /// Whether any piece of `by` attacks the square
+    pub fn is_attacked(&self, sq: Square, by: Player) -> bool {
+        // Knights jump, so check them separately from the rays
+        for (dfile, drank) in [(-1, -2), (1, -2)] {
+            let (dfile, drank) = oriented((dfile, drank), by);
+            if let Some(from) = sq.offset(-dfile, -drank) {
+                if self.piece_at(from) == Some(Piece::new(PieceType::Knight, by)) {
+                    return true;
+                }
+            }
+        }
+
+        for &(dfile, drank) in piece_directions(PieceType::King).0 {
+            let mut current = sq;
+            let mut distance = 0;
+            while let Some(next) = current.offset(dfile, drank) {
+                current = next;
+                distance += 1;
+                let Some(piece) = self.piece_at(current) else {
+                    continue;
+                };
+                if piece.owner == by {
+                    // The attacker moves from `current` back towards `sq`
+                    let toward = oriented((-dfile, -drank), by);
+                    let (steps, slides) = piece_directions(piece.piece_type);
+                    if slides.contains(&toward) || (distance == 1 && steps.contains(&toward)) {
+                        return true;
+                    }
+                }
+                break;
+            }
+        }
+
+        false
+    }
+
+    /// Whether the player's king is in check (false if the player has no king)
+    pub fn in_check(&self, player: Player) -> bool {
+        self.king_square(player)
+            .is_some_and(|sq| self.is_attacked(sq, player.opponent()))
+    }
+
+    /// Play a move without legality checks, returning the captured piece
+    /// The move must at least be pseudo-legal for the side to move
+    pub fn do_move(&mut self, mv: Move) -> Option<Piece> {
+        let side = self.side_to_move;
+        let captured = match mv {
+            Move::Normal { from, to, promote } => {
+                let piece = self.board[from.index()]
+                    .take()
+                    .expect("do_move called with an empty origin square");
+                let captured = self.board[to.index()];
+                if let Some(c) = captured {
+                    self.hands[side.index()].add(c.piece_type);
+                }
+                let piece_type = if promote {
+                    piece.piece_type.promote()
+                } else {
+                    piece.piece_type
+                };
+                self.board[to.index()] = Some(Piece::new(piece_type, piece.owner));
+                captured
+            }
+            Move::Drop { piece_type, to } => {
+                self.hands[side.index()].remove(piece_type);
+                self.board[to.index()] = Some(Piece::new(piece_type, side));
+                None
+            }
+        };
+
+        self.side_to_move = side.opponent();
+        self.ply += 1;
+        captured
+    }
+
+    /// Copy of the position after the move
+    pub fn after_move(&self, mv: Move) -> Position {
+        let mut next = self.clone();
+        next.do_move(mv);
+        next
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self::startpos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sq(file: u8, rank: u8) -> Square {
+        Square::new(file, rank).unwrap()
+    }
+
+    #[test]
+    fn test_startpos_layout() {
+        let pos = Position::startpos();
+        assert_eq!(pos.pieces().count(), 40);
+        assert_eq!(
+            pos.piece_at(sq(5, 9)),
+            Some(Piece::new(PieceType::King, Player::Sente))
+        );
+        assert_eq!(
+            pos.piece_at(sq(2, 8)),
+            Some(Piece::new(PieceType::Rook, Player::Sente))
+        );
+        assert_eq!(
+            pos.piece_at(sq(8, 2)),
+            Some(Piece::new(PieceType::Rook, Player::Gote))
+        );
+        assert_eq!(pos.side_to_move(), Player::Sente);
+        assert_eq!(pos.ply(), 1);
+    }
+
+    #[test]
+    fn test_hand_counts() {
+        let mut hand = Hand::new();
+        assert!(hand.is_empty());
+        hand.add(PieceType::Dragon);
+        hand.add(PieceType::Pawn);
+        hand.add(PieceType::Pawn);
+        assert_eq!(hand.count(PieceType::Rook), 1);
+        assert_eq!(hand.count(PieceType::Pawn), 2);
+        assert!(hand.remove(PieceType::Pawn));
+        assert!(!hand.remove(PieceType::Gold));
+        let held: Vec<_> = hand.iter().collect();
+        assert_eq!(held, vec![(PieceType::Rook, 1), (PieceType::Pawn, 1)]);
+    }
+
+    #[test]
+    fn test_do_move_capture_goes_to_hand() {
+        let mut pos = Position::empty();
+        pos.set_piece(sq(5, 5), Some(Piece::new(PieceType::Rook, Player::Sente)));
+        pos.set_piece(sq(5, 2), Some(Piece::new(PieceType::Horse, Player::Gote)));
+
+        let captured = pos.do_move(Move::Normal {
+            from: sq(5, 5),
+            to: sq(5, 2),
+            promote: true,
+        });
+
+        assert_eq!(captured, Some(Piece::new(PieceType::Horse, Player::Gote)));
+        assert_eq!(pos.hand(Player::Sente).count(PieceType::Bishop), 1);
+        assert_eq!(
+            pos.piece_at(sq(5, 2)),
+            Some(Piece::new(PieceType::Dragon, Player::Sente))
+        );
+        assert_eq!(pos.side_to_move(), Player::Gote);
+        assert_eq!(pos.ply(), 2);
+    }
+
+    #[test]
+    fn test_is_attacked() {
+        let mut pos = Position::empty();
+        pos.set_piece(sq(5, 5), Some(Piece::new(PieceType::Lance, Player::Gote)));
+
+        // Gote lance attacks downwards (increasing rank)
+        assert!(pos.is_attacked(sq(5, 9), Player::Gote));
+        assert!(!pos.is_attacked(sq(5, 4), Player::Gote));
+        pos.set_piece(sq(3, 5), Some(Piece::new(PieceType::Knight, Player::Sente)));
+        assert!(pos.is_attacked(sq(2, 3), Player::Sente));
+        assert!(pos.is_attacked(sq(4, 3), Player::Sente));
+        assert!(!pos.is_attacked(sq(3, 3), Player::Sente));
+    }
+
+    #[test]
+    fn test_in_check_blocked() {
+        let mut pos = Position::empty();
+        pos.set_piece(sq(5, 1), Some(Piece::new(PieceType::King, Player::Gote)));
+        pos.set_piece(sq(5, 9), Some(Piece::new(PieceType::Rook, Player::Sente)));
+        assert!(pos.in_check(Player::Gote));
+
+        pos.set_piece(sq(5, 5), Some(Piece::new(PieceType::Pawn, Player::Gote)));
+        assert!(!pos.in_check(Player::Gote));
+        assert!(!pos.in_check(Player::Sente));
+    }
+}
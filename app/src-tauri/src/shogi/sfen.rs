@@ -0,0 +1,604 @@
+// SFEN parser and serializer with strict validation
+//
+// Format: "<board> <side to move> <hands> <move number>"
+// e.g. "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1"
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::movegen::is_dead_square;
+use super::position::Position;
+use super::types::{Piece, PieceType, Player, Square};
+
+/// SFEN of the standard starting position
+pub const STARTPOS_SFEN: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+
+/// Errors found while parsing or validating an SFEN string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SfenError {
+    /// Not exactly four whitespace-separated fields
+    FieldCount(usize),
+    /// The board does not have nine ranks
+    BadRankCount(usize),
+    /// A rank does not describe exactly nine files
+    BadRankLength { rank: u8, files: usize },
+    /// A character that is not a piece letter, digit or '+'
+    UnknownPiece(char),
+    /// '+' in front of a piece that cannot promote, or at the end of a rank
+    InvalidPromotion(String),
+    /// Side to move is neither "b" nor "w"
+    InvalidSideToMove(String),
+    /// Malformed hand field
+    InvalidHand(String),
+    /// More pieces of one type in a hand than exist in the game
+    ImpossibleHandCount { piece_type: PieceType, count: u32 },
+    /// A pawn, lance or knight on a rank where it could never move
+    DeadPiece { square: String },
+    /// Two unpromoted pawns of one player on the same file
+    DoublePawn { player: Player, file: u8 },
+    /// More than one king for a player
+    TwoKings(Player),
+    /// More pieces of one type on the board and in hand than exist in the game
+    TooManyPieces { piece_type: PieceType, count: u32 },
+    /// Move number is not a positive integer
+    InvalidMoveNumber(String),
+}
+
+impl fmt::Display for SfenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SfenError::FieldCount(n) => {
+                write!(f, "Invalid SFEN: expected 4 fields, found {}", n)
+            }
+            SfenError::BadRankCount(n) => {
+                write!(f, "Invalid SFEN: board must have 9 ranks, found {}", n)
+            }
+            SfenError::BadRankLength { rank, files } => {
+                write!(
+                    f,
+                    "Invalid SFEN: rank {} has {} files, expected 9",
+                    rank, files
+                )
+            }
+            SfenError::UnknownPiece(c) => write!(f, "Invalid SFEN: unknown piece '{}'", c),
+            SfenError::InvalidPromotion(s) => {
+                write!(f, "Invalid SFEN: invalid promoted piece '{}'", s)
+            }
+            SfenError::InvalidSideToMove(s) => {
+                write!(
+                    f,
+                    "Invalid SFEN: side to move must be 'b' or 'w', found '{}'",
+                    s
+                )
+            }
+            SfenError::InvalidHand(s) => write!(f, "Invalid SFEN: invalid hand '{}'", s),
+            SfenError::ImpossibleHandCount { piece_type, count } => write!(
+                f,
+                "Invalid SFEN: {} {} in hand is impossible",
+                count,
+                piece_type.usi_char()
+            ),
+            SfenError::DeadPiece { square } => {
+                write!(f, "Invalid SFEN: piece on {} can never move", square)
+            }
+            SfenError::DoublePawn { player, file } => {
+                write!(f, "Invalid SFEN: two {:?} pawns on file {}", player, file)
+            }
+            SfenError::TwoKings(player) => {
+                write!(f, "Invalid SFEN: {:?} has more than one king", player)
+            }
+            SfenError::TooManyPieces { piece_type, count } => write!(
+                f,
+                "Invalid SFEN: {} pieces of type {} exceed the set",
+                count,
+                piece_type.usi_char()
+            ),
+            SfenError::InvalidMoveNumber(s) => {
+                write!(f, "Invalid SFEN: invalid move number '{}'", s)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SfenError {}
+
+/// Number of pieces of each unpromoted type in a full set (both players)
+fn max_count(piece_type: PieceType) -> u32 {
+    match piece_type.unpromote() {
+        PieceType::Pawn => 18,
+        PieceType::Lance | PieceType::Knight | PieceType::Silver | PieceType::Gold => 4,
+        PieceType::Bishop | PieceType::Rook | PieceType::King => 2,
+        _ => 0,
+    }
+}
+
+/// SFEN character(s) of a piece on the board
+fn piece_to_sfen(piece: Piece) -> String {
+    let c = piece.piece_type.usi_char();
+    let c = match piece.owner {
+        Player::Sente => c,
+        Player::Gote => c.to_ascii_lowercase(),
+    };
+    if piece.piece_type.is_promoted() {
+        format!("+{}", c)
+    } else {
+        c.to_string()
+    }
+}
+
+fn parse_board(board: &str, pos: &mut Position) -> Result<(), SfenError> {
+    let ranks: Vec<&str> = board.split('/').collect();
+    if ranks.len() != 9 {
+        return Err(SfenError::BadRankCount(ranks.len()));
+    }
+
+    for (i, rank_str) in ranks.iter().enumerate() {
+        let rank = i as u8 + 1;
+        // Files run from 9 down to 1 within a rank
+        let mut files = 0usize;
+        let mut chars = rank_str.chars();
+
+        while let Some(c) = chars.next() {
+            if let Some(n) = c.to_digit(10) {
+                if n == 0 {
+                    return Err(SfenError::UnknownPiece(c));
+                }
+                files += n as usize;
+                continue;
+            }
+
+            let (promoted, c) = if c == '+' {
+                match chars.next() {
+                    Some(next) => (true, next),
+                    None => return Err(SfenError::InvalidPromotion("+".to_string())),
+                }
+            } else {
+                (false, c)
+            };
+
+            let base = PieceType::from_usi_char(c).ok_or(SfenError::UnknownPiece(c))?;
+            let piece_type = if promoted {
+                if !base.can_promote() {
+                    return Err(SfenError::InvalidPromotion(format!("+{}", c)));
+                }
+                base.promote()
+            } else {
+                base
+            };
+            let owner = if c.is_ascii_uppercase() {
+                Player::Sente
+            } else {
+                Player::Gote
+            };
+
+            files += 1;
+            if files > 9 {
+                break;
+            }
+            let sq = Square::new(10 - files as u8, rank).unwrap();
+            pos.set_piece(sq, Some(Piece::new(piece_type, owner)));
+        }
+
+        if files != 9 {
+            return Err(SfenError::BadRankLength { rank, files });
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_hands(hands: &str, pos: &mut Position) -> Result<(), SfenError> {
+    if hands == "-" {
+        return Ok(());
+    }
+
+    let invalid = || SfenError::InvalidHand(hands.to_string());
+    let mut seen = [[false; 7]; 2];
+    let mut chars = hands.chars().peekable();
+
+    while chars.peek().is_some() {
+        let mut digits = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        let count: u32 = if digits.is_empty() {
+            1
+        } else {
+            digits.parse().map_err(|_| invalid())?
+        };
+        if count == 0 {
+            return Err(invalid());
+        }
+
+        let c = chars.next().ok_or_else(invalid)?;
+        let piece_type = PieceType::from_usi_char(c).ok_or_else(|| {
+            if c == '+' {
+                invalid()
+            } else {
+                SfenError::UnknownPiece(c)
+            }
+        })?;
+        let hand_index = piece_type.hand_index().ok_or_else(invalid)?;
+        let owner = if c.is_ascii_uppercase() {
+            Player::Sente
+        } else {
+            Player::Gote
+        };
+
+        if seen[owner.index()][hand_index] {
+            return Err(invalid());
+        }
+        seen[owner.index()][hand_index] = true;
+
+        if count > max_count(piece_type) {
+            return Err(SfenError::ImpossibleHandCount { piece_type, count });
+        }
+        pos.hand_mut(owner).set(piece_type, count as u8);
+    }
+
+    Ok(())
+}
+
+/// Check piece placement and piece counts of a parsed position
+fn validate(pos: &Position) -> Result<(), SfenError> {
+    let mut counts = [0u32; 14];
+    let mut kings = [0u32; 2];
+    let mut pawn_files = [[false; 9]; 2];
+
+    for (sq, piece) in pos.pieces() {
+        if is_dead_square(piece.piece_type, sq, piece.owner) {
+            return Err(SfenError::DeadPiece {
+                square: sq.to_usi(),
+            });
+        }
+        if piece.piece_type == PieceType::King {
+            kings[piece.owner.index()] += 1;
+            if kings[piece.owner.index()] > 1 {
+                return Err(SfenError::TwoKings(piece.owner));
+            }
+        }
+        if piece.piece_type == PieceType::Pawn {
+            let seen = &mut pawn_files[piece.owner.index()][sq.file() as usize - 1];
+            if *seen {
+                return Err(SfenError::DoublePawn {
+                    player: piece.owner,
+                    file: sq.file(),
+                });
+            }
+            *seen = true;
+        }
+        counts[piece.piece_type.unpromote().index()] += 1;
+    }
+
+    for player in [Player::Sente, Player::Gote] {
+        for (piece_type, count) in pos.hand(player).iter() {
+            counts[piece_type.index()] += count as u32;
+        }
+    }
+
+    for piece_type in PieceType::ALL {
+        let count = counts[piece_type.index()];
+        if count > max_count(piece_type) {
+            return Err(SfenError::TooManyPieces { piece_type, count });
+        }
+    }
+
+    Ok(())
+}
+
+impl Position {
+    /// Parse and validate an SFEN string
+    pub fn from_sfen(sfen: &str) -> Result<Position, SfenError> {
+        let fields: Vec<&str> = sfen.split_whitespace().collect();
+        if fields.len() != 4 {
+            return Err(SfenError::FieldCount(fields.len()));
+        }
+
+        let mut pos = Position::empty();
+        parse_board(fields[0], &mut pos)?;
+
+        let side = match fields[1] {
+            "b" => Player::Sente,
+            "w" => Player::Gote,
+            other => return Err(SfenError::InvalidSideToMove(other.to_string())),
+        };
+        pos.set_side_to_move(side);
+
+        parse_hands(fields[2], &mut pos)?;
+
+        let ply = fields[3]
+            .parse::<u32>()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(|| SfenError::InvalidMoveNumber(fields[3].to_string()))?;
+        pos.set_ply(ply);
+
+        validate(&pos)?;
+        Ok(pos)
+    }
+
+    /// Serialize the position as SFEN
+    pub fn to_sfen(&self) -> String {
+        let mut ranks = Vec::with_capacity(9);
+        for rank in 1..=9 {
+            let mut rank_str = String::new();
+            let mut empty = 0;
+            for file in (1..=9).rev() {
+                match self.piece_at(Square::new(file, rank).unwrap()) {
+                    None => empty += 1,
+                    Some(piece) => {
+                        if empty > 0 {
+                            rank_str.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        rank_str.push_str(&piece_to_sfen(piece));
+                    }
+                }
+            }
+            if empty > 0 {
+                rank_str.push_str(&empty.to_string());
+            }
+            ranks.push(rank_str);
+        }
+
+        let mut hands = String::new();
+        for player in [Player::Sente, Player::Gote] {
+            for (piece_type, count) in self.hand(player).iter() {
+                if count > 1 {
+                    hands.push_str(&count.to_string());
+                }
+                hands.push_str(&piece_to_sfen(Piece::new(piece_type, player)));
+            }
+        }
+        if hands.is_empty() {
+            hands.push('-');
+        }
+
+        let side = match self.side_to_move() {
+            Player::Sente => "b",
+            Player::Gote => "w",
+        };
+
+        format!("{} {} {} {}", ranks.join("/"), side, hands, self.ply())
+    }
+}
+
+impl FromStr for Position {
+    type Err = SfenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Position::from_sfen(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sq(file: u8, rank: u8) -> Square {
+        Square::new(file, rank).unwrap()
+    }
+
+    fn round_trip(sfen: &str) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        assert_eq!(pos.to_sfen(), sfen);
+    }
+
+    #[test]
+    fn test_startpos_round_trip() {
+        let pos = Position::from_sfen(STARTPOS_SFEN).unwrap();
+        assert_eq!(pos, Position::startpos());
+        assert_eq!(Position::startpos().to_sfen(), STARTPOS_SFEN);
+    }
+
+    #[test]
+    fn test_round_trip_corpus() {
+        for sfen in [
+            // Empty board
+            "9/9/9/9/9/9/9/9/9 b - 1",
+            // Pieces in hand, gold before pawns and rook before bishop
+            "4k4/9/9/9/9/9/9/9/4K4 b G2Prb 5",
+            // Gote to move
+            "4k4/9/9/9/9/9/9/9/4K4 w - 10",
+            // Promoted pieces
+            "4k4/9/4P4/4+R4/9/9/9/9/4K4 b - 1",
+            "4k4/9/4+P4/3+R1+b3/9/9/9/9/4K4 b - 1",
+            // Hand order RBGSNLP
+            "4k4/9/9/9/9/9/9/9/4K4 b RBGSNLP 1",
+            // Several pawns in hand
+            "4k4/9/9/9/9/9/9/9/4K4 b 5P 1",
+            "4k4/9/9/9/9/9/9/9/4K4 w 10P8p 1",
+            // Pieces on the 1 and 9 files
+            "8l/9/9/9/9/9/9/9/L8 b - 1",
+            // All promoted piece kinds
+            "+P+L+N+S+B+R3/9/9/9/9/9/9/9/+p+l+n+s+b+r3 b - 1",
+            // A real middle-game position
+            "ln1g3nl/1r3kg2/p2pppsp1/2ps2p1p/1p7/2P1P1P2/PPSP1PNPP/2G2S1R1/LN2KG2L w Bb 32",
+        ] {
+            round_trip(sfen);
+        }
+    }
+
+    #[test]
+    fn test_parse_details() {
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 w G2Prb 5").unwrap();
+        assert_eq!(pos.side_to_move(), Player::Gote);
+        assert_eq!(pos.ply(), 5);
+        assert_eq!(pos.hand(Player::Sente).count(PieceType::Pawn), 2);
+        assert_eq!(pos.hand(Player::Sente).count(PieceType::Gold), 1);
+        assert_eq!(pos.hand(Player::Gote).count(PieceType::Rook), 1);
+        assert_eq!(
+            pos.piece_at(sq(5, 1)),
+            Some(Piece::new(PieceType::King, Player::Gote))
+        );
+        assert_eq!(
+            pos.piece_at(sq(5, 9)),
+            Some(Piece::new(PieceType::King, Player::Sente))
+        );
+    }
+
+    #[test]
+    fn test_file_order() {
+        let pos = Position::from_sfen("8l/9/9/9/9/9/9/9/L8 b - 1").unwrap();
+        assert_eq!(
+            pos.piece_at(sq(1, 1)),
+            Some(Piece::new(PieceType::Lance, Player::Gote))
+        );
+        assert_eq!(
+            pos.piece_at(sq(9, 9)),
+            Some(Piece::new(PieceType::Lance, Player::Sente))
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        let pos: Position = STARTPOS_SFEN.parse().unwrap();
+        assert_eq!(pos.legal_moves().len(), 30);
+    }
+
+    #[test]
+    fn test_rejects_bad_structure() {
+        assert_eq!(Position::from_sfen(""), Err(SfenError::FieldCount(0)));
+        assert_eq!(
+            Position::from_sfen("9/9/9/9/9/9/9/9/9 b -"),
+            Err(SfenError::FieldCount(3))
+        );
+        assert_eq!(
+            Position::from_sfen("9/9/9/9/9/9/9/9 b - 1"),
+            Err(SfenError::BadRankCount(8))
+        );
+        assert_eq!(
+            Position::from_sfen("9/9/9/9/9/9/9/9/9/9 b - 1"),
+            Err(SfenError::BadRankCount(10))
+        );
+        assert_eq!(
+            Position::from_sfen("8/9/9/9/9/9/9/9/9 b - 1"),
+            Err(SfenError::BadRankLength { rank: 1, files: 8 })
+        );
+        assert_eq!(
+            Position::from_sfen("9/9/9/9/9/9/9/9/5K4 b - 1"),
+            Err(SfenError::BadRankLength { rank: 9, files: 10 })
+        );
+        assert_eq!(
+            Position::from_sfen("9/9/9/9/9/9/9/9/9 x - 1"),
+            Err(SfenError::InvalidSideToMove("x".to_string()))
+        );
+        assert_eq!(
+            Position::from_sfen("9/9/9/9/9/9/9/9/9 b - 0"),
+            Err(SfenError::InvalidMoveNumber("0".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_pieces() {
+        assert_eq!(
+            Position::from_sfen("4x4/9/9/9/9/9/9/9/9 b - 1"),
+            Err(SfenError::UnknownPiece('x'))
+        );
+        assert_eq!(
+            Position::from_sfen("4+G4/9/9/9/9/9/9/9/9 b - 1"),
+            Err(SfenError::InvalidPromotion("+G".to_string()))
+        );
+        assert_eq!(
+            Position::from_sfen("4+k4/9/9/9/9/9/9/9/9 b - 1"),
+            Err(SfenError::InvalidPromotion("+k".to_string()))
+        );
+        assert_eq!(
+            Position::from_sfen("9/9/9/9/9/9/9/9/9 b X 1"),
+            Err(SfenError::UnknownPiece('X'))
+        );
+    }
+
+    #[test]
+    fn test_rejects_bad_hands() {
+        for hand in ["K", "+P", "0P", "2", "PP", "P-"] {
+            let sfen = format!("9/9/9/9/9/9/9/9/9 b {} 1", hand);
+            assert!(
+                matches!(
+                    Position::from_sfen(&sfen),
+                    Err(SfenError::InvalidHand(_)) | Err(SfenError::UnknownPiece(_))
+                ),
+                "hand {} should be rejected",
+                hand
+            );
+        }
+        assert_eq!(
+            Position::from_sfen("9/9/9/9/9/9/9/9/9 b 19P 1"),
+            Err(SfenError::ImpossibleHandCount {
+                piece_type: PieceType::Pawn,
+                count: 19
+            })
+        );
+        assert_eq!(
+            Position::from_sfen("9/9/9/9/9/9/9/9/9 b 3B 1"),
+            Err(SfenError::ImpossibleHandCount {
+                piece_type: PieceType::Bishop,
+                count: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejects_dead_pieces() {
+        assert_eq!(
+            Position::from_sfen("4P4/9/9/9/9/9/9/9/9 b - 1"),
+            Err(SfenError::DeadPiece {
+                square: "5a".to_string()
+            })
+        );
+        assert_eq!(
+            Position::from_sfen("9/9/9/9/9/9/9/9/4l4 b - 1"),
+            Err(SfenError::DeadPiece {
+                square: "5i".to_string()
+            })
+        );
+        assert_eq!(
+            Position::from_sfen("9/4N4/9/9/9/9/9/9/9 b - 1"),
+            Err(SfenError::DeadPiece {
+                square: "5b".to_string()
+            })
+        );
+        // A promoted pawn on the last rank is fine
+        assert!(Position::from_sfen("4+P4/9/9/9/9/9/9/9/9 b - 1").is_ok());
+    }
+
+    #[test]
+    fn test_rejects_two_kings_and_double_pawns() {
+        assert_eq!(
+            Position::from_sfen("9/9/9/9/9/9/9/9/3KK4 b - 1"),
+            Err(SfenError::TwoKings(Player::Sente))
+        );
+        assert_eq!(
+            Position::from_sfen("9/9/9/4P4/9/4P4/9/9/9 b - 1"),
+            Err(SfenError::DoublePawn {
+                player: Player::Sente,
+                file: 5
+            })
+        );
+        // A pawn and a promoted pawn on one file are allowed
+        assert!(Position::from_sfen("9/9/9/4+P4/9/4P4/9/9/9 b - 1").is_ok());
+    }
+
+    #[test]
+    fn test_rejects_too_many_pieces() {
+        assert_eq!(
+            Position::from_sfen("9/9/9/9/4B4/9/9/9/9 b 2b 1"),
+            Err(SfenError::TooManyPieces {
+                piece_type: PieceType::Bishop,
+                count: 3
+            })
+        );
+        assert_eq!(
+            Position::from_sfen("9/9/9/9/4+R4/9/9/9/9 b R2r 1"),
+            Err(SfenError::TooManyPieces {
+                piece_type: PieceType::Rook,
+                count: 4
+            })
+        );
+    }
+}
@@ -0,0 +1,324 @@
+// Basic shogi types: players, pieces and squares
+
+use serde::{Deserialize, Serialize};
+
+/// Player (sente moves first from the bottom of the board)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Player {
+    Sente,
+    Gote,
+}
+
+impl Player {
+    /// The other player
+    pub fn opponent(self) -> Player {
+        match self {
+            Player::Sente => Player::Gote,
+            Player::Gote => Player::Sente,
+        }
+    }
+
+    /// Index for per-player arrays (sente = 0, gote = 1)
+    pub fn index(self) -> usize {
+        match self {
+            Player::Sente => 0,
+            Player::Gote => 1,
+        }
+    }
+
+    /// Rank direction of "forward" for this player
+    pub fn forward(self) -> i8 {
+        match self {
+            Player::Sente => -1,
+            Player::Gote => 1,
+        }
+    }
+}
+
+/// Piece type, serialized with the same names as the frontend `PieceType`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PieceType {
+    #[serde(rename = "FU")]
+    Pawn,
+    #[serde(rename = "KY")]
+    Lance,
+    #[serde(rename = "KE")]
+    Knight,
+    #[serde(rename = "GI")]
+    Silver,
+    #[serde(rename = "KI")]
+    Gold,
+    #[serde(rename = "KA")]
+    Bishop,
+    #[serde(rename = "HI")]
+    Rook,
+    #[serde(rename = "OU")]
+    King,
+    #[serde(rename = "TO")]
+    ProPawn,
+    #[serde(rename = "NY")]
+    ProLance,
+    #[serde(rename = "NK")]
+    ProKnight,
+    #[serde(rename = "NG")]
+    ProSilver,
+    #[serde(rename = "UM")]
+    Horse,
+    #[serde(rename = "RY")]
+    Dragon,
+}
+
+impl PieceType {
+    /// All piece types in declaration order
+    pub const ALL: [PieceType; 14] = [
+        PieceType::Pawn,
+        PieceType::Lance,
+        PieceType::Knight,
+        PieceType::Silver,
+        PieceType::Gold,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::King,
+        PieceType::ProPawn,
+        PieceType::ProLance,
+        PieceType::ProKnight,
+        PieceType::ProSilver,
+        PieceType::Horse,
+        PieceType::Dragon,
+    ];
+
+    /// Pieces that can be held in hand, in SFEN order (飛角金銀桂香歩)
+    pub const HAND: [PieceType; 7] = [
+        PieceType::Rook,
+        PieceType::Bishop,
+        PieceType::Gold,
+        PieceType::Silver,
+        PieceType::Knight,
+        PieceType::Lance,
+        PieceType::Pawn,
+    ];
+
+    /// Index into `PieceType::ALL`
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Index into `PieceType::HAND`, or None for pieces that cannot be in hand
+    pub fn hand_index(self) -> Option<usize> {
+        match self {
+            PieceType::Rook => Some(0),
+            PieceType::Bishop => Some(1),
+            PieceType::Gold => Some(2),
+            PieceType::Silver => Some(3),
+            PieceType::Knight => Some(4),
+            PieceType::Lance => Some(5),
+            PieceType::Pawn => Some(6),
+            _ => None,
+        }
+    }
+
+    /// Whether this piece type has a promoted form
+    pub fn can_promote(self) -> bool {
+        self.promote() != self
+    }
+
+    /// Whether this piece type is a promoted piece
+    pub fn is_promoted(self) -> bool {
+        self.unpromote() != self
+    }
+
+    /// Promoted form (returns self for pieces that cannot promote)
+    pub fn promote(self) -> PieceType {
+        match self {
+            PieceType::Pawn => PieceType::ProPawn,
+            PieceType::Lance => PieceType::ProLance,
+            PieceType::Knight => PieceType::ProKnight,
+            PieceType::Silver => PieceType::ProSilver,
+            PieceType::Bishop => PieceType::Horse,
+            PieceType::Rook => PieceType::Dragon,
+            other => other,
+        }
+    }
+
+    /// Unpromoted form (returns self for unpromoted pieces)
+    pub fn unpromote(self) -> PieceType {
+        match self {
+            PieceType::ProPawn => PieceType::Pawn,
+            PieceType::ProLance => PieceType::Lance,
+            PieceType::ProKnight => PieceType::Knight,
+            PieceType::ProSilver => PieceType::Silver,
+            PieceType::Horse => PieceType::Bishop,
+            PieceType::Dragon => PieceType::Rook,
+            other => other,
+        }
+    }
+
+    /// USI/SFEN letter of the unpromoted piece (upper case)
+    pub fn usi_char(self) -> char {
+        match self.unpromote() {
+            PieceType::Pawn => 'P',
+            PieceType::Lance => 'L',
+            PieceType::Knight => 'N',
+            PieceType::Silver => 'S',
+            PieceType::Gold => 'G',
+            PieceType::Bishop => 'B',
+            PieceType::Rook => 'R',
+            _ => 'K',
+        }
+    }
+
+    /// Parse an unpromoted USI/SFEN letter (case-insensitive)
+    pub fn from_usi_char(c: char) -> Option<PieceType> {
+        match c.to_ascii_uppercase() {
+            'P' => Some(PieceType::Pawn),
+            'L' => Some(PieceType::Lance),
+            'N' => Some(PieceType::Knight),
+            'S' => Some(PieceType::Silver),
+            'G' => Some(PieceType::Gold),
+            'B' => Some(PieceType::Bishop),
+            'R' => Some(PieceType::Rook),
+            'K' => Some(PieceType::King),
+            _ => None,
+        }
+    }
+}
+
+/// A piece on the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Piece {
+    pub piece_type: PieceType,
+    pub owner: Player,
+}
+
+impl Piece {
+    pub fn new(piece_type: PieceType, owner: Player) -> Self {
+        Piece { piece_type, owner }
+    }
+}
+
+/// A board square, stored as an index 0..81
+/// Files run 1-9 (right to left from sente's view), ranks 1-9 (top to bottom, "a"-"i" in USI)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Square(u8);
+
+impl Square {
+    /// Create a square from a 1-based file and rank
+    pub fn new(file: u8, rank: u8) -> Option<Square> {
+        if (1..=9).contains(&file) && (1..=9).contains(&rank) {
+            Some(Square((file - 1) * 9 + (rank - 1)))
+        } else {
+            None
+        }
+    }
+
+    /// Create a square from a board index (0..81)
+    pub fn from_index(index: usize) -> Option<Square> {
+        if index < 81 {
+            Some(Square(index as u8))
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over all 81 squares
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..81u8).map(Square)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// File (1-9)
+    pub fn file(self) -> u8 {
+        self.0 / 9 + 1
+    }
+
+    /// Rank (1-9)
+    pub fn rank(self) -> u8 {
+        self.0 % 9 + 1
+    }
+
+    /// Square offset by (file, rank) deltas, None if off the board
+    pub fn offset(self, dfile: i8, drank: i8) -> Option<Square> {
+        let file = self.file() as i8 + dfile;
+        let rank = self.rank() as i8 + drank;
+        if (1..=9).contains(&file) && (1..=9).contains(&rank) {
+            Square::new(file as u8, rank as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Rank counted from the given player's side (1 = farthest rank)
+    pub fn relative_rank(self, player: Player) -> u8 {
+        match player {
+            Player::Sente => self.rank(),
+            Player::Gote => 10 - self.rank(),
+        }
+    }
+
+    /// Whether the square is in the player's promotion zone (opponent's three ranks)
+    pub fn in_promotion_zone(self, player: Player) -> bool {
+        self.relative_rank(player) <= 3
+    }
+
+    /// Parse a USI square such as "7g"
+    pub fn from_usi(s: &str) -> Option<Square> {
+        let bytes = s.as_bytes();
+        if bytes.len() != 2 {
+            return None;
+        }
+        let file = bytes[0].checked_sub(b'0')?;
+        let rank = bytes[1].checked_sub(b'a')? + 1;
+        Square::new(file, rank)
+    }
+
+    /// USI notation such as "7g"
+    pub fn to_usi(self) -> String {
+        format!("{}{}", self.file(), (b'a' + self.rank() - 1) as char)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square_coordinates() {
+        let sq = Square::new(7, 7).unwrap();
+        assert_eq!(sq.file(), 7);
+        assert_eq!(sq.rank(), 7);
+        assert_eq!(sq.to_usi(), "7g");
+        assert_eq!(Square::from_usi("7g"), Some(sq));
+        assert_eq!(Square::new(0, 5), None);
+        assert_eq!(Square::from_usi("0a"), None);
+        assert_eq!(Square::from_usi("1j"), None);
+    }
+
+    #[test]
+    fn test_square_offset() {
+        let sq = Square::new(1, 1).unwrap();
+        assert_eq!(sq.offset(-1, 0), None);
+        assert_eq!(sq.offset(1, 1), Square::new(2, 2));
+    }
+
+    #[test]
+    fn test_promotion_zone() {
+        assert!(Square::new(5, 3).unwrap().in_promotion_zone(Player::Sente));
+        assert!(!Square::new(5, 4).unwrap().in_promotion_zone(Player::Sente));
+        assert!(Square::new(5, 7).unwrap().in_promotion_zone(Player::Gote));
+        assert!(!Square::new(5, 6).unwrap().in_promotion_zone(Player::Gote));
+    }
+
+    #[test]
+    fn test_promote_unpromote() {
+        assert_eq!(PieceType::Pawn.promote(), PieceType::ProPawn);
+        assert_eq!(PieceType::Dragon.unpromote(), PieceType::Rook);
+        assert!(!PieceType::Gold.can_promote());
+        assert!(!PieceType::King.can_promote());
+        assert!(PieceType::Horse.is_promoted());
+        assert_eq!(PieceType::Horse.usi_char(), 'B');
+    }
+}
@@ -0,0 +1,129 @@
+// Zobrist hashing of positions (board, hands and side to move)
+
+use super::position::Position;
+use super::types::Player;
+
+/// Largest number of one piece type a hand can hold (18 pawns)
+const MAX_HAND_COUNT: usize = 18;
+
+struct ZobristTables {
+    board: [[[u64; 81]; 14]; 2],
+    hand: [[[u64; MAX_HAND_COUNT + 1]; 7]; 2],
+    gote_to_move: u64,
+}
+
+/// splitmix64 step, usable in const context
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+const fn build_tables() -> ZobristTables {
+    let mut tables = ZobristTables {
+        board: [[[0; 81]; 14]; 2],
+        hand: [[[0; MAX_HAND_COUNT + 1]; 7]; 2],
+        gote_to_move: 0,
+    };
+    let mut state = 0x5348_4F47_495F_4B45; // "SHOGI_KE"
+
+    let mut player = 0;
+    while player < 2 {
+        let mut piece = 0;
+        while piece < 14 {
+            let mut sq = 0;
+            while sq < 81 {
+                let (next, value) = splitmix64(state);
+                state = next;
+                tables.board[player][piece][sq] = value;
+                sq += 1;
+            }
+            piece += 1;
+        }
+        let mut piece = 0;
+        while piece < 7 {
+            // Count 0 hashes to zero so empty hands need no special case
+            let mut count = 1;
+            while count <= MAX_HAND_COUNT {
+                let (next, value) = splitmix64(state);
+                state = next;
+                tables.hand[player][piece][count] = value;
+                count += 1;
+            }
+            piece += 1;
+        }
+        player += 1;
+    }
+
+    let (_, value) = splitmix64(state);
+    tables.gote_to_move = value;
+    tables
+}
+
+static TABLES: ZobristTables = build_tables();
+
+impl Position {
+    /// Zobrist key of the position; the move number is not part of the key,
+    /// so repeated positions hash equal
+    pub fn key(&self) -> u64 {
+        let mut key = 0;
+
+        for (sq, piece) in self.pieces() {
+            key ^= TABLES.board[piece.owner.index()][piece.piece_type.index()][sq.index()];
+        }
+
+        for player in [Player::Sente, Player::Gote] {
+            for (piece_type, count) in self.hand(player).iter() {
+                let hand_index = piece_type.hand_index().unwrap();
+                let count = (count as usize).min(MAX_HAND_COUNT);
+                key ^= TABLES.hand[player.index()][hand_index][count];
+            }
+        }
+
+        if self.side_to_move() == Player::Gote {
+            key ^= TABLES.gote_to_move;
+        }
+
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shogi::{Move, STARTPOS_SFEN};
+
+    #[test]
+    fn test_key_ignores_move_number() {
+        let a = Position::from_sfen(STARTPOS_SFEN).unwrap();
+        let b = Position::from_sfen(&STARTPOS_SFEN.replace(" 1", " 57")).unwrap();
+        assert_eq!(a.key(), b.key());
+    }
+
+    #[test]
+    fn test_key_depends_on_side_and_hands() {
+        let a = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b P 1").unwrap();
+        let b = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 w P 1").unwrap();
+        let c = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b 2P 1").unwrap();
+        let d = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b p 1").unwrap();
+        let keys = [a.key(), b.key(), c.key(), d.key()];
+        for i in 0..keys.len() {
+            for j in i + 1..keys.len() {
+                assert_ne!(keys[i], keys[j]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_key_repeats_after_move_cycle() {
+        let mut pos = Position::startpos();
+        let start = pos.key();
+        for usi in ["2h3h", "8b7b", "3h2h", "7b8b"] {
+            pos.do_move(Move::from_usi(usi).unwrap());
+        }
+        assert_eq!(pos.key(), start);
+        assert_ne!(pos.ply(), 1);
+    }
+}
@@ -0,0 +1,169 @@
+// Gate between the engine's bestmove and the game: whatever the engine sends is
+// checked against the position it was searching before it reaches the frontend
+
+use serde::Serialize;
+use std::fmt;
+
+use super::error::{EngineError, UsiError};
+use super::parser::BestMove;
+use crate::shogi::{judge_declaration, DeclarationRule, Move, Position};
+
+/// Why a search produced no move for the game
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SearchError {
+    /// The engine answered with a move that is not legal in the searched position,
+    /// or declared a win ("win") the declaration rules do not allow
+    /// Under tournament rules this loses the game for the engine
+    IllegalMove { usi: String },
+    /// The search could not be run or the engine failed
+    Failed(EngineError),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchError::IllegalMove { usi } => write!(f, "Engine made an illegal move: {}", usi),
+            SearchError::Failed(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+impl From<EngineError> for SearchError {
+    fn from(error: EngineError) -> Self {
+        SearchError::Failed(error)
+    }
+}
+
+impl From<UsiError> for SearchError {
+    fn from(error: UsiError) -> Self {
+        SearchError::Failed(error.into())
+    }
+}
+
+impl From<String> for SearchError {
+    fn from(message: String) -> Self {
+        SearchError::Failed(message.into())
+    }
+}
+
+impl From<&str> for SearchError {
+    fn from(message: &str) -> Self {
+        SearchError::Failed(message.into())
+    }
+}
+
+impl From<SearchError> for String {
+    fn from(error: SearchError) -> String {
+        error.to_string()
+    }
+}
+
+/// Check the engine's bestmove against the position it was searching
+/// Resigning is always allowed; a declaration ("win") must meet the game's rule,
+/// though under the 24-point rule it may only earn a draw
+pub fn check_best_move(
+    pos: &Position,
+    best_move: BestMove,
+    rule: DeclarationRule,
+) -> Result<BestMove, SearchError> {
+    let legal = match &best_move {
+        BestMove::Move(usi) => Move::from_usi(usi).is_ok_and(|mv| pos.is_legal(mv)),
+        BestMove::Resign => true,
+        BestMove::Win => judge_declaration(pos, rule).is_some(),
+    };
+    if legal {
+        Ok(best_move)
+    } else {
+        Err(SearchError::IllegalMove {
+            usi: best_move.to_usi().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(pos: &Position, usi: &str) -> Result<BestMove, SearchError> {
+        check_best_move(pos, BestMove::from_usi(usi), DeclarationRule::Points27)
+    }
+
+    #[test]
+    fn test_legal_move_passes() {
+        let pos = Position::startpos();
+        assert_eq!(check(&pos, "7g7f"), Ok(BestMove::Move("7g7f".to_string())));
+        assert_eq!(check(&pos, "resign"), Ok(BestMove::Resign));
+    }
+
+    #[test]
+    fn test_win_needs_a_valid_declaration() {
+        let pos = Position::startpos();
+        assert_eq!(
+            check(&pos, "win"),
+            Err(SearchError::IllegalMove {
+                usi: "win".to_string()
+            })
+        );
+
+        let pos = Position::from_sfen("+R+B+B+RK4/PPPPPPPP1/9/9/9/9/9/9/4k4 b - 1").unwrap();
+        assert_eq!(check(&pos, "win"), Ok(BestMove::Win));
+
+        // 27 points: short for sente under the 27-point rule, a draw under the 24-point one
+        let pos = Position::from_sfen("+R+B+B+RK4/PPPPPPP2/9/9/9/9/9/9/4k4 b - 1").unwrap();
+        assert!(check(&pos, "win").is_err());
+        assert_eq!(
+            check_best_move(&pos, BestMove::Win, DeclarationRule::Points24),
+            Ok(BestMove::Win)
+        );
+    }
+
+    #[test]
+    fn test_illegal_moves_are_rejected() {
+        let pos = Position::startpos();
+        // Wrong side, blocked piece, piece not in hand, and garbage
+        for usi in ["3c3d", "8h2b+", "P*5e", "7g7f7f", ""] {
+            let err = check(&pos, usi).unwrap_err();
+            assert_eq!(
+                err,
+                SearchError::IllegalMove {
+                    usi: usi.to_string()
+                }
+            );
+        }
+        assert_eq!(
+            check(&pos, "3c3d").unwrap_err().to_string(),
+            "Engine made an illegal move: 3c3d"
+        );
+    }
+
+    #[test]
+    fn test_serialization() {
+        let json = serde_json::to_value(BestMove::Move("7g7f".to_string())).unwrap();
+        assert_eq!(json, serde_json::json!({ "type": "move", "usi": "7g7f" }));
+        let json = serde_json::to_value(BestMove::Resign).unwrap();
+        assert_eq!(json, serde_json::json!({ "type": "resign" }));
+
+        let json = serde_json::to_value(SearchError::IllegalMove {
+            usi: "1a1b".to_string(),
+        })
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "type": "illegalMove", "usi": "1a1b" })
+        );
+
+        // An engine failure keeps the UsiError behind it
+        let json = serde_json::to_value(SearchError::from(UsiError::SearchTimeout)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "failed",
+                "message": "Timeout waiting for engine bestmove",
+                "usiError": { "type": "searchTimeout" }
+            })
+        );
+    }
+}
@@ -1,5 +1,122 @@
 // USI protocol command builder
 
+use serde::{Deserialize, Serialize};
+
+use crate::shogi::{Move, STARTPOS_SFEN};
+
+/// Keyword for the standard initial position in "position" commands
+pub const STARTPOS: &str = "startpos";
+
+/// Time allowed for a "go mate" search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum MateLimit {
+    /// Milliseconds
+    Time(u32),
+    Infinite,
+}
+
+/// Search limits for a "go" command (all times in milliseconds)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GoLimits {
+    pub btime: Option<u32>,
+    pub wtime: Option<u32>,
+    pub binc: Option<u32>,
+    pub winc: Option<u32>,
+    pub byoyomi: Option<u32>,
+    /// Fixed time for this move
+    pub movetime: Option<u32>,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    /// "go mate": search for a forced mate (tsume) instead of a move
+    pub mate: Option<MateLimit>,
+    /// Think until "stop"
+    pub infinite: bool,
+    /// Only consider these moves (USI notation)
+    pub searchmoves: Vec<String>,
+    /// "go ponder": think on the opponent's expected move until "ponderhit" or "stop"
+    pub ponder: bool,
+}
+
+impl GoLimits {
+    /// Fixed time per move
+    pub fn byoyomi(time_ms: u32) -> Self {
+        GoLimits {
+            byoyomi: Some(time_ms),
+            ..Default::default()
+        }
+    }
+
+    /// Mate search
+    pub fn mate(limit: MateLimit) -> Self {
+        GoLimits {
+            mate: Some(limit),
+            ..Default::default()
+        }
+    }
+
+    /// Check that the limits form a valid "go" command
+    pub fn validate(&self) -> Result<(), String> {
+        let has_clock = self.btime.is_some() || self.wtime.is_some();
+        let has_inc = self.binc.is_some() || self.winc.is_some();
+
+        if self.btime.is_some() != self.wtime.is_some() {
+            return Err("btime and wtime must be given together".to_string());
+        }
+        if self.binc.is_some() != self.winc.is_some() {
+            return Err("binc and winc must be given together".to_string());
+        }
+        if has_inc && !has_clock {
+            return Err("binc and winc require btime and wtime".to_string());
+        }
+        if has_inc && self.byoyomi.is_some() {
+            return Err("byoyomi cannot be combined with binc and winc".to_string());
+        }
+        if self.movetime.is_some() && (has_clock || self.byoyomi.is_some()) {
+            return Err("movetime cannot be combined with a clock or byoyomi".to_string());
+        }
+
+        let has_time = has_clock || self.byoyomi.is_some() || self.movetime.is_some();
+        let has_limit = has_time || self.depth.is_some() || self.nodes.is_some();
+        if self.mate.is_some() && (has_limit || self.infinite || self.ponder) {
+            return Err("mate cannot be combined with other limits".to_string());
+        }
+        if self.infinite && (has_limit || self.ponder) {
+            return Err("infinite cannot be combined with other limits".to_string());
+        }
+
+        for mv in &self.searchmoves {
+            Move::from_usi(mv).map_err(|e| format!("Invalid searchmoves: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Rough time to spend on one move for an in-process engine,
+    /// given the remaining time, increment and byoyomi of the side to move
+    pub fn move_time_ms(&self, sente_to_move: bool) -> Option<u64> {
+        let (time, inc) = if sente_to_move {
+            (self.btime, self.binc)
+        } else {
+            (self.wtime, self.winc)
+        };
+        let byoyomi = self.byoyomi.unwrap_or(0) as u64;
+        let inc = inc.unwrap_or(0) as u64;
+
+        if let Some(movetime) = self.movetime {
+            return Some(movetime as u64);
+        }
+        if let Some(MateLimit::Time(time)) = self.mate {
+            return Some(time as u64);
+        }
+        match time {
+            Some(time) => Some(time as u64 / 30 + inc + byoyomi),
+            None if byoyomi > 0 || inc > 0 => Some(byoyomi + inc),
+            None => None,
+        }
+    }
+}
+
 /// Build the "usi" command
 /// Tells the engine to use USI protocol
 pub fn build_usi_command() -> String {
@@ -13,39 +130,72 @@ pub fn build_isready_command() -> String {
 }
 
 /// Build the "position" command
-/// Format: "position sfen <sfen> [moves <move1> <move2> ...]"
+/// Format: "position <startpos|sfen <sfen>> [moves <move1> <move2> ...]"
+/// The standard initial position (or the keyword "startpos") is sent as "startpos"
 pub fn build_position_command(sfen: &str, moves: &[String]) -> String {
-    if moves.is_empty() {
+    let base = if is_startpos(sfen) {
+        "position startpos".to_string()
+    } else {
         format!("position sfen {}", sfen)
+    };
+
+    if moves.is_empty() {
+        base
     } else {
-        format!("position sfen {} moves {}", sfen, moves.join(" "))
+        format!("{} moves {}", base, moves.join(" "))
     }
 }
 
-/// Build the "go" command with byoyomi (time per move)
-/// Format: "go byoyomi <time_ms>"
-pub fn build_go_byoyomi_command(time_ms: u32) -> String {
-    format!("go byoyomi {}", time_ms)
+/// Whether `sfen` denotes the standard initial position
+pub fn is_startpos(sfen: &str) -> bool {
+    let sfen = sfen.trim();
+    sfen == STARTPOS || sfen == STARTPOS_SFEN
 }
 
-/// Build the "go" command with time controls
-/// Format: "go btime <black_time> wtime <white_time> binc <black_inc> winc <white_inc>"
-pub fn build_go_time_command(
-    black_time_ms: u32,
-    white_time_ms: u32,
-    black_inc_ms: u32,
-    white_inc_ms: u32,
-) -> String {
-    format!(
-        "go btime {} wtime {} binc {} winc {}",
-        black_time_ms, white_time_ms, black_inc_ms, white_inc_ms
-    )
+/// Build a "go" command from search limits
+/// Format: "go [ponder] [btime <b> wtime <w>] [binc <bi> winc <wi>] [byoyomi <t>]
+/// [movetime <t>] [depth <d>] [nodes <n>] [mate <t|infinite>] [infinite] [searchmoves <m1> ...]"
+/// "searchmoves" comes last because it takes the rest of the line
+/// The limits are not checked here; see `GoLimits::validate`
+pub fn build_go_command(limits: &GoLimits) -> String {
+    let mut command = "go".to_string();
+    if limits.ponder {
+        command.push_str(" ponder");
+    }
+    let fields = [
+        ("btime", limits.btime.map(u64::from)),
+        ("wtime", limits.wtime.map(u64::from)),
+        ("binc", limits.binc.map(u64::from)),
+        ("winc", limits.winc.map(u64::from)),
+        ("byoyomi", limits.byoyomi.map(u64::from)),
+        ("movetime", limits.movetime.map(u64::from)),
+        ("depth", limits.depth.map(u64::from)),
+        ("nodes", limits.nodes),
+    ];
+    for (name, value) in fields {
+        if let Some(value) = value {
+            command.push_str(&format!(" {} {}", name, value));
+        }
+    }
+    match limits.mate {
+        Some(MateLimit::Time(time_ms)) => command.push_str(&format!(" mate {}", time_ms)),
+        Some(MateLimit::Infinite) => command.push_str(" mate infinite"),
+        None => {}
+    }
+    if limits.infinite {
+        command.push_str(" infinite");
+    }
+    if !limits.searchmoves.is_empty() {
+        command.push_str(" searchmoves ");
+        command.push_str(&limits.searchmoves.join(" "));
+    }
+    command
 }
 
-/// Build the "go" command with depth limit
-/// Format: "go depth <depth>"
-pub fn build_go_depth_command(depth: u32) -> String {
-    format!("go depth {}", depth)
+/// Build the "ponderhit" command
+/// Tells the engine the expected move was played
+pub fn build_ponderhit_command() -> String {
+    "ponderhit".to_string()
 }
 
 /// Build the "stop" command
@@ -72,6 +222,12 @@ pub fn build_setoption_command(name: &str, value: &str) -> String {
     format!("setoption name {} value {}", name, value)
 }
 
+/// Build a value-less "setoption" command for button options
+/// Format: "setoption name <name>"
+pub fn build_setoption_button_command(name: &str) -> String {
+    format!("setoption name {}", name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -106,21 +262,226 @@ mod tests {
     }
 
     #[test]
-    fn test_build_go_byoyomi_command() {
-        assert_eq!(build_go_byoyomi_command(1000), "go byoyomi 1000");
+    fn test_build_position_command_startpos() {
+        let moves = vec!["7g7f".to_string(), "3c3d".to_string()];
+        assert_eq!(build_position_command(STARTPOS, &[]), "position startpos");
+        assert_eq!(
+            build_position_command(STARTPOS_SFEN, &moves),
+            "position startpos moves 7g7f 3c3d"
+        );
+        assert!(!is_startpos("4k4/9/9/9/9/9/9/9/4K4 b - 1"));
+    }
+
+    #[test]
+    fn test_build_go_command() {
+        assert_eq!(
+            build_go_command(&GoLimits::byoyomi(1000)),
+            "go byoyomi 1000"
+        );
+        assert_eq!(
+            build_go_command(&GoLimits {
+                depth: Some(8),
+                ..Default::default()
+            }),
+            "go depth 8"
+        );
+        let limits = GoLimits {
+            btime: Some(60000),
+            wtime: Some(50000),
+            byoyomi: Some(10000),
+            ..Default::default()
+        };
+        assert_eq!(
+            build_go_command(&limits),
+            "go btime 60000 wtime 50000 byoyomi 10000"
+        );
+
+        let limits = GoLimits {
+            ponder: true,
+            ..GoLimits::byoyomi(1000)
+        };
+        assert_eq!(build_go_command(&limits), "go ponder byoyomi 1000");
+
+        let limits = GoLimits {
+            btime: Some(60000),
+            wtime: Some(50000),
+            binc: Some(2000),
+            winc: Some(2000),
+            ..Default::default()
+        };
+        assert_eq!(
+            build_go_command(&limits),
+            "go btime 60000 wtime 50000 binc 2000 winc 2000"
+        );
+        assert_eq!(
+            build_go_command(&GoLimits {
+                nodes: Some(100000),
+                ..Default::default()
+            }),
+            "go nodes 100000"
+        );
+        assert_eq!(
+            build_go_command(&GoLimits {
+                movetime: Some(500),
+                ..Default::default()
+            }),
+            "go movetime 500"
+        );
+        assert_eq!(
+            build_go_command(&GoLimits {
+                infinite: true,
+                ..Default::default()
+            }),
+            "go infinite"
+        );
+        assert_eq!(
+            build_go_command(&GoLimits::mate(MateLimit::Time(3000))),
+            "go mate 3000"
+        );
+        assert_eq!(
+            build_go_command(&GoLimits::mate(MateLimit::Infinite)),
+            "go mate infinite"
+        );
+
+        let limits = GoLimits {
+            depth: Some(6),
+            searchmoves: vec!["7g7f".to_string(), "2g2f".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            build_go_command(&limits),
+            "go depth 6 searchmoves 7g7f 2g2f"
+        );
+    }
+
+    #[test]
+    fn test_go_limits_validate() {
+        assert!(GoLimits::default().validate().is_ok());
+        assert!(GoLimits::byoyomi(1000).validate().is_ok());
+        assert!(GoLimits {
+            infinite: true,
+            ..Default::default()
+        }
+        .validate()
+        .is_ok());
+        assert!(GoLimits::mate(MateLimit::Infinite).validate().is_ok());
+        let clock = GoLimits {
+            btime: Some(60000),
+            wtime: Some(60000),
+            ..Default::default()
+        };
+        let with_byoyomi = GoLimits {
+            byoyomi: Some(10000),
+            depth: Some(20),
+            ..clock.clone()
+        };
+        assert!(with_byoyomi.validate().is_ok());
+
+        let invalid = [
+            GoLimits {
+                binc: Some(1000),
+                winc: Some(1000),
+                ..with_byoyomi.clone()
+            },
+            GoLimits {
+                btime: Some(60000),
+                ..Default::default()
+            },
+            GoLimits {
+                binc: Some(1000),
+                ..clock.clone()
+            },
+            GoLimits {
+                binc: Some(1000),
+                winc: Some(1000),
+                ..Default::default()
+            },
+            GoLimits {
+                movetime: Some(1000),
+                ..GoLimits::byoyomi(1000)
+            },
+            GoLimits {
+                depth: Some(5),
+                ..GoLimits::mate(MateLimit::Time(1000))
+            },
+            GoLimits {
+                infinite: true,
+                ..clock.clone()
+            },
+            GoLimits {
+                infinite: true,
+                ponder: true,
+                ..Default::default()
+            },
+            GoLimits {
+                searchmoves: vec!["7g7f".to_string(), "xyz".to_string()],
+                ..Default::default()
+            },
+        ];
+        for limits in invalid {
+            assert!(limits.validate().is_err(), "{:?}", limits);
+        }
+        assert_eq!(
+            GoLimits {
+                binc: Some(1000),
+                winc: Some(1000),
+                ..with_byoyomi
+            }
+            .validate(),
+            Err("byoyomi cannot be combined with binc and winc".to_string())
+        );
+    }
+
+    #[test]
+    fn test_go_limits_deserialize() {
+        let limits: GoLimits =
+            serde_json::from_str(r#"{"nodes": 5000, "mate": {"type": "infinite"}}"#).unwrap();
+        assert_eq!(limits.nodes, Some(5000));
+        assert_eq!(limits.mate, Some(MateLimit::Infinite));
+        assert!(!limits.infinite);
+        assert!(limits.searchmoves.is_empty());
     }
 
     #[test]
-    fn test_build_go_time_command() {
+    fn test_go_limits_move_time() {
+        assert_eq!(GoLimits::byoyomi(1000).move_time_ms(true), Some(1000));
         assert_eq!(
-            build_go_time_command(60000, 60000, 0, 0),
-            "go btime 60000 wtime 60000 binc 0 winc 0"
+            GoLimits {
+                depth: Some(5),
+                ..Default::default()
+            }
+            .move_time_ms(true),
+            None
+        );
+        let limits = GoLimits {
+            btime: Some(60000),
+            wtime: Some(30000),
+            winc: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(limits.move_time_ms(true), Some(2000));
+        assert_eq!(limits.move_time_ms(false), Some(2000));
+        assert_eq!(
+            GoLimits {
+                movetime: Some(700),
+                ..Default::default()
+            }
+            .move_time_ms(false),
+            Some(700)
+        );
+        assert_eq!(
+            GoLimits {
+                infinite: true,
+                ..Default::default()
+            }
+            .move_time_ms(true),
+            None
         );
     }
 
     #[test]
-    fn test_build_go_depth_command() {
-        assert_eq!(build_go_depth_command(10), "go depth 10");
+    fn test_build_ponderhit_command() {
+        assert_eq!(build_ponderhit_command(), "ponderhit");
     }
 
     #[test]
@@ -145,4 +506,12 @@ mod tests {
             "setoption name USI_Hash value 256"
         );
     }
+
+    #[test]
+    fn test_build_setoption_button_command() {
+        assert_eq!(
+            build_setoption_button_command("ClearHash"),
+            "setoption name ClearHash"
+        );
+    }
 }
@@ -1,20 +1,100 @@
 // USI Engine process management
 // Handles real engine communication via stdin/stdout
 
+use std::collections::VecDeque;
 use std::io::{BufRead, BufReader, Write};
-use std::process::{Child, Command, Stdio};
+use std::process::{Child, ChildStderr, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use super::commands::*;
-use super::parser::{parse_usi_line, UsiResponse};
+use super::error::UsiError;
+use super::options::EngineInfo;
+use super::parser::{BestMove, TsumeResult, UsiResponse};
+use super::response_stream::{ResponseStream, Subscription, RESPONSE_BUFFER_SIZE};
+use super::shogi_engine::{GoResult, InfoCallback, ShogiEngine, ShutdownOutcome, StopHandle};
+use crate::shogi::DeclarationRule;
+
+/// Extra time allowed for the engine to send "bestmove" after its time budget
+const BESTMOVE_GRACE_MS: u64 = 5000;
+
+/// How long a search that overran its time gets to answer "stop"
+const STOP_GRACE: Duration = Duration::from_secs(1);
+
+/// Default time allowed for "usiok" and "readyok"
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Lines of stderr kept in the log
+const STDERR_LOG_LINES: usize = 200;
+
+/// Lines of stderr attached to a `ProcessExited` error
+const EXIT_STDERR_LINES: usize = 10;
+
+/// How long a process whose output closed may take to finish exiting
+const EXIT_WAIT: Duration = Duration::from_secs(1);
+
+/// How often a process that is expected to exit is checked on
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Time an engine gets to quit on its own before it is killed, e.g. to
+/// finish writing learning files
+pub const DEFAULT_QUIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Restart a crashed engine and replay its options, game and position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Restarts allowed over the engine's lifetime
+    pub max_restarts: u32,
+}
+
+/// Most recent stderr lines of the engine process
+#[derive(Default)]
+struct StderrLog {
+    lines: VecDeque<String>,
+}
+
+impl StderrLog {
+    fn push(&mut self, line: String) {
+        if self.lines.len() == STDERR_LOG_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    fn tail(&self, count: usize) -> Vec<String> {
+        let skip = self.lines.len().saturating_sub(count);
+        self.lines.iter().skip(skip).cloned().collect()
+    }
+}
 
 /// USI Engine manager
 pub struct UsiEngine {
     child: Option<Child>,
-    stdin: Option<std::process::ChildStdin>,
-    response_buffer: Arc<Mutex<Vec<String>>>,
+    /// Set once the process is known to have exited
+    exit_status: Option<ExitStatus>,
+    /// Shared so a running `go` can be stopped from another thread
+    stdin: Arc<Mutex<Option<ChildStdin>>>,
+    /// Parsed stdout of the current process
+    responses: ResponseStream,
+    /// Subscription the engine itself reads replies from
+    replies: Option<Subscription>,
+    /// Read continuously so a chatty engine never blocks on a full pipe
+    stderr_log: Arc<Mutex<StderrLog>>,
+    stderr_reader: Option<JoinHandle<()>>,
+    info_callback: Option<InfoCallback>,
+    engine_info: EngineInfo,
+    handshake_timeout: Duration,
+    engine_path: Option<String>,
+    restart_policy: Option<RestartPolicy>,
+    restarts: u32,
+    /// Option values sent since `init`, replayed after a restart
+    sent_options: Vec<(String, String)>,
+    /// Whether "usinewgame" was sent since `init`
+    in_game: bool,
+    /// Last position sent, replayed after a restart
+    position: Option<(String, Vec<String>)>,
 }
 
 impl UsiEngine {
@@ -22,170 +102,537 @@ impl UsiEngine {
     pub fn new() -> Self {
         UsiEngine {
             child: None,
-            stdin: None,
-            response_buffer: Arc::new(Mutex::new(Vec::new())),
+            exit_status: None,
+            stdin: Arc::new(Mutex::new(None)),
+            responses: ResponseStream::new(),
+            replies: None,
+            stderr_log: Arc::new(Mutex::new(StderrLog::default())),
+            stderr_reader: None,
+            info_callback: None,
+            engine_info: EngineInfo::default(),
+            handshake_timeout: HANDSHAKE_TIMEOUT,
+            engine_path: None,
+            restart_policy: None,
+            restarts: 0,
+            sent_options: Vec::new(),
+            in_game: false,
+            position: None,
         }
     }
 
     /// Start the engine process
-    pub fn start(&mut self, engine_path: &str) -> Result<(), String> {
+    pub fn start(&mut self, engine_path: &str) -> Result<(), UsiError> {
         let mut child = Command::new(engine_path)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
-            .map_err(|e| format!("Failed to start engine process: {}", e))?;
-
-        let stdin = child
-            .stdin
-            .take()
-            .ok_or("Failed to capture engine stdin")?;
-
-        let stdout = child
-            .stdout
-            .take()
-            .ok_or("Failed to capture engine stdout")?;
-
-        // Spawn a thread to read from stdout
-        let buffer = Arc::clone(&self.response_buffer);
-        thread::spawn(move || {
-            let reader = BufReader::new(stdout);
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    let mut buf = buffer.lock().unwrap();
-                    buf.push(line);
-                }
-            }
-        });
+            .map_err(|e| UsiError::SpawnFailed {
+                reason: e.to_string(),
+            })?;
+
+        let stdin = child.stdin.take().ok_or_else(|| UsiError::SpawnFailed {
+            reason: "Failed to capture engine stdin".to_string(),
+        })?;
+
+        let stdout = child.stdout.take().ok_or_else(|| UsiError::SpawnFailed {
+            reason: "Failed to capture engine stdout".to_string(),
+        })?;
+
+        let stderr = child.stderr.take().ok_or_else(|| UsiError::SpawnFailed {
+            reason: "Failed to capture engine stderr".to_string(),
+        })?;
+
+        // Each process gets its own stream, read and parsed on a background thread
+        self.responses = ResponseStream::new();
+        self.replies = Some(self.responses.subscribe(RESPONSE_BUFFER_SIZE));
+        self.responses.spawn_reader(stdout);
+        self.stderr_reader = Some(drain_stderr(stderr, Arc::clone(&self.stderr_log)));
 
         self.child = Some(child);
-        self.stdin = Some(stdin);
+        self.exit_status = None;
+        *self.stdin.lock().unwrap() = Some(stdin);
+        self.engine_path = Some(engine_path.to_string());
 
         Ok(())
     }
 
+    /// Restart the process when it turns out to have crashed, during a search
+    /// or between commands, at most `max_restarts` times; None disables restarts
+    pub fn set_restart_policy(&mut self, policy: Option<RestartPolicy>) {
+        self.restart_policy = policy;
+    }
+
+    /// How many times the process has been restarted
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    /// How long to wait for "usiok" and "readyok"; engines loading large
+    /// evaluation files may need more than the default
+    pub fn set_handshake_timeout(&mut self, timeout: Duration) {
+        self.handshake_timeout = timeout;
+    }
+
     /// Send a command to the engine
-    pub fn send_command(&mut self, command: &str) -> Result<(), String> {
-        if let Some(stdin) = &mut self.stdin {
-            writeln!(stdin, "{}", command)
-                .map_err(|e| format!("Failed to write to engine: {}", e))?;
-            stdin
-                .flush()
-                .map_err(|e| format!("Failed to flush stdin: {}", e))?;
-            Ok(())
-        } else {
-            Err("Engine not started".to_string())
+    pub fn send_command(&mut self, command: &str) -> Result<(), UsiError> {
+        match write_command(&self.stdin, command) {
+            // Writing usually fails because the process is gone
+            Err(error @ UsiError::Io { .. }) => Err(self.exit_error(error)),
+            result => result,
         }
     }
 
-    /// Read a single response line from the engine
-    fn read_response_line(&self, timeout_ms: u64) -> Result<String, String> {
-        let start = std::time::Instant::now();
-        loop {
-            {
-                let mut buffer = self.response_buffer.lock().unwrap();
-                if !buffer.is_empty() {
-                    return Ok(buffer.remove(0));
-                }
+    /// Everything the engine recently wrote to stderr, oldest first
+    pub fn stderr_log(&self) -> Vec<String> {
+        self.stderr_log.lock().unwrap().tail(STDERR_LOG_LINES)
+    }
+
+    /// Wait for the next response from the engine
+    /// `on_timeout` is returned when nothing arrives in time
+    fn read_response(
+        &mut self,
+        timeout: Duration,
+        on_timeout: UsiError,
+    ) -> Result<UsiResponse, UsiError> {
+        let replies = self.replies.as_ref().ok_or(UsiError::NotStarted)?;
+        match replies.recv_timeout(timeout) {
+            Ok(response) => Ok(response),
+            Err(RecvTimeoutError::Timeout) => Err(on_timeout),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(self.exit_error(UsiError::ProtocolViolation {
+                    detail: "engine closed its output".to_string(),
+                }))
             }
+        }
+    }
+
+    /// Wait briefly for the process to exit and describe how it ended,
+    /// or return `otherwise` if it keeps running
+    fn exit_error(&mut self, otherwise: UsiError) -> UsiError {
+        let Some(child) = self.child.as_mut() else {
+            return UsiError::NotStarted;
+        };
 
-            if start.elapsed().as_millis() > timeout_ms as u128 {
-                return Err("Timeout waiting for engine response".to_string());
+        let deadline = Instant::now() + EXIT_WAIT;
+        let Some(status) = wait_for_exit(child, deadline) else {
+            return otherwise;
+        };
+        self.exit_status = Some(status);
+
+        // Give the stderr reader a moment to log the process's last words
+        if let Some(reader) = &self.stderr_reader {
+            while !reader.is_finished() && Instant::now() < deadline {
+                thread::sleep(EXIT_POLL_INTERVAL);
             }
+        }
 
-            thread::sleep(Duration::from_millis(10));
+        UsiError::ProcessExited {
+            code: status.code(),
+            last_stderr: self.stderr_log.lock().unwrap().tail(EXIT_STDERR_LINES),
         }
     }
 
-    /// Initialize the engine
-    pub fn init(&mut self) -> Result<(), String> {
-        // Send "usi" command
+    /// Get the best move for a position within the given limits
+    pub fn get_best_move(&mut self, sfen: &str, limits: &GoLimits) -> Result<BestMove, UsiError> {
+        self.set_position(sfen, &[])?;
+        Ok(self.go(limits)?.best_move)
+    }
+
+    /// Check if the engine is running
+    pub fn is_running(&self) -> bool {
+        self.child.is_some() && self.exit_status.is_none()
+    }
+
+    /// How long to wait for "bestmove": the whole clock of the slower side
+    /// (or the fixed move or mate time) plus a grace period, or indefinitely
+    /// for depth, node and infinite searches and for pondering, which lasts
+    /// until "ponderhit" or "stop"
+    fn bestmove_timeout_ms(limits: &GoLimits) -> u64 {
+        if limits.ponder || limits.infinite {
+            return u64::MAX;
+        }
+        match limits.mate {
+            Some(MateLimit::Time(time_ms)) => return time_ms as u64 + BESTMOVE_GRACE_MS,
+            Some(MateLimit::Infinite) => return u64::MAX,
+            None => {}
+        }
+        let clock = limits.btime.max(limits.wtime).unwrap_or(0) as u64;
+        let inc = limits.binc.max(limits.winc).unwrap_or(0) as u64;
+        let byoyomi = limits.byoyomi.unwrap_or(0) as u64;
+        let movetime = limits.movetime.unwrap_or(0) as u64;
+        let total = clock + inc + byoyomi + movetime;
+        if total == 0 {
+            u64::MAX
+        } else {
+            total + BESTMOVE_GRACE_MS
+        }
+    }
+
+    /// Send "usi" and collect identity and option declarations until "usiok"
+    fn handshake(&mut self) -> Result<EngineInfo, UsiError> {
         self.send_command(&build_usi_command())?;
 
-        // Wait for "usiok" response
+        let mut info = EngineInfo::default();
         loop {
-            let line = self.read_response_line(5000)?;
-            match parse_usi_line(&line) {
+            let timeout = UsiError::HandshakeTimeout { reply: "usiok" };
+            match self.read_response(self.handshake_timeout, timeout)? {
+                UsiResponse::IdName(name) => info.name = Some(name),
+                UsiResponse::IdAuthor(author) => info.author = Some(author),
+                UsiResponse::Option(decl) => info.options.push(decl),
                 UsiResponse::UsiOk => break,
+                UsiResponse::BestMove { .. } => {
+                    return Err(UsiError::ProtocolViolation {
+                        detail: "bestmove before usiok".to_string(),
+                    })
+                }
                 _ => continue,
             }
         }
 
-        // Send "isready" command
+        self.engine_info = info.clone();
+        Ok(info)
+    }
+
+    /// Send "isready" and wait for "readyok"
+    fn ready(&mut self) -> Result<(), UsiError> {
         self.send_command(&build_isready_command())?;
 
-        // Wait for "readyok" response
         loop {
-            let line = self.read_response_line(5000)?;
-            match parse_usi_line(&line) {
-                UsiResponse::ReadyOk => break,
-                _ => continue,
+            let timeout = UsiError::HandshakeTimeout { reply: "readyok" };
+            if let UsiResponse::ReadyOk = self.read_response(self.handshake_timeout, timeout)? {
+                return Ok(());
             }
         }
-
-        Ok(())
     }
 
-    /// Get the best move for a position
-    pub fn get_best_move(&mut self, sfen: &str, time_ms: u32) -> Result<String, String> {
-        // Send position command
-        self.send_command(&build_position_command(sfen, &[]))?;
+    /// Send "go" and wait for the bestmove, forwarding info lines to the callback
+    fn search(&mut self, limits: &GoLimits) -> Result<GoResult, UsiError> {
+        self.discard_stale_replies();
+        self.send_command(&build_go_command(limits))?;
 
-        // Send go command
-        self.send_command(&build_go_byoyomi_command(time_ms))?;
+        let timeout = Duration::from_millis(Self::bestmove_timeout_ms(limits));
+        loop {
+            let response = match self.read_response(timeout, UsiError::SearchTimeout) {
+                Err(UsiError::SearchTimeout) => return Err(self.abandon_search()),
+                response => response?,
+            };
+            match response {
+                UsiResponse::BestMove { best_move, ponder } => {
+                    return Ok(GoResult { best_move, ponder })
+                }
+                UsiResponse::Info(info) => {
+                    if let Some(cb) = self.info_callback.as_mut() {
+                        cb(&info);
+                    }
+                }
+                UsiResponse::Unknown(line) if line.starts_with("bestmove") => {
+                    return Err(UsiError::ProtocolViolation {
+                        detail: format!("malformed bestmove: {}", line),
+                    })
+                }
+                UsiResponse::Checkmate(_) => {
+                    return Err(UsiError::ProtocolViolation {
+                        detail: "checkmate in reply to go".to_string(),
+                    })
+                }
+                _ => continue,
+            }
+        }
+    }
 
-        // Wait for bestmove response
-        let timeout_ms = time_ms as u64 + 5000; // Add 5 seconds buffer
+    /// Send "go mate" and wait for the checkmate answer, forwarding info lines
+    fn mate_search(&mut self, limit: MateLimit) -> Result<TsumeResult, UsiError> {
+        let limits = GoLimits::mate(limit);
+        self.discard_stale_replies();
+        self.send_command(&build_go_command(&limits))?;
+
+        let timeout = Duration::from_millis(Self::bestmove_timeout_ms(&limits));
         loop {
-            let line = self.read_response_line(timeout_ms)?;
-            match parse_usi_line(&line) {
-                UsiResponse::BestMove { best_move, .. } => return Ok(best_move),
-                UsiResponse::Info(_) => continue, // Ignore info lines
+            let response = match self.read_response(timeout, UsiError::SearchTimeout) {
+                Err(UsiError::SearchTimeout) => return Err(self.abandon_search()),
+                response => response?,
+            };
+            match response {
+                UsiResponse::Checkmate(result) => return Ok(result),
+                UsiResponse::Info(info) => {
+                    if let Some(cb) = self.info_callback.as_mut() {
+                        cb(&info);
+                    }
+                }
+                UsiResponse::BestMove { .. } => {
+                    return Err(UsiError::ProtocolViolation {
+                        detail: "bestmove in reply to go mate".to_string(),
+                    })
+                }
                 _ => continue,
             }
         }
     }
 
-    /// Stop the engine from thinking
-    pub fn stop(&mut self) -> Result<(), String> {
-        self.send_command(&build_stop_command())
+    /// Drop replies left over from an earlier search, such as the bestmove
+    /// that followed a protocol violation, so they are not taken as the answer
+    /// to the next "go"
+    fn discard_stale_replies(&mut self) {
+        if let Some(replies) = &self.replies {
+            while replies.try_recv().is_some() {}
+        }
     }
 
-    /// Quit the engine
-    pub fn quit(&mut self) -> Result<(), String> {
-        self.send_command(&build_quit_command())?;
+    /// Stop a search that overran its time and consume the answer it still
+    /// owes, waiting up to `STOP_GRACE`; returns the timeout to report
+    fn abandon_search(&mut self) -> UsiError {
+        if self.send_command(&build_stop_command()).is_ok() {
+            if let Some(replies) = &self.replies {
+                let deadline = Instant::now() + STOP_GRACE;
+                while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                    match replies.recv_timeout(left) {
+                        Ok(UsiResponse::BestMove { .. } | UsiResponse::Checkmate(_)) => break,
+                        Ok(_) => continue,
+                        Err(_) => break,
+                    }
+                }
+            }
+        }
+        UsiError::SearchTimeout
+    }
 
-        // Wait a bit for the engine to quit
-        thread::sleep(Duration::from_millis(100));
+    /// Send a command that sets the engine up, first restarting a process
+    /// that crashed while idle if the restart policy allows it
+    fn send_or_restart(&mut self, command: &str) -> Result<(), UsiError> {
+        match self.send_command(command) {
+            Err(UsiError::ProcessExited { .. }) if self.can_restart() => {
+                self.restart()?;
+                self.send_command(command)
+            }
+            result => result,
+        }
+    }
+
+    fn can_restart(&self) -> bool {
+        self.restart_policy
+            .is_some_and(|policy| self.restarts < policy.max_restarts)
+    }
+
+    /// Start a fresh process and bring it to where the old one was:
+    /// the same options, game and position
+    pub fn restart(&mut self) -> Result<(), UsiError> {
+        let path = self.engine_path.clone().ok_or(UsiError::NotStarted)?;
+        self.restarts += 1;
+        self.kill();
+        self.start(&path)?;
+
+        self.handshake()?;
+        for (name, value) in self.sent_options.clone() {
+            self.send_command(&build_setoption_command(&name, &value))?;
+        }
+        self.ready()?;
+
+        if self.in_game {
+            self.send_command(&build_usinewgame_command())?;
+        }
+        if let Some((sfen, moves)) = self.position.clone() {
+            self.send_command(&build_position_command(&sfen, &moves))?;
+        }
+        Ok(())
+    }
 
-        // Kill the process if it's still running
-        if let Some(child) = &mut self.child {
+    /// Kill the process and forget its pipes
+    fn kill(&mut self) {
+        if let Some(mut child) = self.detach() {
             let _ = child.kill();
             let _ = child.wait();
         }
+    }
+
+    /// Take the process out of the engine and close its stdin, which tells
+    /// engines that ignore "quit" to exit as well
+    fn detach(&mut self) -> Option<Child> {
+        self.exit_status = None;
+        self.replies = None;
+        *self.stdin.lock().unwrap() = None;
+        self.child.take()
+    }
+}
+
+impl ShogiEngine for UsiEngine {
+    /// Initialize the engine and read what it announces about itself
+    fn init(&mut self) -> Result<EngineInfo, UsiError> {
+        self.sent_options.clear();
+        self.in_game = false;
+        self.position = None;
 
-        self.child = None;
-        self.stdin = None;
+        let info = self.handshake()?;
+        self.ready()?;
 
+        Ok(info)
+    }
+
+    fn wait_ready(&mut self) -> Result<(), UsiError> {
+        self.ready()
+    }
+
+    /// Start a new game, restarting a process that crashed since the last command
+    fn new_game(&mut self) -> Result<(), UsiError> {
+        self.send_or_restart(&build_usinewgame_command())?;
+        self.in_game = true;
         Ok(())
     }
 
-    /// Check if the engine is running
-    pub fn is_running(&self) -> bool {
-        self.child.is_some()
+    /// Set an engine option after checking it against the declarations from `init`
+    fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), UsiError> {
+        match self.engine_info.validate_option(name, value)? {
+            Some(value) => {
+                self.send_or_restart(&build_setoption_command(name, &value))?;
+                match self.sent_options.iter_mut().find(|(n, _)| n == name) {
+                    Some((_, sent)) => *sent = value,
+                    None => self.sent_options.push((name.to_string(), value)),
+                }
+            }
+            // Buttons are actions, not settings, so they are not replayed
+            None => self.send_or_restart(&build_setoption_button_command(name))?,
+        }
+        Ok(())
+    }
+
+    /// Send the position command, restarting a process that crashed since the
+    /// last command
+    fn set_position(&mut self, sfen: &str, moves: &[String]) -> Result<(), UsiError> {
+        self.send_or_restart(&build_position_command(sfen, moves))?;
+        self.position = Some((sfen.to_string(), moves.to_vec()));
+        Ok(())
+    }
+
+    /// The engine follows its own entering-king option; its declarations are
+    /// checked against the game's rule
+    fn set_declaration_rule(&mut self, _rule: DeclarationRule) {}
+
+    /// Start searching and wait for the bestmove response
+    /// Invalid limits are rejected before anything is sent
+    /// If the process crashes and the restart policy allows it, the search is
+    /// repeated on a restarted process
+    fn go(&mut self, limits: &GoLimits) -> Result<GoResult, UsiError> {
+        limits.validate()?;
+        if limits.mate.is_some() {
+            return Err("Mate searches are started with go_mate".into());
+        }
+        match self.search(limits) {
+            Err(UsiError::ProcessExited { .. }) if self.can_restart() => {
+                self.restart()?;
+                self.search(limits)
+            }
+            result => result,
+        }
+    }
+
+    /// Start a mate search and wait for the checkmate response,
+    /// repeating it on a restarted process like `go`
+    fn go_mate(&mut self, limit: MateLimit) -> Result<TsumeResult, UsiError> {
+        match self.mate_search(limit) {
+            Err(UsiError::ProcessExited { .. }) if self.can_restart() => {
+                self.restart()?;
+                self.mate_search(limit)
+            }
+            result => result,
+        }
+    }
+
+    /// Info lines received during `go` are forwarded to the callback
+    fn set_info_callback(&mut self, callback: Option<InfoCallback>) {
+        self.info_callback = callback;
+    }
+
+    /// Stop the engine from thinking
+    fn stop(&mut self) -> Result<(), UsiError> {
+        self.send_command(&build_stop_command())
+    }
+
+    /// Writes "stop" and "ponderhit" directly to stdin, so they work while
+    /// `go` waits for bestmove
+    fn stop_handle(&self) -> StopHandle {
+        let stdin = Arc::clone(&self.stdin);
+        let ponder_stdin = Arc::clone(&self.stdin);
+        StopHandle::new(move || write_command(&stdin, &build_stop_command()))
+            .with_ponderhit(move || write_command(&ponder_stdin, &build_ponderhit_command()))
+    }
+
+    /// Every USI engine has to understand "go ponder"
+    fn can_ponder(&self) -> bool {
+        true
+    }
+
+    /// Tell the engine the ponder move was played
+    fn ponderhit(&mut self) -> Result<(), UsiError> {
+        self.send_command(&build_ponderhit_command())
+    }
+
+    /// Send "quit" and wait up to `timeout` for the process to exit,
+    /// killing it if it does not
+    fn quit(&mut self, timeout: Duration) -> Result<ShutdownOutcome, UsiError> {
+        if self.child.is_none() {
+            return Ok(ShutdownOutcome::NotRunning);
+        }
+        // The process may already be gone; waiting below finds out either way
+        let _ = write_command(&self.stdin, &build_quit_command());
+
+        match self.detach() {
+            Some(mut child) => Ok(finish_process(&mut child, timeout)),
+            None => Ok(ShutdownOutcome::NotRunning),
+        }
     }
 
-    /// Start a new game
-    pub fn new_game(&mut self) -> Result<(), String> {
-        self.send_command(&build_usinewgame_command())
+    /// The engine is ready once its process is running
+    fn is_ready(&self) -> bool {
+        self.is_running()
     }
+}
+
+/// Write one command line to the engine's stdin
+fn write_command(stdin: &Mutex<Option<ChildStdin>>, command: &str) -> Result<(), UsiError> {
+    let mut stdin = stdin.lock().map_err(|e| UsiError::Io {
+        reason: e.to_string(),
+    })?;
+    let stdin = stdin.as_mut().ok_or(UsiError::NotStarted)?;
+    writeln!(stdin, "{}", command).map_err(|e| UsiError::Io {
+        reason: e.to_string(),
+    })?;
+    stdin.flush().map_err(|e| UsiError::Io {
+        reason: e.to_string(),
+    })
+}
 
-    /// Set an engine option
-    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
-        self.send_command(&build_setoption_command(name, value))
+/// Poll the process until it exits or `deadline` passes
+fn wait_for_exit(child: &mut Child, deadline: Instant) -> Option<ExitStatus> {
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) if Instant::now() < deadline => thread::sleep(EXIT_POLL_INTERVAL),
+            _ => return None,
+        }
     }
 }
 
+/// Give the process up to `timeout` to exit on its own, then kill it
+fn finish_process(child: &mut Child, timeout: Duration) -> ShutdownOutcome {
+    if let Some(status) = wait_for_exit(child, Instant::now() + timeout) {
+        return ShutdownOutcome::Exited {
+            code: status.code(),
+        };
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    ShutdownOutcome::Killed
+}
+
+/// Read stderr into the log until the process closes it
+fn drain_stderr(stderr: ChildStderr, log: Arc<Mutex<StderrLog>>) -> JoinHandle<()> {
+    thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            log.lock().unwrap().push(line);
+        }
+    })
+}
+
 impl Default for UsiEngine {
     fn default() -> Self {
         Self::new()
@@ -193,8 +640,13 @@ impl Default for UsiEngine {
 }
 
 impl Drop for UsiEngine {
+    /// Ask the engine to quit and let a background thread wait for it, so
+    /// dropping never blocks the caller
     fn drop(&mut self) {
-        let _ = self.quit();
+        let _ = write_command(&self.stdin, &build_quit_command());
+        if let Some(mut child) = self.detach() {
+            thread::spawn(move || finish_process(&mut child, DEFAULT_QUIT_TIMEOUT));
+        }
     }
 }
 
@@ -208,6 +660,464 @@ mod tests {
         assert!(!engine.is_running());
     }
 
-    // Note: Real engine tests would require an actual USI engine binary
-    // These are integration tests that should be run separately
+    #[test]
+    fn test_bestmove_timeout() {
+        assert_eq!(
+            UsiEngine::bestmove_timeout_ms(&GoLimits::byoyomi(1000)),
+            1000 + BESTMOVE_GRACE_MS
+        );
+        assert_eq!(
+            UsiEngine::bestmove_timeout_ms(&GoLimits {
+                depth: Some(10),
+                ..Default::default()
+            }),
+            u64::MAX
+        );
+        assert_eq!(
+            UsiEngine::bestmove_timeout_ms(&GoLimits {
+                movetime: Some(300),
+                ..Default::default()
+            }),
+            300 + BESTMOVE_GRACE_MS
+        );
+        assert_eq!(
+            UsiEngine::bestmove_timeout_ms(&GoLimits::mate(MateLimit::Time(2000))),
+            2000 + BESTMOVE_GRACE_MS
+        );
+        assert_eq!(
+            UsiEngine::bestmove_timeout_ms(&GoLimits::mate(MateLimit::Infinite)),
+            u64::MAX
+        );
+        assert_eq!(
+            UsiEngine::bestmove_timeout_ms(&GoLimits {
+                infinite: true,
+                ..Default::default()
+            }),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn test_invalid_limits_are_rejected() {
+        let mut engine = UsiEngine::new();
+        let limits = GoLimits {
+            binc: Some(1000),
+            winc: Some(1000),
+            btime: Some(60000),
+            wtime: Some(60000),
+            ..GoLimits::byoyomi(1000)
+        };
+        assert_eq!(
+            engine.go(&limits).unwrap_err(),
+            UsiError::Rejected {
+                reason: "byoyomi cannot be combined with binc and winc".to_string()
+            }
+        );
+    }
+
+    /// Write a shell script that answers like a minimal USI engine
+    #[cfg(unix)]
+    pub(crate) fn fake_engine(name: &str, script: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("{}_{}.sh", name, std::process::id()));
+        std::fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[cfg(unix)]
+    const FAKE_ENGINE: &str = r#"
+while read -r cmd; do
+  case "$cmd" in
+    usi) echo "id name Fake"; echo "option name Threads type spin default 1 min 1 max 4"; echo usiok ;;
+    isready) echo readyok ;;
+    go*) echo "info depth 1 score cp 12 pv 7g7f"; echo "bestmove 7g7f ponder 3c3d" ;;
+    quit) exit 0 ;;
+  esac
+done
+"#;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_fake_engine_session() {
+        let mut engine = UsiEngine::new();
+        engine
+            .start(&fake_engine("usi_session", FAKE_ENGINE))
+            .unwrap();
+
+        let info = engine.init().unwrap();
+        assert_eq!(info.name.as_deref(), Some("Fake"));
+        assert!(info.option("Threads").is_some());
+
+        let depths = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&depths);
+        engine.set_info_callback(Some(Box::new(move |info| {
+            seen.lock().unwrap().push(info.depth);
+        })));
+        engine.set_position(STARTPOS, &[]).unwrap();
+        let result = engine.go(&GoLimits::byoyomi(1000)).unwrap();
+        assert_eq!(result.best_move.to_usi(), "7g7f");
+        assert_eq!(result.ponder.as_deref(), Some("3c3d"));
+        assert_eq!(*depths.lock().unwrap(), vec![Some(1)]);
+
+        assert_eq!(
+            engine.quit(DEFAULT_QUIT_TIMEOUT),
+            Ok(ShutdownOutcome::Exited { code: Some(0) })
+        );
+    }
+
+    #[test]
+    fn test_spawn_failure() {
+        let mut engine = UsiEngine::new();
+        let error = engine.start("/nonexistent/engine").unwrap_err();
+        assert!(matches!(error, UsiError::SpawnFailed { .. }));
+        assert!(!engine.is_running());
+    }
+
+    #[test]
+    fn test_stderr_log_keeps_latest_lines() {
+        let mut log = StderrLog::default();
+        for i in 0..STDERR_LOG_LINES + 5 {
+            log.push(i.to_string());
+        }
+        assert_eq!(log.lines.len(), STDERR_LOG_LINES);
+        assert_eq!(
+            log.tail(2),
+            vec![
+                (STDERR_LOG_LINES + 3).to_string(),
+                (STDERR_LOG_LINES + 4).to_string()
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_silent_engine_handshake_times_out() {
+        let mut engine = UsiEngine::new();
+        engine.set_handshake_timeout(Duration::from_millis(50));
+        engine
+            .start(&fake_engine("usi_silent", "while read -r cmd; do :; done"))
+            .unwrap();
+        assert_eq!(
+            engine.handshake(),
+            Err(UsiError::HandshakeTimeout { reply: "usiok" })
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_bestmove_during_handshake_is_a_protocol_violation() {
+        let script = "while read -r cmd; do echo \"bestmove 7g7f\"; done";
+        let mut engine = UsiEngine::new();
+        engine.start(&fake_engine("usi_violation", script)).unwrap();
+        assert!(matches!(
+            engine.handshake(),
+            Err(UsiError::ProtocolViolation { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_chatty_stderr_does_not_block_engine() {
+        // Far more than a pipe buffer holds, written before answering "usi"
+        let script = r#"
+read -r cmd
+i=0
+while [ $i -lt 3000 ]; do
+  echo "loading evaluation parameters, block $i of 3000 ......................" >&2
+  i=$((i+1))
+done
+echo usiok
+read -r cmd
+echo readyok
+while read -r cmd; do :; done
+"#;
+        let mut engine = UsiEngine::new();
+        engine.start(&fake_engine("usi_stderr", script)).unwrap();
+        engine.init().unwrap();
+        assert!(engine.stderr_log().last().unwrap().contains("block 2999"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_crash_reports_exit_code_and_stderr() {
+        let script = r#"
+while read -r cmd; do
+  case "$cmd" in
+    usi) echo usiok ;;
+    isready) echo readyok ;;
+    go*) echo "eval file not found" >&2; exit 3 ;;
+  esac
+done
+"#;
+        let mut engine = UsiEngine::new();
+        engine.start(&fake_engine("usi_crash", script)).unwrap();
+        engine.init().unwrap();
+
+        let error = engine.search(&GoLimits::byoyomi(1000)).unwrap_err();
+        assert_eq!(
+            error,
+            UsiError::ProcessExited {
+                code: Some(3),
+                last_stderr: vec!["eval file not found".to_string()],
+            }
+        );
+        assert!(!engine.is_running());
+        assert!(!engine.is_ready());
+
+        // Without a restart policy the crash is reported to the caller
+        assert!(engine.go(&GoLimits::byoyomi(1000)).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_restart_replays_options_and_position() {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let log = dir.join(format!("usi_restart_{}.log", id));
+        let crashed = dir.join(format!("usi_restart_{}.crashed", id));
+        let _ = std::fs::remove_file(&log);
+        let _ = std::fs::remove_file(&crashed);
+
+        // Crashes on its first "go" only, and records every command it receives
+        let script = format!(
+            r#"
+while read -r cmd; do
+  echo "$cmd" >> {log}
+  case "$cmd" in
+    usi) echo "option name Threads type spin default 1 min 1 max 4"; echo usiok ;;
+    isready) echo readyok ;;
+    go*) if [ -f {crashed} ]; then echo "bestmove 3c3d"; else touch {crashed}; exit 1; fi ;;
+  esac
+done
+"#,
+            log = log.display(),
+            crashed = crashed.display()
+        );
+
+        let mut engine = UsiEngine::new();
+        engine.set_restart_policy(Some(RestartPolicy { max_restarts: 1 }));
+        engine.start(&fake_engine("usi_restart", &script)).unwrap();
+        engine.init().unwrap();
+        engine.set_option("Threads", Some("2")).unwrap();
+        engine.new_game().unwrap();
+        engine
+            .set_position(STARTPOS, &["7g7f".to_string()])
+            .unwrap();
+
+        let result = engine.go(&GoLimits::byoyomi(1000)).unwrap();
+        assert_eq!(result.best_move.to_usi(), "3c3d");
+        assert_eq!(engine.restarts(), 1);
+
+        let commands = std::fs::read_to_string(&log).unwrap();
+        let replayed: Vec<&str> = commands.lines().skip(6).collect();
+        assert_eq!(
+            replayed,
+            vec![
+                "usi",
+                "setoption name Threads value 2",
+                "isready",
+                "usinewgame",
+                "position startpos moves 7g7f",
+                "go byoyomi 1000"
+            ]
+        );
+
+        std::fs::remove_file(&log).unwrap();
+        std::fs::remove_file(&crashed).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_leftover_replies_are_not_taken_for_the_next_answer() {
+        // The first "go" is answered with checkmate followed by a bestmove;
+        // "stop" is answered with a bestmove as well
+        let script = r#"
+answered=
+while read -r cmd; do
+  case "$cmd" in
+    usi) echo usiok ;;
+    isready) echo readyok ;;
+    stop) echo "bestmove 9g9f" ;;
+    go*) if [ -z "$answered" ]; then answered=1; echo "checkmate nomate"; echo "bestmove 1g1f"; else echo "bestmove 7g7f"; fi ;;
+  esac
+done
+"#;
+        let mut engine = UsiEngine::new();
+        engine.start(&fake_engine("usi_leftover", script)).unwrap();
+        engine.init().unwrap();
+        engine.set_position(STARTPOS, &[]).unwrap();
+
+        assert!(engine.go(&GoLimits::byoyomi(1000)).is_err());
+        thread::sleep(Duration::from_millis(50));
+        let result = engine.go(&GoLimits::byoyomi(1000)).unwrap();
+        assert_eq!(result.best_move.to_usi(), "7g7f");
+
+        // A search that timed out is stopped and its bestmove consumed
+        assert_eq!(engine.abandon_search(), UsiError::SearchTimeout);
+        thread::sleep(Duration::from_millis(50));
+        assert!(engine.replies.as_ref().unwrap().try_recv().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_restart_after_crash_between_searches() {
+        let crashed = std::env::temp_dir().join(format!("usi_idle_{}.crashed", std::process::id()));
+        let _ = std::fs::remove_file(&crashed);
+
+        // Exits right after its first "readyok", before any search
+        let script = format!(
+            r#"
+while read -r cmd; do
+  case "$cmd" in
+    usi) echo usiok ;;
+    isready) echo readyok; [ -f {crashed} ] || {{ touch {crashed}; exit 1; }} ;;
+    go*) echo "bestmove 7g7f" ;;
+  esac
+done
+"#,
+            crashed = crashed.display()
+        );
+
+        let mut engine = UsiEngine::new();
+        engine.set_restart_policy(Some(RestartPolicy { max_restarts: 1 }));
+        engine.start(&fake_engine("usi_idle", &script)).unwrap();
+        engine.init().unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        engine.set_position(STARTPOS, &[]).unwrap();
+        assert_eq!(engine.restarts(), 1);
+        let result = engine.go(&GoLimits::byoyomi(1000)).unwrap();
+        assert_eq!(result.best_move.to_usi(), "7g7f");
+
+        std::fs::remove_file(&crashed).unwrap();
+    }
+
+    #[test]
+    fn test_quit_without_process() {
+        let mut engine = UsiEngine::new();
+        assert_eq!(
+            engine.quit(DEFAULT_QUIT_TIMEOUT),
+            Ok(ShutdownOutcome::NotRunning)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_quit_waits_for_slow_exit() {
+        // Takes a while to exit after "quit", as when saving learning files
+        let script = "while read -r cmd; do [ \"$cmd\" = quit ] && break; done\nsleep 0.3\nexit 0";
+        let mut engine = UsiEngine::new();
+        engine.start(&fake_engine("usi_slow_quit", script)).unwrap();
+
+        assert_eq!(
+            engine.quit(DEFAULT_QUIT_TIMEOUT),
+            Ok(ShutdownOutcome::Exited { code: Some(0) })
+        );
+        assert!(!engine.is_running());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_quit_kills_engine_after_deadline() {
+        let script = "exec sleep 3";
+        let mut engine = UsiEngine::new();
+        engine.start(&fake_engine("usi_hung", script)).unwrap();
+
+        let start = Instant::now();
+        assert_eq!(
+            engine.quit(Duration::from_millis(100)),
+            Ok(ShutdownOutcome::Killed)
+        );
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_drop_does_not_block() {
+        let mut engine = UsiEngine::new();
+        engine
+            .start(&fake_engine("usi_hung_drop", "exec sleep 3"))
+            .unwrap();
+
+        let start = Instant::now();
+        drop(engine);
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ponder_search_continues_on_ponderhit() {
+        let script = r#"
+while read -r cmd; do
+  case "$cmd" in
+    usi) echo usiok ;;
+    isready) echo readyok ;;
+    "go ponder"*) read -r next; [ "$next" = ponderhit ] && echo "bestmove 2g2f" || echo "bestmove 1g1f" ;;
+  esac
+done
+"#;
+        let mut engine = UsiEngine::new();
+        engine.start(&fake_engine("usi_ponder", script)).unwrap();
+        engine.init().unwrap();
+        engine
+            .set_position(STARTPOS, &["7g7f".to_string(), "3c3d".to_string()])
+            .unwrap();
+
+        let handle = engine.stop_handle();
+        let limits = GoLimits {
+            ponder: true,
+            ..GoLimits::byoyomi(1000)
+        };
+        let search = thread::spawn(move || engine.go(&limits));
+
+        thread::sleep(Duration::from_millis(50));
+        handle.ponderhit().unwrap();
+        assert_eq!(search.join().unwrap().unwrap().best_move.to_usi(), "2g2f");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_go_mate_reads_checkmate_answer() {
+        let script = r#"
+while read -r cmd; do
+  case "$cmd" in
+    usi) echo usiok ;;
+    isready) echo readyok ;;
+    "go mate 1000") echo "info depth 3 nodes 120"; echo "checkmate G*5b 5a4a 5b4b" ;;
+    "go mate infinite") echo "checkmate nomate" ;;
+    go*) echo "checkmate timeout" ;;
+  esac
+done
+"#;
+        let mut engine = UsiEngine::new();
+        engine.start(&fake_engine("usi_mate", script)).unwrap();
+        engine.init().unwrap();
+        engine
+            .set_position("4k4/9/4P4/9/9/9/9/9/4K4 b G 1", &[])
+            .unwrap();
+
+        assert_eq!(
+            engine.go_mate(MateLimit::Time(1000)).unwrap(),
+            TsumeResult::Mate(vec![
+                "G*5b".to_string(),
+                "5a4a".to_string(),
+                "5b4b".to_string()
+            ])
+        );
+        assert_eq!(
+            engine.go_mate(MateLimit::Infinite).unwrap(),
+            TsumeResult::NoMate
+        );
+
+        // A checkmate answer is never a bestmove
+        assert_eq!(
+            engine.go(&GoLimits::byoyomi(1000)),
+            Err(UsiError::ProtocolViolation {
+                detail: "checkmate in reply to go".to_string()
+            })
+        );
+        assert!(engine.go(&GoLimits::mate(MateLimit::Infinite)).is_err());
+    }
 }
@@ -0,0 +1,196 @@
+// Errors from driving an engine, as the `ShogiEngine` trait and the Tauri
+// commands report them
+
+use serde::Serialize;
+use std::fmt;
+
+/// Why talking to an engine failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum UsiError {
+    /// The process could not be started
+    SpawnFailed { reason: String },
+    /// The engine did not answer "usi" or "isready" in time; holds the awaited reply
+    HandshakeTimeout { reply: &'static str },
+    /// No bestmove arrived within the time allowed for the search
+    SearchTimeout,
+    /// The process exited; `code` is None when it was killed by a signal
+    ProcessExited {
+        code: Option<i32>,
+        last_stderr: Vec<String>,
+    },
+    /// The engine sent something the protocol does not allow at this point
+    ProtocolViolation { detail: String },
+    /// No engine has been started
+    NotStarted,
+    /// Writing to the process failed while it was still running
+    Io { reason: String },
+    /// The request was refused before reaching the engine, e.g. an option the
+    /// engine does not declare, invalid limits or an illegal position
+    Rejected { reason: String },
+}
+
+impl fmt::Display for UsiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UsiError::SpawnFailed { reason } => {
+                write!(f, "Failed to start engine process: {}", reason)
+            }
+            UsiError::HandshakeTimeout { reply } => {
+                write!(f, "Timeout waiting for engine to answer with {}", reply)
+            }
+            UsiError::SearchTimeout => write!(f, "Timeout waiting for engine bestmove"),
+            UsiError::ProcessExited { code, last_stderr } => {
+                match code {
+                    Some(code) => write!(f, "Engine process exited with code {}", code)?,
+                    None => write!(f, "Engine process was terminated")?,
+                }
+                match last_stderr.last() {
+                    Some(line) => write!(f, ": {}", line),
+                    None => Ok(()),
+                }
+            }
+            UsiError::ProtocolViolation { detail } => {
+                write!(f, "Engine violated the USI protocol: {}", detail)
+            }
+            UsiError::NotStarted => write!(f, "Engine not started"),
+            UsiError::Io { reason } => write!(f, "Failed to write to engine: {}", reason),
+            UsiError::Rejected { reason } => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for UsiError {}
+
+/// Errors described only by a message are requests the engine cannot accept
+impl From<String> for UsiError {
+    fn from(reason: String) -> Self {
+        UsiError::Rejected { reason }
+    }
+}
+
+impl From<&str> for UsiError {
+    fn from(reason: &str) -> Self {
+        UsiError::Rejected {
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Commands about the game rather than the engine report errors as strings
+impl From<UsiError> for String {
+    fn from(error: UsiError) -> String {
+        error.to_string()
+    }
+}
+
+/// Error of the engine commands: the message to show, and the `UsiError`
+/// behind it for the frontend to act on (None for other failures, such as an
+/// unreadable options file)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineError {
+    pub message: String,
+    pub usi_error: Option<UsiError>,
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<UsiError> for EngineError {
+    fn from(error: UsiError) -> Self {
+        EngineError {
+            message: error.to_string(),
+            usi_error: Some(error),
+        }
+    }
+}
+
+impl From<String> for EngineError {
+    fn from(message: String) -> Self {
+        EngineError {
+            message,
+            usi_error: None,
+        }
+    }
+}
+
+impl From<&str> for EngineError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl From<EngineError> for String {
+    fn from(error: EngineError) -> String {
+        error.message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_exited_message() {
+        let error = UsiError::ProcessExited {
+            code: Some(1),
+            last_stderr: vec![
+                "loading eval".to_string(),
+                "eval file not found".to_string(),
+            ],
+        };
+        assert_eq!(
+            String::from(error),
+            "Engine process exited with code 1: eval file not found"
+        );
+
+        let error = UsiError::ProcessExited {
+            code: None,
+            last_stderr: Vec::new(),
+        };
+        assert_eq!(error.to_string(), "Engine process was terminated");
+    }
+
+    #[test]
+    fn test_engine_error_payload() {
+        let error = EngineError::from(UsiError::ProcessExited {
+            code: Some(3),
+            last_stderr: vec!["eval file not found".to_string()],
+        });
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "message": "Engine process exited with code 3: eval file not found",
+                "usiError": {
+                    "type": "processExited",
+                    "code": 3,
+                    "lastStderr": ["eval file not found"]
+                }
+            })
+        );
+
+        let error = EngineError::from("Failed to read engine options".to_string());
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({ "message": "Failed to read engine options", "usiError": null })
+        );
+    }
+
+    #[test]
+    fn test_handshake_timeout_message() {
+        assert_eq!(
+            UsiError::HandshakeTimeout { reply: "usiok" }.to_string(),
+            "Timeout waiting for engine to answer with usiok"
+        );
+    }
+}
@@ -1,89 +1,276 @@
 // Mock USI engine for testing without actual YaneuraOu binary
-// Returns random legal moves based on the position
+// Plays legal moves for the given position using a simple selectable strategy
 
-use std::collections::HashMap;
+use std::cell::Cell;
+#[cfg(test)]
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::commands::{GoLimits, MateLimit};
+use super::error::UsiError;
+use super::options::EngineInfo;
+use super::parser::{BestMove, ThinkingInfo, TsumeResult};
+use super::shogi_engine::{
+    position_after_moves, GoResult, InfoCallback, ShogiEngine, ShutdownOutcome, StopHandle,
+};
+use crate::search::{material_balance, TsumeLimits, TsumeOutcome, TsumeSolver};
+use crate::shogi::{DeclarationRule, Move, Position};
+
+/// Mixed into seeds so small seeds still give well-spread generator states
+const RNG_SEED_MIX: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// How the mock engine picks among the legal moves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum MockStrategy {
+    /// Uniformly random legal move (deterministic for a given seed)
+    Random { seed: u64 },
+    /// Random capture if one exists, otherwise a random move
+    CaptureFirst { seed: u64 },
+    /// Move with the best material balance after one ply (random among ties)
+    Greedy { seed: u64 },
+}
+
+impl MockStrategy {
+    fn seed(self) -> u64 {
+        match self {
+            MockStrategy::Random { seed }
+            | MockStrategy::CaptureFirst { seed }
+            | MockStrategy::Greedy { seed } => seed,
+        }
+    }
+}
+
+impl Default for MockStrategy {
+    fn default() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        MockStrategy::Random { seed }
+    }
+}
 
 /// Mock engine that simulates USI protocol responses
 pub struct MockEngine {
     initialized: bool,
+    strategy: MockStrategy,
+    rng_state: Cell<u64>,
+    position: Position,
+    info_callback: Option<InfoCallback>,
 }
 
 impl MockEngine {
     pub fn new() -> Self {
+        Self::with_strategy(MockStrategy::default())
+    }
+
+    /// Create a mock engine using the given move selection strategy
+    pub fn with_strategy(strategy: MockStrategy) -> Self {
         MockEngine {
             initialized: false,
+            strategy,
+            rng_state: Cell::new(Self::initial_rng_state(strategy.seed())),
+            position: Position::startpos(),
+            info_callback: None,
         }
     }
 
+    /// Change the strategy and reseed the random generator
+    pub fn set_strategy(&mut self, strategy: MockStrategy) {
+        self.strategy = strategy;
+        self.rng_state.set(Self::initial_rng_state(strategy.seed()));
+    }
+
+    pub fn strategy(&self) -> MockStrategy {
+        self.strategy
+    }
+
+    /// Get a legal move for the position, or resign if there is none
+    pub fn get_best_move(&self, sfen: &str, _time_ms: u32) -> Result<BestMove, UsiError> {
+        if !self.initialized {
+            return Err(UsiError::NotStarted);
+        }
+
+        let pos = Position::from_sfen(sfen).map_err(|e| e.to_string())?;
+        Ok(self
+            .choose_move(&pos)
+            .map_or(BestMove::Resign, |mv| BestMove::Move(mv.to_usi())))
+    }
+
+    /// Pick a legal move according to the strategy
+    fn choose_move(&self, pos: &Position) -> Option<Move> {
+        let moves = pos.legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+
+        let candidates: Vec<Move> = match self.strategy {
+            MockStrategy::Random { .. } => moves,
+            MockStrategy::CaptureFirst { .. } => {
+                let captures: Vec<Move> = moves
+                    .iter()
+                    .copied()
+                    .filter(|mv| !mv.is_drop() && pos.piece_at(mv.to()).is_some())
+                    .collect();
+                if captures.is_empty() {
+                    moves
+                } else {
+                    captures
+                }
+            }
+            MockStrategy::Greedy { .. } => {
+                let side = pos.side_to_move();
+                let scored: Vec<(Move, i32)> = moves
+                    .iter()
+                    .map(|&mv| (mv, material_balance(&pos.after_move(mv), side)))
+                    .collect();
+                let best = scored.iter().map(|&(_, score)| score).max().unwrap();
+                scored
+                    .into_iter()
+                    .filter(|&(_, score)| score == best)
+                    .map(|(mv, _)| mv)
+                    .collect()
+            }
+        };
+
+        let index = (self.next_random() % candidates.len() as u64) as usize;
+        Some(candidates[index])
+    }
+
+    fn initial_rng_state(seed: u64) -> u64 {
+        // xorshift must not start from zero, or it stays there forever
+        match seed ^ RNG_SEED_MIX {
+            0 => RNG_SEED_MIX,
+            state => state,
+        }
+    }
+
+    /// xorshift64* pseudo-random generator
+    fn next_random(&self) -> u64 {
+        let mut x = self.rng_state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng_state.set(x);
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+impl ShogiEngine for MockEngine {
     /// Initialize the mock engine
-    pub fn init(&mut self) -> Result<(), String> {
+    fn init(&mut self) -> Result<EngineInfo, UsiError> {
         self.initialized = true;
+        Ok(EngineInfo {
+            name: Some("Mock Engine".to_string()),
+            author: None,
+            options: Vec::new(),
+        })
+    }
+
+    fn wait_ready(&mut self) -> Result<(), UsiError> {
+        Ok(())
+    }
+
+    fn new_game(&mut self) -> Result<(), UsiError> {
+        self.position = Position::startpos();
+        Ok(())
+    }
+
+    /// Options are accepted and ignored
+    fn set_option(&mut self, _name: &str, _value: Option<&str>) -> Result<(), UsiError> {
+        Ok(())
+    }
+
+    fn set_position(&mut self, sfen: &str, moves: &[String]) -> Result<(), UsiError> {
+        self.position = position_after_moves(sfen, moves)?;
         Ok(())
     }
 
-    /// Get a mock best move based on the position
-    /// For now, returns predefined moves or simple heuristics
-    pub fn get_best_move(&self, sfen: &str, _time_ms: u32) -> Result<String, String> {
+    /// The mock engine never declares
+    fn set_declaration_rule(&mut self, _rule: DeclarationRule) {}
+
+    /// Pick a move for the current position; limits are ignored
+    fn go(&mut self, _limits: &GoLimits) -> Result<GoResult, UsiError> {
         if !self.initialized {
-            return Err("Engine not initialized".to_string());
+            return Err(UsiError::NotStarted);
         }
 
-        // Parse SFEN to determine position (simplified)
-        // For a proper implementation, we would analyze the position
-        // For now, return common opening moves or a simple move
-
-        let move_str = self.generate_mock_move(sfen)?;
-        Ok(move_str)
-    }
-
-    /// Generate a mock move based on the SFEN position
-    fn generate_mock_move(&self, sfen: &str) -> Result<String, String> {
-        // Common opening moves based on position
-        let opening_moves = HashMap::from([
-            // Initial position - common first moves
-            ("lnsgkgsnl/1b5r1/ppppppppp/9/9/9/PPPPPPPPP/1R5B1/LNSGKGSNL b - 1", vec![
-                "7g7f", // 7七歩
-                "2g2f", // 2七歩
-                "5g5f", // 5七歩
-                "6g6f", // 6七歩
-            ]),
-        ]);
-
-        // Extract just the board part of SFEN (before the turn indicator)
-        let sfen_parts: Vec<&str> = sfen.split_whitespace().collect();
-        if sfen_parts.len() < 4 {
-            return Err("Invalid SFEN format".to_string());
+        let chosen = self.choose_move(&self.position);
+
+        // Report a single one-ply "search" so the UI has something to show
+        if let (Some(cb), Some(mv)) = (self.info_callback.as_mut(), chosen) {
+            cb(&ThinkingInfo {
+                depth: Some(1),
+                pv: vec![mv.to_usi()],
+                ..ThinkingInfo::new()
+            });
         }
 
-        // For the initial position or common positions, return a known good move
-        let position_key = format!("{} {} {} {}", sfen_parts[0], sfen_parts[1], sfen_parts[2], sfen_parts[3]);
+        let best_move = chosen.map_or(BestMove::Resign, |mv| BestMove::Move(mv.to_usi()));
+        Ok(GoResult {
+            best_move,
+            ponder: None,
+        })
+    }
 
-        if let Some(moves) = opening_moves.get(position_key.as_str()) {
-            if !moves.is_empty() {
-                // Return first move (could be randomized)
-                return Ok(moves[0].to_string());
-            }
+    /// Solve the current position with the df-pn tsume solver
+    fn go_mate(&mut self, limit: MateLimit) -> Result<TsumeResult, UsiError> {
+        if !self.initialized {
+            return Err(UsiError::NotStarted);
         }
 
-        // For other positions, return a simple default move
-        // In a real implementation, we would analyze the board and generate legal moves
-        // For now, return a common pawn move as fallback
-        Ok("7g7f".to_string())
+        let limits = TsumeLimits {
+            time_ms: match limit {
+                MateLimit::Time(time_ms) => Some(time_ms as u64),
+                MateLimit::Infinite => None,
+            },
+            ..TsumeLimits::default()
+        };
+        let solution = TsumeSolver::new().solve(&self.position, limits);
+        Ok(match solution.outcome {
+            TsumeOutcome::Mate(line) => {
+                TsumeResult::Mate(line.iter().map(|mv| mv.to_usi()).collect())
+            }
+            TsumeOutcome::NoMate => TsumeResult::NoMate,
+            TsumeOutcome::NoMateWithin(_) | TsumeOutcome::Unknown => TsumeResult::Timeout,
+        })
+    }
+
+    fn set_info_callback(&mut self, callback: Option<InfoCallback>) {
+        self.info_callback = callback;
     }
 
     /// Stop thinking (no-op for mock engine)
-    pub fn stop(&mut self) -> Result<(), String> {
+    fn stop(&mut self) -> Result<(), UsiError> {
         Ok(())
     }
 
-    /// Quit the engine
-    pub fn quit(&mut self) -> Result<(), String> {
-        self.initialized = false;
+    /// Mock searches return immediately, so there is nothing to stop
+    fn stop_handle(&self) -> StopHandle {
+        StopHandle::noop()
+    }
+
+    /// Pondering is not supported
+    fn can_ponder(&self) -> bool {
+        false
+    }
+
+    /// Ponder hit (no-op for mock engine)
+    fn ponderhit(&mut self) -> Result<(), UsiError> {
         Ok(())
     }
 
+    /// Quit the engine; there is no process to wait for
+    fn quit(&mut self, _timeout: Duration) -> Result<ShutdownOutcome, UsiError> {
+        self.initialized = false;
+        Ok(ShutdownOutcome::Exited { code: None })
+    }
+
     /// Check if engine is initialized
-    pub fn is_ready(&self) -> bool {
+    fn is_ready(&self) -> bool {
         self.initialized
     }
 }
@@ -94,9 +281,143 @@ impl Default for MockEngine {
     }
 }
 
+/// Test double with scripted behaviour: answers every search with the same
+/// move, records the options it accepts and refuses the ones it is told to
+/// When pondering, a ponder search thinks until "ponderhit" or "stop"
+#[cfg(test)]
+#[derive(Default)]
+pub struct ScriptedEngine {
+    answer: Option<String>,
+    rejected_options: Vec<String>,
+    options: Vec<String>,
+    pondering: bool,
+    /// Last of "stop" or "ponderhit" received through the stop handle
+    signal: Arc<(Mutex<Option<&'static str>>, Condvar)>,
+}
+
+#[cfg(test)]
+impl ScriptedEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer every search with `usi` (a move, "resign" or "win")
+    pub fn answering(mut self, usi: &str) -> Self {
+        self.answer = Some(usi.to_string());
+        self
+    }
+
+    /// Refuse to set option `name`, as for an option the engine does not declare
+    pub fn rejecting_option(mut self, name: &str) -> Self {
+        self.rejected_options.push(name.to_string());
+        self
+    }
+
+    /// Accept ponder searches, which wait for "ponderhit" or "stop"
+    pub fn pondering(mut self) -> Self {
+        self.pondering = true;
+        self
+    }
+
+    /// Options accepted so far, as "name=value"
+    pub fn options(&self) -> &[String] {
+        &self.options
+    }
+
+    fn signal_handle(&self, name: &'static str) -> impl Fn() -> Result<(), UsiError> {
+        let signal = Arc::clone(&self.signal);
+        move || {
+            *signal.0.lock().unwrap() = Some(name);
+            signal.1.notify_all();
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+impl ShogiEngine for ScriptedEngine {
+    fn init(&mut self) -> Result<EngineInfo, UsiError> {
+        Ok(EngineInfo::default())
+    }
+
+    fn wait_ready(&mut self) -> Result<(), UsiError> {
+        Ok(())
+    }
+
+    fn new_game(&mut self) -> Result<(), UsiError> {
+        Ok(())
+    }
+
+    fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), UsiError> {
+        if self.rejected_options.iter().any(|n| n == name) {
+            return Err(format!("Unknown engine option: {}", name).into());
+        }
+        self.options
+            .push(format!("{}={}", name, value.unwrap_or("")));
+        Ok(())
+    }
+
+    fn set_position(&mut self, _sfen: &str, _moves: &[String]) -> Result<(), UsiError> {
+        Ok(())
+    }
+
+    fn set_declaration_rule(&mut self, _rule: DeclarationRule) {}
+
+    /// Answer with the scripted move, resigning when there is none
+    /// A ponder search first waits for a signal, and keeps thinking briefly
+    /// after a ponderhit
+    fn go(&mut self, limits: &GoLimits) -> Result<GoResult, UsiError> {
+        if self.pondering && limits.ponder {
+            let (signal, ready) = &*self.signal;
+            let signal = ready
+                .wait_while(signal.lock().unwrap(), |signal| signal.is_none())
+                .unwrap();
+            if *signal == Some("ponderhit") {
+                drop(signal);
+                std::thread::sleep(Duration::from_millis(20));
+            }
+        }
+        Ok(GoResult {
+            best_move: BestMove::from_usi(self.answer.as_deref().unwrap_or("resign")),
+            ponder: None,
+        })
+    }
+
+    fn go_mate(&mut self, _limit: MateLimit) -> Result<TsumeResult, UsiError> {
+        Ok(TsumeResult::NotImplemented)
+    }
+
+    fn set_info_callback(&mut self, _callback: Option<InfoCallback>) {}
+
+    fn stop(&mut self) -> Result<(), UsiError> {
+        Ok(())
+    }
+
+    fn stop_handle(&self) -> StopHandle {
+        StopHandle::new(self.signal_handle("stop")).with_ponderhit(self.signal_handle("ponderhit"))
+    }
+
+    fn can_ponder(&self) -> bool {
+        self.pondering
+    }
+
+    fn ponderhit(&mut self) -> Result<(), UsiError> {
+        Ok(())
+    }
+
+    fn quit(&mut self, _timeout: Duration) -> Result<ShutdownOutcome, UsiError> {
+        Ok(ShutdownOutcome::NotRunning)
+    }
+
+    fn is_ready(&self) -> bool {
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shogi::STARTPOS_SFEN;
 
     #[test]
     fn test_mock_engine_init() {
@@ -116,8 +437,7 @@ mod tests {
         let result = engine.get_best_move(sfen, 1000);
 
         assert!(result.is_ok());
-        let move_str = result.unwrap();
-        assert!(!move_str.is_empty());
+        assert!(result.unwrap().as_move().is_some());
     }
 
     #[test]
@@ -128,4 +448,158 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_mock_engine_returns_legal_moves() {
+        let mut engine = MockEngine::with_strategy(MockStrategy::Random { seed: 7 });
+        engine.init().unwrap();
+
+        // Play a short game against itself; every move must be legal
+        let mut pos = Position::startpos();
+        for _ in 0..40 {
+            let best = engine.get_best_move(&pos.to_sfen(), 1000).unwrap();
+            let Some(best) = best.as_move() else {
+                assert!(pos.legal_moves().is_empty());
+                break;
+            };
+            let mv = Move::from_usi(best).unwrap();
+            assert!(
+                pos.is_legal(mv),
+                "illegal move {} in {}",
+                best,
+                pos.to_sfen()
+            );
+            pos.do_move(mv);
+        }
+    }
+
+    #[test]
+    fn test_mock_engine_seed_is_deterministic() {
+        let mut a = MockEngine::with_strategy(MockStrategy::Random { seed: 42 });
+        let mut b = MockEngine::with_strategy(MockStrategy::Random { seed: 42 });
+        a.init().unwrap();
+        b.init().unwrap();
+
+        let sfen = STARTPOS_SFEN;
+        for _ in 0..5 {
+            assert_eq!(
+                a.get_best_move(sfen, 0).unwrap(),
+                b.get_best_move(sfen, 0).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_mock_engine_seed_never_stalls_the_generator() {
+        // This seed would cancel the mixing constant and leave the state at zero
+        let engine = MockEngine::with_strategy(MockStrategy::Random { seed: RNG_SEED_MIX });
+        let draws: Vec<u64> = (0..3).map(|_| engine.next_random()).collect();
+        assert!(draws.iter().all(|&x| x != 0));
+        assert_ne!(draws[0], draws[1]);
+    }
+
+    #[test]
+    fn test_mock_engine_capture_first() {
+        let mut engine = MockEngine::with_strategy(MockStrategy::CaptureFirst { seed: 1 });
+        engine.init().unwrap();
+
+        // Only one capture is available: the rook takes the pawn on 5c
+        let sfen = "4k4/9/4p4/9/9/9/9/4R4/K8 b - 1";
+        let best = engine.get_best_move(sfen, 0).unwrap();
+        assert!(best.to_usi().starts_with("5h5c"), "unexpected move {:?}", best);
+    }
+
+    #[test]
+    fn test_mock_engine_greedy_takes_most_material() {
+        let mut engine = MockEngine::with_strategy(MockStrategy::Greedy { seed: 3 });
+        engine.init().unwrap();
+
+        // The bishop can take a pawn on 7c or a rook on 3c (promoting either way)
+        let sfen = "4k4/9/2p3r2/9/4B4/9/9/9/K8 b - 1";
+        assert_eq!(engine.get_best_move(sfen, 0).unwrap().to_usi(), "5e3c+");
+    }
+
+    #[test]
+    fn test_mock_engine_resigns_when_mated() {
+        let mut engine = MockEngine::with_strategy(MockStrategy::Random { seed: 0 });
+        engine.init().unwrap();
+
+        let sfen = "4k4/4G4/4S4/9/9/9/9/9/K8 w - 1";
+        assert_eq!(engine.get_best_move(sfen, 0).unwrap(), BestMove::Resign);
+    }
+
+    #[test]
+    fn test_mock_engine_solves_tsume() {
+        let mut engine = MockEngine::new();
+        engine.init().unwrap();
+
+        // Gold drop on 5b is protected by the pawn on 5c
+        engine
+            .set_position("4k4/9/4P4/9/9/9/9/9/4K4 b G 1", &[])
+            .unwrap();
+        assert_eq!(
+            engine.go_mate(MateLimit::Infinite).unwrap(),
+            TsumeResult::Mate(vec!["G*5b".to_string()])
+        );
+
+        // Mate in 3 from the solver's bundled problems
+        engine
+            .set_position("8k/9/7L1/8S/9/9/9/9/9 b SN 1", &[])
+            .unwrap();
+        match engine.go_mate(MateLimit::Time(10_000)).unwrap() {
+            TsumeResult::Mate(line) => {
+                assert_eq!(line.len(), 3);
+                assert_eq!(line[0], "S*2b");
+            }
+            other => panic!("Expected a mate, got {:?}", other),
+        }
+
+        engine.set_position(STARTPOS_SFEN, &[]).unwrap();
+        assert_eq!(
+            engine.go_mate(MateLimit::Time(1000)).unwrap(),
+            TsumeResult::NoMate
+        );
+    }
+
+    #[test]
+    fn test_mock_engine_go_after_moves() {
+        let mut engine = MockEngine::with_strategy(MockStrategy::Random { seed: 9 });
+        engine.init().unwrap();
+
+        let moves = vec!["7g7f".to_string(), "3c3d".to_string()];
+        engine.set_position(STARTPOS_SFEN, &moves).unwrap();
+        let result = engine.go(&GoLimits::byoyomi(100)).unwrap();
+
+        let pos = position_after_moves(STARTPOS_SFEN, &moves).unwrap();
+        assert!(pos.is_legal(Move::from_usi(result.best_move.to_usi()).unwrap()));
+        assert!(engine
+            .set_position(STARTPOS_SFEN, &["7g7f".to_string(), "7g7f".to_string()])
+            .is_err());
+    }
+
+    #[test]
+    fn test_mock_engine_reports_info() {
+        use std::sync::{Arc, Mutex};
+
+        let infos = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&infos);
+
+        let mut engine = MockEngine::with_strategy(MockStrategy::Random { seed: 5 });
+        engine.init().unwrap();
+        engine.set_info_callback(Some(Box::new(move |info| {
+            sink.lock().unwrap().push(info.clone());
+        })));
+
+        let result = engine.go(&GoLimits::byoyomi(0)).unwrap();
+        let infos = infos.lock().unwrap();
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].pv, vec![result.best_move.to_usi()]);
+    }
+
+    #[test]
+    fn test_mock_engine_rejects_invalid_sfen() {
+        let mut engine = MockEngine::new();
+        engine.init().unwrap();
+        assert!(engine.get_best_move("not a position", 0).is_err());
+    }
 }
@@ -1,11 +1,25 @@
 // USI (Universal Shogi Interface) protocol implementation
 
+pub mod best_move;
 pub mod commands;
 pub mod engine;
+pub mod error;
 pub mod mock_engine;
+pub mod native_engine;
+pub mod options;
 pub mod parser;
+pub mod response_stream;
+pub mod search_task;
+pub mod shogi_engine;
 
+pub use best_move::*;
 pub use commands::*;
 pub use engine::*;
+pub use error::*;
 pub use mock_engine::*;
+pub use native_engine::*;
+pub use options::*;
 pub use parser::*;
+pub use response_stream::*;
+pub use search_task::*;
+pub use shogi_engine::*;
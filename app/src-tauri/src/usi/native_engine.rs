@@ -0,0 +1,358 @@
+// Native engine backed by the built-in alpha-beta searcher and df-pn tsume solver
+// Offers the same interface as UsiEngine/MockEngine without an external binary
+
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use super::commands::{GoLimits, MateLimit};
+use super::error::UsiError;
+use super::options::EngineInfo;
+use super::parser::{BestMove, TsumeResult};
+use super::shogi_engine::{
+    position_after_moves, GoResult, InfoCallback, ShogiEngine, ShutdownOutcome, StopHandle,
+};
+use crate::search::{SearchLimits, Searcher, TsumeLimits, TsumeOutcome, TsumeSolver, MAX_PLY};
+use crate::shogi::{can_declare_win, DeclarationRule, Player, Position};
+
+/// Default transposition table size in megabytes
+const DEFAULT_HASH_MB: usize = 64;
+
+/// Engine that searches positions in-process
+pub struct NativeEngine {
+    initialized: bool,
+    searcher: Searcher,
+    /// Shares the searcher's stop flag
+    tsume: TsumeSolver,
+    max_depth: u32,
+    info_callback: Option<InfoCallback>,
+    position: Position,
+    /// Rule the engine declares a win under
+    declaration_rule: DeclarationRule,
+}
+
+impl NativeEngine {
+    pub fn new() -> Self {
+        let searcher = Searcher::new(DEFAULT_HASH_MB);
+        NativeEngine {
+            initialized: false,
+            tsume: TsumeSolver::with_stop_flag(searcher.stop_flag()),
+            searcher,
+            max_depth: MAX_PLY as u32 - 1,
+            info_callback: None,
+            position: Position::startpos(),
+            declaration_rule: DeclarationRule::default(),
+        }
+    }
+
+    /// Limit the search depth (e.g. for weaker play)
+    pub fn set_max_depth(&mut self, depth: u32) {
+        self.max_depth = depth.max(1);
+    }
+
+    /// Search the position within `limits` and return the best move, or
+    /// resign or declare a win
+    pub fn get_best_move(&mut self, sfen: &str, limits: &GoLimits) -> Result<BestMove, UsiError> {
+        self.set_position(sfen, &[])?;
+        Ok(self.go(limits)?.best_move)
+    }
+}
+
+impl ShogiEngine for NativeEngine {
+    /// Initialize the engine
+    fn init(&mut self) -> Result<EngineInfo, UsiError> {
+        self.initialized = true;
+        Ok(EngineInfo {
+            name: Some("Native Engine".to_string()),
+            author: None,
+            options: Vec::new(),
+        })
+    }
+
+    fn wait_ready(&mut self) -> Result<(), UsiError> {
+        Ok(())
+    }
+
+    /// Start a new game (clears the transposition table)
+    fn new_game(&mut self) -> Result<(), UsiError> {
+        self.searcher.clear();
+        self.position = Position::startpos();
+        Ok(())
+    }
+
+    /// Options are not supported yet and are ignored
+    fn set_option(&mut self, _name: &str, _value: Option<&str>) -> Result<(), UsiError> {
+        Ok(())
+    }
+
+    fn set_position(&mut self, sfen: &str, moves: &[String]) -> Result<(), UsiError> {
+        self.position = position_after_moves(sfen, moves)?;
+        Ok(())
+    }
+
+    fn set_declaration_rule(&mut self, rule: DeclarationRule) {
+        self.declaration_rule = rule;
+    }
+
+    /// Search the current position within the given limits, declaring a win
+    /// instead whenever the game's declaration rule allows it
+    /// "searchmoves" is not supported and is ignored
+    fn go(&mut self, limits: &GoLimits) -> Result<GoResult, UsiError> {
+        if !self.initialized {
+            return Err(UsiError::NotStarted);
+        }
+        limits.validate()?;
+        if limits.mate.is_some() {
+            return Err("Mate searches are started with go_mate".into());
+        }
+        if can_declare_win(&self.position, self.declaration_rule) {
+            return Ok(GoResult {
+                best_move: BestMove::Win,
+                ponder: None,
+            });
+        }
+
+        let sente_to_move = self.position.side_to_move() == Player::Sente;
+        let limits = SearchLimits {
+            time_ms: limits.move_time_ms(sente_to_move),
+            max_depth: limits
+                .depth
+                .map_or(self.max_depth, |d| d.min(self.max_depth)),
+            nodes: limits.nodes,
+        };
+
+        let callback = &mut self.info_callback;
+        let result = self.searcher.search(&self.position, limits, |info| {
+            if let Some(cb) = callback.as_mut() {
+                cb(info);
+            }
+        });
+
+        Ok(GoResult {
+            best_move: result
+                .best_move
+                .map_or(BestMove::Resign, |mv| BestMove::Move(mv.to_usi())),
+            ponder: result.pv.get(1).map(|mv| mv.to_usi()),
+        })
+    }
+
+    /// Solve the current position with the df-pn tsume solver
+    /// Giving up within the time or node budget is answered as a timeout
+    fn go_mate(&mut self, limit: MateLimit) -> Result<TsumeResult, UsiError> {
+        if !self.initialized {
+            return Err(UsiError::NotStarted);
+        }
+
+        let limits = TsumeLimits {
+            time_ms: match limit {
+                MateLimit::Time(time_ms) => Some(time_ms as u64),
+                MateLimit::Infinite => None,
+            },
+            ..TsumeLimits::default()
+        };
+        Ok(match self.tsume.solve(&self.position, limits).outcome {
+            TsumeOutcome::Mate(line) => {
+                TsumeResult::Mate(line.iter().map(|mv| mv.to_usi()).collect())
+            }
+            TsumeOutcome::NoMate => TsumeResult::NoMate,
+            TsumeOutcome::NoMateWithin(_) | TsumeOutcome::Unknown => TsumeResult::Timeout,
+        })
+    }
+
+    /// Info is reported after each completed iteration
+    fn set_info_callback(&mut self, callback: Option<InfoCallback>) {
+        self.info_callback = callback;
+    }
+
+    /// Stop thinking
+    fn stop(&mut self) -> Result<(), UsiError> {
+        self.searcher.stop_flag().store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn stop_handle(&self) -> StopHandle {
+        let flag = self.searcher.stop_flag();
+        StopHandle::new(move || {
+            flag.store(true, Ordering::SeqCst);
+            Ok(())
+        })
+    }
+
+    /// Pondering is not supported
+    fn can_ponder(&self) -> bool {
+        false
+    }
+
+    /// Pondering is not supported; nothing to do
+    fn ponderhit(&mut self) -> Result<(), UsiError> {
+        Ok(())
+    }
+
+    /// Quit the engine; there is no process to wait for
+    fn quit(&mut self, _timeout: Duration) -> Result<ShutdownOutcome, UsiError> {
+        self.initialized = false;
+        Ok(ShutdownOutcome::Exited { code: None })
+    }
+
+    /// Check if engine is initialized
+    fn is_ready(&self) -> bool {
+        self.initialized
+    }
+}
+
+impl Default for NativeEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shogi::{Move, STARTPOS_SFEN};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_native_engine_not_initialized() {
+        let mut engine = NativeEngine::new();
+        assert!(!engine.is_ready());
+        assert!(engine
+            .get_best_move(STARTPOS_SFEN, &GoLimits::byoyomi(100))
+            .is_err());
+    }
+
+    #[test]
+    fn test_native_engine_plays_legal_move() {
+        let mut engine = NativeEngine::new();
+        engine.init().unwrap();
+        engine.set_max_depth(2);
+
+        let best = engine
+            .get_best_move(STARTPOS_SFEN, &GoLimits::byoyomi(1000))
+            .unwrap();
+        let pos = Position::startpos();
+        assert!(pos.is_legal(Move::from_usi(best.to_usi()).unwrap()));
+    }
+
+    #[test]
+    fn test_native_engine_reports_info() {
+        let infos = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&infos);
+
+        let mut engine = NativeEngine::new();
+        engine.init().unwrap();
+        engine.set_max_depth(3);
+        engine.set_info_callback(Some(Box::new(move |info| {
+            sink.lock().unwrap().push(info.clone());
+        })));
+
+        engine
+            .get_best_move(STARTPOS_SFEN, &GoLimits::byoyomi(2000))
+            .unwrap();
+        let infos = infos.lock().unwrap();
+        assert_eq!(infos.len(), 3);
+        assert_eq!(infos[2].depth, Some(3));
+        assert!(infos[2].nodes.unwrap() > 0);
+    }
+
+    #[test]
+    fn test_native_engine_go_with_depth_limit() {
+        let mut engine = NativeEngine::new();
+        engine.init().unwrap();
+
+        let moves = vec!["7g7f".to_string(), "3c3d".to_string()];
+        engine.set_position(STARTPOS_SFEN, &moves).unwrap();
+        let result = engine
+            .go(&GoLimits {
+                depth: Some(2),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let pos = position_after_moves(STARTPOS_SFEN, &moves).unwrap();
+        let best = Move::from_usi(result.best_move.to_usi()).unwrap();
+        assert!(pos.is_legal(best));
+        let ponder = Move::from_usi(&result.ponder.unwrap()).unwrap();
+        assert!(pos.after_move(best).is_legal(ponder));
+    }
+
+    #[test]
+    fn test_native_engine_go_with_node_limit() {
+        let mut engine = NativeEngine::new();
+        engine.init().unwrap();
+
+        let result = engine
+            .go(&GoLimits {
+                nodes: Some(5000),
+                ..Default::default()
+            })
+            .unwrap();
+        let best = Move::from_usi(result.best_move.to_usi()).unwrap();
+        assert!(Position::startpos().is_legal(best));
+
+        assert!(engine.go(&GoLimits::mate(MateLimit::Infinite)).is_err());
+    }
+
+    #[test]
+    fn test_native_engine_solves_tsume() {
+        let mut engine = NativeEngine::new();
+        engine.init().unwrap();
+
+        engine
+            .set_position("6k2/5P3/5s3/6N2/9/9/9/9/9 b B 1", &[])
+            .unwrap();
+        match engine.go_mate(MateLimit::Time(10_000)).unwrap() {
+            TsumeResult::Mate(line) => {
+                assert_eq!(line.len(), 3);
+                assert_eq!(line[0], "B*1c");
+            }
+            other => panic!("Expected a mate, got {:?}", other),
+        }
+
+        engine.set_position(STARTPOS_SFEN, &[]).unwrap();
+        assert_eq!(
+            engine.go_mate(MateLimit::Infinite).unwrap(),
+            TsumeResult::NoMate
+        );
+    }
+
+    #[test]
+    fn test_native_engine_resigns_when_mated() {
+        let mut engine = NativeEngine::new();
+        engine.init().unwrap();
+        let sfen = "4k4/4G4/4S4/9/9/9/9/9/K8 w - 1";
+        assert_eq!(
+            engine.get_best_move(sfen, &GoLimits::byoyomi(100)).unwrap(),
+            BestMove::Resign
+        );
+    }
+
+    #[test]
+    fn test_native_engine_declares_win() {
+        let mut engine = NativeEngine::new();
+        engine.init().unwrap();
+        let sfen = "+R+B+B+RK4/PPPPPPPP1/9/9/9/9/9/9/4k4 b - 1";
+        assert_eq!(
+            engine.get_best_move(sfen, &GoLimits::byoyomi(100)).unwrap(),
+            BestMove::Win
+        );
+    }
+
+    #[test]
+    fn test_native_engine_declares_under_the_24_point_rule() {
+        let mut engine = NativeEngine::new();
+        engine.init().unwrap();
+        engine.set_declaration_rule(DeclarationRule::Points24);
+
+        // 28 points only draw under the 24-point rule, so the engine plays on
+        let sfen = "+R+B+B+RK4/PPPPPPPP1/9/9/9/9/9/9/4k4 b - 1";
+        let best = engine.get_best_move(sfen, &GoLimits::byoyomi(100)).unwrap();
+        assert!(best.as_move().is_some(), "unexpected answer {:?}", best);
+
+        // 31 points win
+        let sfen = "+R+B+B+RK4/PPPPPPPP1/9/9/9/9/9/9/4k4 b 3P 1";
+        assert_eq!(
+            engine.get_best_move(sfen, &GoLimits::byoyomi(100)).unwrap(),
+            BestMove::Win
+        );
+    }
+}
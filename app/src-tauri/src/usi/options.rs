@@ -0,0 +1,391 @@
+// Engine identity and option declarations announced during the "usi" handshake
+
+use serde::{Deserialize, Serialize};
+
+/// Identity and options reported by an engine before "usiok"
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EngineInfo {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub options: Vec<UsiOptionDecl>,
+}
+
+impl EngineInfo {
+    /// Look up a declared option by name
+    pub fn option(&self, name: &str) -> Option<&UsiOptionDecl> {
+        self.options.iter().find(|opt| opt.name == name)
+    }
+
+    /// Check a value against the option's declaration and return it in the
+    /// form to send; the GUI-managed USI_Hash and USI_Ponder are accepted
+    /// even when the engine does not declare them
+    pub fn validate_option(
+        &self,
+        name: &str,
+        value: Option<&str>,
+    ) -> Result<Option<String>, String> {
+        match self.option(name) {
+            Some(decl) => decl.validate(value),
+            None => match builtin_option(name) {
+                Some(decl) => decl.validate(value),
+                None => Err(format!("Unknown engine option: {}", name)),
+            },
+        }
+    }
+}
+
+/// Options every USI GUI may send whether or not the engine declares them
+fn builtin_option(name: &str) -> Option<UsiOptionDecl> {
+    let kind = match name {
+        "USI_Hash" => UsiOptionKind::Spin {
+            default: None,
+            min: Some(1),
+            max: None,
+        },
+        "USI_Ponder" => UsiOptionKind::Check { default: None },
+        _ => return None,
+    };
+    Some(UsiOptionDecl {
+        name: name.to_string(),
+        kind,
+    })
+}
+
+/// One "option name <id> type <t> ..." declaration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsiOptionDecl {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: UsiOptionKind,
+}
+
+impl UsiOptionDecl {
+    /// Check a value against the declared type and range
+    /// Buttons take no value; every other type requires one
+    pub fn validate(&self, value: Option<&str>) -> Result<Option<String>, String> {
+        let name = &self.name;
+        let value = match (&self.kind, value) {
+            (UsiOptionKind::Button, None) => return Ok(None),
+            (UsiOptionKind::Button, Some(_)) => {
+                return Err(format!("Option {} is a button and takes no value", name))
+            }
+            (_, None) => return Err(format!("Option {} requires a value", name)),
+            (_, Some(value)) => value.trim(),
+        };
+
+        match &self.kind {
+            UsiOptionKind::Check { .. } => match value {
+                "true" | "false" => {}
+                _ => {
+                    return Err(format!(
+                        "Option {} must be true or false, got {}",
+                        name, value
+                    ))
+                }
+            },
+            UsiOptionKind::Spin { min, max, .. } => {
+                let number: i64 = value
+                    .parse()
+                    .map_err(|_| format!("Option {} must be an integer, got {}", name, value))?;
+                if min.is_some_and(|min| number < min) || max.is_some_and(|max| number > max) {
+                    return Err(format!(
+                        "Option {} must be between {} and {}, got {}",
+                        name,
+                        min.map_or("-inf".to_string(), |m| m.to_string()),
+                        max.map_or("inf".to_string(), |m| m.to_string()),
+                        number
+                    ));
+                }
+            }
+            UsiOptionKind::Combo { vars, .. } => {
+                if !vars.iter().any(|var| var == value) {
+                    return Err(format!(
+                        "Option {} must be one of {}, got {}",
+                        name,
+                        vars.join(", "),
+                        value
+                    ));
+                }
+            }
+            UsiOptionKind::String { .. } | UsiOptionKind::Filename { .. } => {
+                // Values are sent on one line; newlines would break the protocol
+                if value.contains(['\n', '\r']) {
+                    return Err(format!("Option {} must not contain line breaks", name));
+                }
+            }
+            UsiOptionKind::Button => unreachable!(),
+        }
+
+        Ok(Some(value.to_string()))
+    }
+}
+
+/// Option type with its default value and constraints
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum UsiOptionKind {
+    Check {
+        default: Option<bool>,
+    },
+    Spin {
+        default: Option<i64>,
+        min: Option<i64>,
+        max: Option<i64>,
+    },
+    Combo {
+        default: Option<String>,
+        vars: Vec<String>,
+    },
+    String {
+        default: Option<String>,
+    },
+    Button,
+    Filename {
+        default: Option<String>,
+    },
+}
+
+/// Keywords that start a new field in an option declaration
+const OPTION_KEYWORDS: [&str; 6] = ["name", "type", "default", "min", "max", "var"];
+
+/// Parse an "option" line
+/// Format: "option name <id> type <check|spin|combo|string|button|filename>
+///          [default <x>] [min <x>] [max <x>] [var <x>]*"
+pub fn parse_option_line(line: &str) -> Option<UsiOptionDecl> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.first() != Some(&"option") {
+        return None;
+    }
+
+    let mut name = None;
+    let mut option_type = None;
+    let mut default = None;
+    let mut min = None;
+    let mut max = None;
+    let mut vars = Vec::new();
+
+    let mut i = 1; // Skip "option"
+    while i < parts.len() {
+        let keyword = parts[i];
+        // Values run until the next keyword, so names and strings may contain spaces
+        let end = parts[i + 1..]
+            .iter()
+            .position(|p| OPTION_KEYWORDS.contains(p))
+            .map_or(parts.len(), |offset| i + 1 + offset);
+        let value = parts[i + 1..end].join(" ");
+
+        match keyword {
+            "name" => name = Some(value),
+            "type" => option_type = Some(value),
+            "default" => default = Some(value),
+            "min" => min = value.parse().ok(),
+            "max" => max = value.parse().ok(),
+            "var" => vars.push(value),
+            _ => {}
+        }
+        i = end;
+    }
+
+    // USI writes an empty string value as "<empty>"
+    let default_string = default.map(|d| if d == "<empty>" { String::new() } else { d });
+
+    let kind = match option_type?.as_str() {
+        "check" => UsiOptionKind::Check {
+            default: default_string.and_then(|d| d.parse().ok()),
+        },
+        "spin" => UsiOptionKind::Spin {
+            default: default_string.and_then(|d| d.parse().ok()),
+            min,
+            max,
+        },
+        "combo" => UsiOptionKind::Combo {
+            default: default_string,
+            vars,
+        },
+        "string" => UsiOptionKind::String {
+            default: default_string,
+        },
+        "button" => UsiOptionKind::Button,
+        "filename" => UsiOptionKind::Filename {
+            default: default_string,
+        },
+        _ => return None,
+    };
+
+    Some(UsiOptionDecl {
+        name: name.filter(|n| !n.is_empty())?,
+        kind,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spin_option() {
+        let decl =
+            parse_option_line("option name USI_Hash type spin default 256 min 1 max 33554432")
+                .unwrap();
+        assert_eq!(decl.name, "USI_Hash");
+        assert_eq!(
+            decl.kind,
+            UsiOptionKind::Spin {
+                default: Some(256),
+                min: Some(1),
+                max: Some(33554432),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_check_and_button_options() {
+        let decl = parse_option_line("option name USI_Ponder type check default false").unwrap();
+        assert_eq!(
+            decl.kind,
+            UsiOptionKind::Check {
+                default: Some(false)
+            }
+        );
+
+        let decl = parse_option_line("option name ClearHash type button").unwrap();
+        assert_eq!(decl.kind, UsiOptionKind::Button);
+    }
+
+    #[test]
+    fn test_parse_combo_option() {
+        let decl = parse_option_line(
+            "option name BookMoveSelection type combo default Weighted var Best var Weighted var Random",
+        )
+        .unwrap();
+        assert_eq!(
+            decl.kind,
+            UsiOptionKind::Combo {
+                default: Some("Weighted".to_string()),
+                vars: vec![
+                    "Best".to_string(),
+                    "Weighted".to_string(),
+                    "Random".to_string()
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_string_and_filename_options() {
+        let decl = parse_option_line("option name EvalDir type string default eval").unwrap();
+        assert_eq!(
+            decl.kind,
+            UsiOptionKind::String {
+                default: Some("eval".to_string())
+            }
+        );
+
+        let decl = parse_option_line("option name BookFile type filename default <empty>").unwrap();
+        assert_eq!(
+            decl.kind,
+            UsiOptionKind::Filename {
+                default: Some(String::new())
+            }
+        );
+
+        let decl =
+            parse_option_line("option name BookDir type string default my books/main").unwrap();
+        assert_eq!(
+            decl.kind,
+            UsiOptionKind::String {
+                default: Some("my books/main".to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_option_lines() {
+        assert!(parse_option_line("option name Foo").is_none());
+        assert!(parse_option_line("option type spin default 1").is_none());
+        assert!(parse_option_line("option name Foo type unknown").is_none());
+        assert!(parse_option_line("id name Foo").is_none());
+    }
+
+    fn engine_info() -> EngineInfo {
+        let lines = [
+            "option name Threads type spin default 4 min 1 max 512",
+            "option name USI_Ponder type check default false",
+            "option name BookMoveSelection type combo default Best var Best var Random",
+            "option name EvalDir type string default eval",
+            "option name ClearHash type button",
+        ];
+        EngineInfo {
+            name: Some("Test".to_string()),
+            author: None,
+            options: lines.iter().filter_map(|l| parse_option_line(l)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_validate_option_values() {
+        let info = engine_info();
+        assert_eq!(
+            info.validate_option("Threads", Some("8")),
+            Ok(Some("8".to_string()))
+        );
+        assert_eq!(
+            info.validate_option("USI_Ponder", Some("true")),
+            Ok(Some("true".to_string()))
+        );
+        assert_eq!(
+            info.validate_option("BookMoveSelection", Some("Random")),
+            Ok(Some("Random".to_string()))
+        );
+        assert_eq!(
+            info.validate_option("EvalDir", Some("eval/nnue")),
+            Ok(Some("eval/nnue".to_string()))
+        );
+        assert_eq!(info.validate_option("ClearHash", None), Ok(None));
+        // Not declared, but always accepted from the GUI
+        assert_eq!(
+            info.validate_option("USI_Hash", Some("1024")),
+            Ok(Some("1024".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_validate_option_errors() {
+        let info = engine_info();
+        assert_eq!(
+            info.validate_option("Threads", Some("0")).unwrap_err(),
+            "Option Threads must be between 1 and 512, got 0"
+        );
+        assert!(info.validate_option("Threads", Some("many")).is_err());
+        assert!(info.validate_option("Threads", None).is_err());
+        assert!(info.validate_option("USI_Ponder", Some("yes")).is_err());
+        assert!(info
+            .validate_option("BookMoveSelection", Some("Worst"))
+            .is_err());
+        assert!(info.validate_option("EvalDir", Some("a\nb")).is_err());
+        assert!(info.validate_option("ClearHash", Some("true")).is_err());
+        assert_eq!(
+            info.validate_option("Nonexistent", Some("1")).unwrap_err(),
+            "Unknown engine option: Nonexistent"
+        );
+    }
+
+    #[test]
+    fn test_option_serialization() {
+        let decl =
+            parse_option_line("option name Threads type spin default 4 min 1 max 512").unwrap();
+        let json = serde_json::to_value(&decl).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "name": "Threads",
+                "type": "spin",
+                "default": 4,
+                "min": 1,
+                "max": 512
+            })
+        );
+    }
+}
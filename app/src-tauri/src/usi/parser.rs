@@ -2,25 +2,64 @@
 
 use serde::{Deserialize, Serialize};
 
+use super::options::{parse_option_line, UsiOptionDecl};
+
+/// Evaluation reported in an "info score" field, from the side to move's view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum Score {
+    /// Score in centipawns
+    Cp(i32),
+    /// Mate in N plies; negative when the side to move is being mated
+    Mate(i32),
+    /// Mate without a known distance ("mate +" / "mate -"); true when the side to move mates
+    MateUnknown(bool),
+}
+
+/// Whether the score is exact or only a bound from a failed aspiration search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScoreBound {
+    #[default]
+    Exact,
+    /// "lowerbound": the real score is at least this
+    Lower,
+    /// "upperbound": the real score is at most this
+    Upper,
+}
+
 /// Information about the engine's current thinking
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ThinkingInfo {
     pub depth: Option<u32>,
-    pub score_cp: Option<i32>,  // Score in centipawns
-    pub nodes: Option<u64>,      // Number of nodes searched
-    pub nps: Option<u64>,        // Nodes per second
-    pub time: Option<u32>,       // Time in milliseconds
-    pub pv: Vec<String>,         // Principal variation (best line)
+    pub seldepth: Option<u32>,      // Selective search depth
+    pub score: Option<Score>,
+    pub bound: ScoreBound,
+    pub multipv: Option<u32>,       // Index of the line when searching several PVs
+    pub nodes: Option<u64>,         // Number of nodes searched
+    pub nps: Option<u64>,           // Nodes per second
+    pub hashfull: Option<u32>,      // Hash table usage in permille
+    pub time: Option<u32>,          // Time in milliseconds
+    pub currmove: Option<String>,   // Move currently being searched
+    pub string: Option<String>,     // Free text from the engine
+    pub pv: Vec<String>,            // Principal variation (best line)
 }
 
 impl ThinkingInfo {
     pub fn new() -> Self {
         ThinkingInfo {
             depth: None,
-            score_cp: None,
+            seldepth: None,
+            score: None,
+            bound: ScoreBound::Exact,
+            multipv: None,
             nodes: None,
             nps: None,
+            hashfull: None,
             time: None,
+            currmove: None,
+            string: None,
             pv: Vec::new(),
         }
     }
@@ -32,11 +71,70 @@ impl Default for ThinkingInfo {
     }
 }
 
+/// Answer to a "go mate" (tsume) search
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "moves", rename_all = "camelCase")]
+pub enum TsumeResult {
+    /// "checkmate <move1> ...": the mating line in USI notation
+    Mate(Vec<String>),
+    /// "checkmate nomate": there is no forced mate
+    NoMate,
+    /// "checkmate timeout": no answer within the time allowed
+    Timeout,
+    /// "checkmate notimplemented": the engine cannot solve tsume
+    NotImplemented,
+}
+
+/// Answer of a "bestmove" line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "usi", rename_all = "camelCase")]
+pub enum BestMove {
+    /// A move in USI notation
+    Move(String),
+    /// "bestmove resign": the engine gives up and loses
+    Resign,
+    /// "bestmove win": the engine declares a win by entering king (nyugyoku)
+    Win,
+}
+
+impl BestMove {
+    /// Interpret the move field of a "bestmove" line
+    pub fn from_usi(usi: &str) -> BestMove {
+        match usi {
+            "resign" => BestMove::Resign,
+            "win" => BestMove::Win,
+            usi => BestMove::Move(usi.to_string()),
+        }
+    }
+
+    /// The move field as the engine sent it
+    pub fn to_usi(&self) -> &str {
+        match self {
+            BestMove::Move(usi) => usi,
+            BestMove::Resign => "resign",
+            BestMove::Win => "win",
+        }
+    }
+
+    /// The move in USI notation, if the engine played one
+    pub fn as_move(&self) -> Option<&str> {
+        match self {
+            BestMove::Move(usi) => Some(usi),
+            BestMove::Resign | BestMove::Win => None,
+        }
+    }
+}
+
 /// Parse a USI response line
+#[derive(Debug, Clone, PartialEq)]
 pub enum UsiResponse {
+    IdName(String),
+    IdAuthor(String),
+    Option(UsiOptionDecl),
     UsiOk,
     ReadyOk,
-    BestMove { best_move: String, ponder: Option<String> },
+    BestMove { best_move: BestMove, ponder: Option<String> },
+    Checkmate(TsumeResult),
     Info(ThinkingInfo),
     Unknown(String),
 }
@@ -49,6 +147,20 @@ pub fn parse_usi_line(line: &str) -> UsiResponse {
         return UsiResponse::Unknown(String::new());
     }
 
+    if let Some(name) = trimmed.strip_prefix("id name ") {
+        return UsiResponse::IdName(name.trim().to_string());
+    }
+
+    if let Some(author) = trimmed.strip_prefix("id author ") {
+        return UsiResponse::IdAuthor(author.trim().to_string());
+    }
+
+    if trimmed.starts_with("option ") {
+        if let Some(decl) = parse_option_line(trimmed) {
+            return UsiResponse::Option(decl);
+        }
+    }
+
     if trimmed == "usiok" {
         return UsiResponse::UsiOk;
     }
@@ -61,6 +173,10 @@ pub fn parse_usi_line(line: &str) -> UsiResponse {
         return parse_bestmove(trimmed);
     }
 
+    if trimmed.starts_with("checkmate") {
+        return parse_checkmate(trimmed);
+    }
+
     if trimmed.starts_with("info") {
         return parse_info(trimmed);
     }
@@ -69,7 +185,7 @@ pub fn parse_usi_line(line: &str) -> UsiResponse {
 }
 
 /// Parse a "bestmove" line
-/// Format: "bestmove <move> [ponder <move>]"
+/// Format: "bestmove <move|resign|win> [ponder <move>]"
 fn parse_bestmove(line: &str) -> UsiResponse {
     let parts: Vec<&str> = line.split_whitespace().collect();
 
@@ -77,7 +193,7 @@ fn parse_bestmove(line: &str) -> UsiResponse {
         return UsiResponse::Unknown(line.to_string());
     }
 
-    let best_move = parts[1].to_string();
+    let best_move = BestMove::from_usi(parts[1]);
 
     let ponder = if parts.len() >= 4 && parts[2] == "ponder" {
         Some(parts[3].to_string())
@@ -88,69 +204,127 @@ fn parse_bestmove(line: &str) -> UsiResponse {
     UsiResponse::BestMove { best_move, ponder }
 }
 
+/// Parse a "checkmate" line
+/// Format: "checkmate <move1> <move2> ..." or "checkmate <nomate|timeout|notimplemented>"
+fn parse_checkmate(line: &str) -> UsiResponse {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+
+    let result = match parts[1..] {
+        [] => return UsiResponse::Unknown(line.to_string()),
+        ["nomate"] => TsumeResult::NoMate,
+        ["timeout"] => TsumeResult::Timeout,
+        ["notimplemented"] => TsumeResult::NotImplemented,
+        _ => TsumeResult::Mate(parts[1..].iter().map(|mv| mv.to_string()).collect()),
+    };
+    UsiResponse::Checkmate(result)
+}
+
+/// Keywords `parse_info` understands
+const INFO_KEYWORDS: [&str; 13] = [
+    "depth",
+    "seldepth",
+    "multipv",
+    "nodes",
+    "nps",
+    "hashfull",
+    "time",
+    "currmove",
+    "score",
+    "lowerbound",
+    "upperbound",
+    "string",
+    "pv",
+];
+
 /// Parse an "info" line
-/// Format: "info depth <d> score cp <s> nodes <n> nps <nps> time <t> pv <moves...>"
+/// Format: "info [depth <d>] [seldepth <sd>] [score cp <s>|mate <n|+|->] [lowerbound|upperbound]
+///          [multipv <k>] [nodes <n>] [nps <nps>] [hashfull <h>] [time <t>] [currmove <m>]
+///          [string <text...>] [pv <moves...>]"
 fn parse_info(line: &str) -> UsiResponse {
     let parts: Vec<&str> = line.split_whitespace().collect();
     let mut info = ThinkingInfo::new();
 
     let mut i = 1; // Skip "info"
     while i < parts.len() {
+        let value = parts.get(i + 1).copied();
         match parts[i] {
-            "depth" => {
-                if i + 1 < parts.len() {
-                    info.depth = parts[i + 1].parse().ok();
-                    i += 2;
-                } else {
-                    i += 1;
-                }
-            }
+            "depth" => info.depth = value.and_then(|v| v.parse().ok()),
+            "seldepth" => info.seldepth = value.and_then(|v| v.parse().ok()),
+            "multipv" => info.multipv = value.and_then(|v| v.parse().ok()),
+            "nodes" => info.nodes = value.and_then(|v| v.parse().ok()),
+            "nps" => info.nps = value.and_then(|v| v.parse().ok()),
+            "hashfull" => info.hashfull = value.and_then(|v| v.parse().ok()),
+            "time" => info.time = value.and_then(|v| v.parse().ok()),
+            "currmove" => info.currmove = value.map(|v| v.to_string()),
             "score" => {
-                if i + 2 < parts.len() && parts[i + 1] == "cp" {
-                    info.score_cp = parts[i + 2].parse().ok();
-                    i += 3;
-                } else {
-                    i += 1;
-                }
+                info.score = parts.get(i + 2).and_then(|v| parse_score(value?, v));
+                i += 3;
+                continue;
             }
-            "nodes" => {
-                if i + 1 < parts.len() {
-                    info.nodes = parts[i + 1].parse().ok();
-                    i += 2;
-                } else {
-                    i += 1;
-                }
+            // Bounds may follow the score value
+            "lowerbound" => {
+                info.bound = ScoreBound::Lower;
+                i += 1;
+                continue;
             }
-            "nps" => {
-                if i + 1 < parts.len() {
-                    info.nps = parts[i + 1].parse().ok();
-                    i += 2;
-                } else {
-                    i += 1;
-                }
+            "upperbound" => {
+                info.bound = ScoreBound::Upper;
+                i += 1;
+                continue;
             }
-            "time" => {
-                if i + 1 < parts.len() {
-                    info.time = parts[i + 1].parse().ok();
-                    i += 2;
-                } else {
-                    i += 1;
-                }
+            "string" => {
+                // Free text swallows the rest of the line, keeping its spacing
+                info.string = Some(rest_of_line(line, "string"));
+                break;
             }
             "pv" => {
                 // Collect all remaining parts as the principal variation
                 info.pv = parts[i + 1..].iter().map(|s| s.to_string()).collect();
                 break;
             }
+            // Unknown keyword (e.g. "currmovenumber"); skip it, and its value
+            // unless the next token is a keyword of its own (a value-less flag)
             _ => {
-                i += 1;
+                i += match value {
+                    Some(v) if !INFO_KEYWORDS.contains(&v) => 2,
+                    _ => 1,
+                };
+                continue;
             }
         }
+        i += 2;
     }
 
     UsiResponse::Info(info)
 }
 
+/// Parse "cp <n>", "mate <n>", "mate +" or "mate -"
+fn parse_score(kind: &str, value: &str) -> Option<Score> {
+    match (kind, value) {
+        ("cp", v) => v.parse().ok().map(Score::Cp),
+        ("mate", "+") => Some(Score::MateUnknown(true)),
+        ("mate", "-") => Some(Score::MateUnknown(false)),
+        ("mate", v) => v.parse().ok().map(Score::Mate),
+        _ => None,
+    }
+}
+
+/// Text following the first `keyword` token of the line
+fn rest_of_line(line: &str, keyword: &str) -> String {
+    let mut rest = line;
+    while let Some(pos) = rest.find(keyword) {
+        let before = &rest[..pos];
+        let after = &rest[pos + keyword.len()..];
+        let at_token_start = before.is_empty() || before.ends_with(char::is_whitespace);
+        let at_token_end = after.is_empty() || after.starts_with(char::is_whitespace);
+        if at_token_start && at_token_end {
+            return after.trim().to_string();
+        }
+        rest = after;
+    }
+    String::new()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -163,6 +337,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_id_lines() {
+        match parse_usi_line("id name YaneuraOu NNUE 7.10 64AVX2 TOURNAMENT") {
+            UsiResponse::IdName(name) => assert_eq!(name, "YaneuraOu NNUE 7.10 64AVX2 TOURNAMENT"),
+            _ => panic!("Expected IdName"),
+        }
+        match parse_usi_line("id author by yaneurao") {
+            UsiResponse::IdAuthor(author) => assert_eq!(author, "by yaneurao"),
+            _ => panic!("Expected IdAuthor"),
+        }
+    }
+
+    #[test]
+    fn test_parse_option_line() {
+        match parse_usi_line("option name Threads type spin default 4 min 1 max 512") {
+            UsiResponse::Option(decl) => assert_eq!(decl.name, "Threads"),
+            _ => panic!("Expected Option"),
+        }
+        assert!(matches!(
+            parse_usi_line("option name Broken"),
+            UsiResponse::Unknown(_)
+        ));
+    }
+
     #[test]
     fn test_parse_readyok() {
         match parse_usi_line("readyok") {
@@ -175,30 +373,77 @@ mod tests {
     fn test_parse_bestmove() {
         match parse_usi_line("bestmove 7g7f") {
             UsiResponse::BestMove { best_move, ponder } => {
-                assert_eq!(best_move, "7g7f");
+                assert_eq!(best_move, BestMove::Move("7g7f".to_string()));
                 assert_eq!(ponder, None);
             }
             _ => panic!("Expected BestMove"),
         }
     }
 
+    #[test]
+    fn test_parse_bestmove_resign_and_win() {
+        assert_eq!(
+            parse_usi_line("bestmove resign"),
+            UsiResponse::BestMove {
+                best_move: BestMove::Resign,
+                ponder: None
+            }
+        );
+        assert_eq!(
+            parse_usi_line("bestmove win"),
+            UsiResponse::BestMove {
+                best_move: BestMove::Win,
+                ponder: None
+            }
+        );
+        assert_eq!(BestMove::Win.to_usi(), "win");
+    }
+
     #[test]
     fn test_parse_bestmove_with_ponder() {
         match parse_usi_line("bestmove 7g7f ponder 3c3d") {
             UsiResponse::BestMove { best_move, ponder } => {
-                assert_eq!(best_move, "7g7f");
+                assert_eq!(best_move.as_move(), Some("7g7f"));
                 assert_eq!(ponder, Some("3c3d".to_string()));
             }
             _ => panic!("Expected BestMove with ponder"),
         }
     }
 
+    #[test]
+    fn test_parse_checkmate() {
+        assert_eq!(
+            parse_usi_line("checkmate G*5b 5a4a 5b4b"),
+            UsiResponse::Checkmate(TsumeResult::Mate(vec![
+                "G*5b".to_string(),
+                "5a4a".to_string(),
+                "5b4b".to_string()
+            ]))
+        );
+        assert_eq!(
+            parse_usi_line("checkmate nomate"),
+            UsiResponse::Checkmate(TsumeResult::NoMate)
+        );
+        assert_eq!(
+            parse_usi_line("checkmate timeout"),
+            UsiResponse::Checkmate(TsumeResult::Timeout)
+        );
+        assert_eq!(
+            parse_usi_line("checkmate notimplemented"),
+            UsiResponse::Checkmate(TsumeResult::NotImplemented)
+        );
+        assert_eq!(
+            parse_usi_line("checkmate"),
+            UsiResponse::Unknown("checkmate".to_string())
+        );
+    }
+
     #[test]
     fn test_parse_info() {
         match parse_usi_line("info depth 5 score cp 100 nodes 1000 nps 50000 time 20 pv 7g7f 3c3d") {
             UsiResponse::Info(info) => {
                 assert_eq!(info.depth, Some(5));
-                assert_eq!(info.score_cp, Some(100));
+                assert_eq!(info.score, Some(Score::Cp(100)));
                 assert_eq!(info.nodes, Some(1000));
                 assert_eq!(info.nps, Some(50000));
                 assert_eq!(info.time, Some(20));
@@ -207,4 +452,108 @@ mod tests {
             _ => panic!("Expected Info"),
         }
     }
+
+    fn info(line: &str) -> ThinkingInfo {
+        match parse_usi_line(line) {
+            UsiResponse::Info(info) => info,
+            _ => panic!("Expected Info: {}", line),
+        }
+    }
+
+    #[test]
+    fn test_parse_info_yaneuraou_full_line() {
+        let info = info(
+            "info depth 18 seldepth 24 score cp 52 nodes 4853277 nps 1617759 hashfull 284 \
+             time 3000 pv 2g2f 8c8d 2f2e 8d8e 6i7h 4a3b",
+        );
+        assert_eq!(info.depth, Some(18));
+        assert_eq!(info.seldepth, Some(24));
+        assert_eq!(info.score, Some(Score::Cp(52)));
+        assert_eq!(info.bound, ScoreBound::Exact);
+        assert_eq!(info.nodes, Some(4853277));
+        assert_eq!(info.nps, Some(1617759));
+        assert_eq!(info.hashfull, Some(284));
+        assert_eq!(info.time, Some(3000));
+        assert_eq!(info.pv.len(), 6);
+        assert_eq!(info.pv[5], "4a3b");
+    }
+
+    #[test]
+    fn test_parse_info_bounds() {
+        let info1 = info("info depth 21 seldepth 29 score cp 118 upperbound nodes 9241823 nps 1540303 time 6000 pv 7g7f");
+        assert_eq!(info1.score, Some(Score::Cp(118)));
+        assert_eq!(info1.bound, ScoreBound::Upper);
+        assert_eq!(info1.nodes, Some(9241823));
+
+        let info2 = info("info depth 21 seldepth 27 score cp -35 lowerbound nodes 8802113 nps 1467018 time 6000 pv 8c8d");
+        assert_eq!(info2.score, Some(Score::Cp(-35)));
+        assert_eq!(info2.bound, ScoreBound::Lower);
+    }
+
+    #[test]
+    fn test_parse_info_mate_scores() {
+        let info1 = info("info depth 13 seldepth 14 score mate 13 nodes 60231 nps 1338466 time 45 pv G*5b 4a5b S*4b");
+        assert_eq!(info1.score, Some(Score::Mate(13)));
+        assert_eq!(info1.pv[0], "G*5b");
+
+        let info2 = info("info depth 12 seldepth 12 score mate -12 nodes 48811 nps 1220275 time 40 pv 5a4b");
+        assert_eq!(info2.score, Some(Score::Mate(-12)));
+
+        assert_eq!(info("info score mate + pv 5c5b+").score, Some(Score::MateUnknown(true)));
+        assert_eq!(info("info score mate -").score, Some(Score::MateUnknown(false)));
+        assert_eq!(info("info score mate +7").score, Some(Score::Mate(7)));
+    }
+
+    #[test]
+    fn test_parse_info_suisho_multipv() {
+        let info = info(
+            "info depth 16 seldepth 21 score cp -48 multipv 2 nodes 3178920 nps 1589460 \
+             hashfull 97 time 2000 pv 3c3d 7g7f 4c4d",
+        );
+        assert_eq!(info.multipv, Some(2));
+        assert_eq!(info.score, Some(Score::Cp(-48)));
+        assert_eq!(info.hashfull, Some(97));
+        assert_eq!(info.pv, vec!["3c3d", "7g7f", "4c4d"]);
+    }
+
+    #[test]
+    fn test_parse_info_currmove() {
+        let info = info("info depth 9 currmove 7g7f currmovenumber 3");
+        assert_eq!(info.depth, Some(9));
+        assert_eq!(info.currmove, Some("7g7f".to_string()));
+        assert!(info.pv.is_empty());
+    }
+
+    #[test]
+    fn test_parse_info_skips_unknown_flags() {
+        // A value-less flag must not swallow the keyword after it
+        let info1 = info("info sbhits depth 5 nodes 1200 pv 7g7f");
+        assert_eq!(info1.depth, Some(5));
+        assert_eq!(info1.nodes, Some(1200));
+        assert_eq!(info1.pv, vec!["7g7f"]);
+
+        let info2 = info("info depth 7 tbhits 12 refutation pv 2g2f");
+        assert_eq!(info2.depth, Some(7));
+        assert_eq!(info2.pv, vec!["2g2f"]);
+    }
+
+    #[test]
+    fn test_parse_info_string_swallows_rest() {
+        let info1 = info("info string loading eval file : eval/nn.bin");
+        assert_eq!(info1.string, Some("loading eval file : eval/nn.bin".to_string()));
+
+        // Keywords inside the text must not be parsed
+        let info2 = info("info depth 3 string depth 99 pv 7g7f is just text");
+        assert_eq!(info2.depth, Some(3));
+        assert_eq!(info2.string, Some("depth 99 pv 7g7f is just text".to_string()));
+        assert!(info2.pv.is_empty());
+    }
+
+    #[test]
+    fn test_score_serialization() {
+        let json = serde_json::to_value(Score::Mate(-3)).unwrap();
+        assert_eq!(json, serde_json::json!({ "type": "mate", "value": -3 }));
+        let json = serde_json::to_value(ScoreBound::Upper).unwrap();
+        assert_eq!(json, "upper");
+    }
 }
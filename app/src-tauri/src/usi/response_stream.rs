@@ -0,0 +1,215 @@
+// Parsed engine output delivered to any number of subscribers
+// A reader thread parses each stdout line once and hands it to every subscriber
+// through a bounded channel; responses are never dropped, so a full channel
+// blocks the reader (backpressure)
+
+use std::io::{BufRead, BufReader, Read};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::parser::{parse_usi_line, UsiResponse};
+
+/// Default number of responses a subscriber may have queued before the reader waits
+pub const RESPONSE_BUFFER_SIZE: usize = 1024;
+
+#[derive(Default)]
+struct Subscribers {
+    senders: Vec<(u64, SyncSender<UsiResponse>)>,
+    next_id: u64,
+    closed: bool,
+}
+
+/// Broadcasts engine responses to its subscribers
+#[derive(Clone, Default)]
+pub struct ResponseStream {
+    subscribers: Arc<Mutex<Subscribers>>,
+}
+
+impl ResponseStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Receive every response published from now on, buffering up to
+    /// `capacity` (at least one); while the buffer is full the reader waits,
+    /// so the subscriber must keep draining it
+    pub fn subscribe(&self, capacity: usize) -> Subscription {
+        let (sender, receiver) = mpsc::sync_channel(capacity.max(1));
+        let mut subscribers = self.subscribers.lock().unwrap();
+        // Subscribers of a closed stream see the end right away
+        if !subscribers.closed {
+            let id = subscribers.next_id;
+            subscribers.next_id += 1;
+            subscribers.senders.push((id, sender));
+        }
+        Subscription { receiver }
+    }
+
+    /// Deliver a response to every subscriber, waiting while a buffer is full
+    /// Subscribers that have been dropped are removed
+    pub fn publish(&self, response: UsiResponse) {
+        // Send outside the lock so a full subscriber does not block `subscribe`
+        let senders = self.subscribers.lock().unwrap().senders.clone();
+        let disconnected: Vec<u64> = senders
+            .iter()
+            .filter(|(_, sender)| sender.send(response.clone()).is_err())
+            .map(|(id, _)| *id)
+            .collect();
+
+        if !disconnected.is_empty() {
+            let mut subscribers = self.subscribers.lock().unwrap();
+            subscribers
+                .senders
+                .retain(|(id, _)| !disconnected.contains(id));
+        }
+    }
+
+    /// End the stream; subscribers receive the remaining responses, then an error
+    pub fn close(&self) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.closed = true;
+        subscribers.senders.clear();
+    }
+
+    /// Parse and publish every line of `output` on a background thread,
+    /// closing the stream when the output ends
+    pub fn spawn_reader<R: Read + Send + 'static>(&self, output: R) -> JoinHandle<()> {
+        let stream = self.clone();
+        thread::spawn(move || {
+            let reader = BufReader::new(output);
+            for line in reader.lines().map_while(Result::ok) {
+                stream.publish(parse_usi_line(&line));
+            }
+            stream.close();
+        })
+    }
+}
+
+/// Receiving end of a `ResponseStream`
+pub struct Subscription {
+    receiver: Receiver<UsiResponse>,
+}
+
+impl Subscription {
+    /// Wait up to `timeout` for the next response
+    /// `Disconnected` means the stream has ended and nothing is left to receive
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<UsiResponse, RecvTimeoutError> {
+        self.receiver.recv_timeout(timeout)
+    }
+
+    /// Wait for the next response; None once the stream has ended
+    pub fn recv(&self) -> Option<UsiResponse> {
+        self.receiver.recv().ok()
+    }
+
+    /// Next response if one is already queued
+    pub fn try_recv(&self) -> Option<UsiResponse> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Iterator for Subscription {
+    type Item = UsiResponse;
+
+    fn next(&mut self) -> Option<UsiResponse> {
+        self.recv()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn output(lines: &[&str]) -> Cursor<Vec<u8>> {
+        Cursor::new(lines.join("\n").into_bytes())
+    }
+
+    #[test]
+    fn test_every_subscriber_gets_every_response() {
+        let stream = ResponseStream::new();
+        let logger = stream.subscribe(RESPONSE_BUFFER_SIZE);
+        let waiter = stream.subscribe(RESPONSE_BUFFER_SIZE);
+
+        stream
+            .spawn_reader(output(&["id name Fake", "usiok", "readyok"]))
+            .join()
+            .unwrap();
+
+        for subscription in [logger, waiter] {
+            let responses: Vec<UsiResponse> = subscription.collect();
+            assert_eq!(
+                responses,
+                vec![
+                    UsiResponse::IdName("Fake".to_string()),
+                    UsiResponse::UsiOk,
+                    UsiResponse::ReadyOk
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_full_buffer_applies_backpressure() {
+        let stream = ResponseStream::new();
+        let subscription = stream.subscribe(1);
+
+        let lines: Vec<String> = (1..=50).map(|d| format!("info depth {}", d)).collect();
+        let lines: Vec<&str> = lines.iter().map(|l| l.as_str()).collect();
+        let reader = stream.spawn_reader(output(&lines));
+
+        // The reader cannot get ahead of the subscriber, yet nothing is lost
+        thread::sleep(Duration::from_millis(50));
+        assert!(!reader.is_finished());
+
+        let depths: Vec<u32> = subscription
+            .map(|response| match response {
+                UsiResponse::Info(info) => info.depth.unwrap(),
+                _ => panic!("Expected Info"),
+            })
+            .collect();
+        assert_eq!(depths, (1..=50).collect::<Vec<u32>>());
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn test_dropped_subscriber_does_not_block_others() {
+        let stream = ResponseStream::new();
+        let dropped = stream.subscribe(1);
+        let kept = stream.subscribe(RESPONSE_BUFFER_SIZE);
+        drop(dropped);
+
+        stream
+            .spawn_reader(output(&["usiok", "readyok", "readyok"]))
+            .join()
+            .unwrap();
+        assert_eq!(kept.count(), 3);
+    }
+
+    #[test]
+    fn test_recv_timeout_and_close() {
+        let stream = ResponseStream::new();
+        let subscription = stream.subscribe(RESPONSE_BUFFER_SIZE);
+
+        assert_eq!(
+            subscription.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        stream.publish(UsiResponse::ReadyOk);
+        stream.close();
+        assert_eq!(
+            subscription.recv_timeout(Duration::from_millis(10)),
+            Ok(UsiResponse::ReadyOk)
+        );
+        assert_eq!(
+            subscription.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+
+        // Late subscribers see the end immediately
+        assert!(stream.subscribe(1).recv().is_none());
+    }
+}
@@ -0,0 +1,456 @@
+// Engine searches running on a background thread
+// Commands get a handle back immediately and can wait for or stop the search
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::best_move::{check_best_move, SearchError};
+use super::commands::GoLimits;
+use super::error::UsiError;
+use super::parser::{BestMove, Score, ThinkingInfo};
+use super::shogi_engine::{position_after_moves, GoResult, InfoCallback, ShogiEngine, StopHandle};
+use crate::shogi::DeclarationRule;
+
+/// Engine slot shared between the Tauri commands and the search thread
+pub type SharedEngine = Arc<Mutex<Option<Box<dyn ShogiEngine>>>>;
+
+/// How often `stop` is repeated while waiting for the bestmove, in case the
+/// first one reached the engine before its "go"
+const STOP_RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
+/// What to search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchRequest {
+    pub sfen: String,
+    pub moves: Vec<String>,
+    pub limits: GoLimits,
+    /// Send "usinewgame" before the position
+    pub new_game: bool,
+    /// Rule a "bestmove win" is checked against
+    pub declaration_rule: DeclarationRule,
+}
+
+/// Result of a background search
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchOutcome {
+    pub search_id: u64,
+    /// The engine's move, resignation or win declaration, checked against the position
+    pub best_move: Option<BestMove>,
+    pub ponder: Option<String>,
+    pub error: Option<SearchError>,
+    /// Last score the engine reported for its main line, from its own point of view
+    pub score: Option<Score>,
+    /// Thinking time charged to the engine; a ponder search is only charged
+    /// from its "ponderhit"
+    pub time_ms: u64,
+    /// A ponder search that was stopped before "ponderhit": its move answers
+    /// a position that never arose
+    pub pondering: bool,
+}
+
+impl SearchOutcome {
+    /// The best move, or the error that ended the search
+    pub fn into_result(self) -> Result<BestMove, SearchError> {
+        match (self.best_move, self.error) {
+            (Some(best_move), None) => Ok(best_move),
+            (_, Some(error)) => Err(error),
+            (None, None) => Err("Search ended without a best move".into()),
+        }
+    }
+}
+
+/// Where the search thread leaves its outcome for any number of waiters
+#[derive(Default)]
+struct OutcomeSlot {
+    outcome: Mutex<Option<SearchOutcome>>,
+    ready: Condvar,
+    /// When "ponderhit" was sent to a ponder search
+    ponderhit_at: Mutex<Option<Instant>>,
+}
+
+/// Handle to a search running in the background
+#[derive(Clone)]
+pub struct SearchHandle {
+    id: u64,
+    stop: StopHandle,
+    slot: Arc<OutcomeSlot>,
+    /// Position a ponder search thinks on, including the expected move
+    ponder_position: Option<Arc<(String, Vec<String>)>>,
+}
+
+impl SearchHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Whether this is a ponder search still waiting for "ponderhit"
+    pub fn is_pondering(&self) -> bool {
+        self.ponder_position.is_some()
+            && self.slot.ponderhit_at.lock().unwrap().is_none()
+            && !self.is_finished()
+    }
+
+    /// Whether this search is pondering on the position after `moves` from `sfen`
+    pub fn ponders_on(&self, sfen: &str, moves: &[String]) -> bool {
+        self.is_pondering()
+            && self
+                .ponder_position
+                .as_deref()
+                .is_some_and(|(ponder_sfen, ponder_moves)| {
+                    ponder_sfen == sfen && ponder_moves == moves
+                })
+    }
+
+    /// The expected move was played: keep searching as a normal search,
+    /// with the engine's clock starting now
+    pub fn ponderhit(&self) -> Result<(), UsiError> {
+        if !self.is_pondering() {
+            return Err("Search is not pondering".into());
+        }
+        // Recorded before sending, so the search thread cannot see the
+        // bestmove without the ponderhit
+        let mut ponderhit_at = self.slot.ponderhit_at.lock().unwrap();
+        *ponderhit_at = Some(Instant::now());
+        self.stop.ponderhit().inspect_err(|_| *ponderhit_at = None)
+    }
+
+    /// The outcome if the search has finished
+    pub fn try_outcome(&self) -> Option<SearchOutcome> {
+        self.slot.outcome.lock().unwrap().clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.try_outcome().is_some()
+    }
+
+    /// Block until the search finishes
+    pub fn wait(&self) -> SearchOutcome {
+        let mut outcome = self.slot.outcome.lock().unwrap();
+        loop {
+            if let Some(outcome) = outcome.as_ref() {
+                return outcome.clone();
+            }
+            outcome = self.slot.ready.wait(outcome).unwrap();
+        }
+    }
+
+    /// Block until the search finishes or the timeout expires
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<SearchOutcome> {
+        let outcome = self.slot.outcome.lock().unwrap();
+        let (outcome, _) = self
+            .slot
+            .ready
+            .wait_timeout_while(outcome, timeout, |outcome| outcome.is_none())
+            .unwrap();
+        outcome.clone()
+    }
+
+    /// Send "stop" and wait up to `timeout` for the resulting bestmove
+    pub fn stop_and_wait(&self, timeout: Duration) -> Option<SearchOutcome> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(outcome) = self.try_outcome() {
+                return Some(outcome);
+            }
+            let _ = self.stop.stop();
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return None;
+            }
+            if let Some(outcome) = self.wait_timeout(remaining.min(STOP_RETRY_INTERVAL)) {
+                return Some(outcome);
+            }
+        }
+    }
+
+    fn finish(&self, outcome: SearchOutcome) {
+        *self.slot.outcome.lock().unwrap() = Some(outcome);
+        self.slot.ready.notify_all();
+    }
+}
+
+/// Start `request` on a background thread and return its handle immediately
+/// `on_info` receives thinking info while searching; `on_done` is called with the
+/// outcome before waiters are woken; invalid limits are rejected up front
+pub fn spawn_search<F>(
+    engine: SharedEngine,
+    id: u64,
+    request: SearchRequest,
+    on_info: Option<InfoCallback>,
+    on_done: F,
+) -> Result<SearchHandle, UsiError>
+where
+    F: FnOnce(&SearchOutcome) + Send + 'static,
+{
+    request.limits.validate()?;
+    let stop = {
+        let engine_lock = engine.lock().map_err(|e| e.to_string())?;
+        let engine = engine_lock.as_ref().ok_or(UsiError::NotStarted)?;
+        engine.stop_handle()
+    };
+
+    let ponder_position = request
+        .limits
+        .ponder
+        .then(|| Arc::new((request.sfen.clone(), request.moves.clone())));
+    let handle = SearchHandle {
+        id,
+        stop,
+        slot: Arc::new(OutcomeSlot::default()),
+        ponder_position,
+    };
+
+    let task = handle.clone();
+    thread::spawn(move || {
+        let started = Instant::now();
+        let (sfen, moves) = (request.sfen.clone(), request.moves.clone());
+        let rule = request.declaration_rule;
+        let score = Arc::new(Mutex::new(None));
+        let on_info = record_score(on_info, Arc::clone(&score));
+        let result = run_search(&engine, request, Some(on_info));
+
+        // Time spent pondering is the opponent's time, not the engine's
+        let ponderhit_at = *task.slot.ponderhit_at.lock().unwrap();
+        let pondering = task.ponder_position.is_some() && ponderhit_at.is_none();
+        let time_ms = ponderhit_at.unwrap_or(started).elapsed().as_millis() as u64;
+
+        // Nothing the engine sends reaches the game without being checked
+        let checked = result.map_err(SearchError::from).and_then(|go| {
+            let pos = position_after_moves(&sfen, &moves)?;
+            Ok((check_best_move(&pos, go.best_move, rule)?, go.ponder))
+        });
+        let (best_move, ponder, error) = match checked {
+            Ok((best_move, ponder)) => (Some(best_move), ponder, None),
+            Err(error) => (None, None, Some(error)),
+        };
+        let outcome = SearchOutcome {
+            search_id: id,
+            best_move,
+            ponder,
+            error,
+            score: *score.lock().unwrap(),
+            time_ms,
+            pondering,
+        };
+        on_done(&outcome);
+        task.finish(outcome);
+    });
+
+    Ok(handle)
+}
+
+/// Wrap `on_info` so the last score of the main line is kept in `score`
+fn record_score(
+    mut on_info: Option<InfoCallback>,
+    score: Arc<Mutex<Option<Score>>>,
+) -> InfoCallback {
+    Box::new(move |info: &ThinkingInfo| {
+        if info.score.is_some() && info.multipv.unwrap_or(1) == 1 {
+            *score.lock().unwrap() = info.score;
+        }
+        if let Some(on_info) = on_info.as_mut() {
+            on_info(info);
+        }
+    })
+}
+
+fn run_search(
+    engine: &SharedEngine,
+    request: SearchRequest,
+    on_info: Option<InfoCallback>,
+) -> Result<GoResult, UsiError> {
+    let mut engine_lock = engine.lock().map_err(|e| e.to_string())?;
+    let engine = engine_lock.as_mut().ok_or(UsiError::NotStarted)?;
+
+    if request.new_game {
+        engine.new_game()?;
+    }
+    engine.set_declaration_rule(request.declaration_rule);
+    engine.set_info_callback(on_info);
+    let result = engine
+        .set_position(&request.sfen, &request.moves)
+        .and_then(|_| engine.go(&request.limits));
+    engine.set_info_callback(None);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shogi::{Move, Position, STARTPOS_SFEN};
+    use crate::usi::{MockEngine, MockStrategy, NativeEngine, ScriptedEngine};
+
+    fn shared(mut engine: Box<dyn ShogiEngine>) -> SharedEngine {
+        engine.init().unwrap();
+        Arc::new(Mutex::new(Some(engine)))
+    }
+
+    fn request(limits: GoLimits) -> SearchRequest {
+        SearchRequest {
+            sfen: STARTPOS_SFEN.to_string(),
+            moves: vec!["7g7f".to_string()],
+            limits,
+            new_game: true,
+            declaration_rule: DeclarationRule::default(),
+        }
+    }
+
+    #[test]
+    fn test_search_returns_handle_and_outcome() {
+        let engine = shared(Box::new(MockEngine::with_strategy(MockStrategy::Random {
+            seed: 1,
+        })));
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let handle = spawn_search(engine, 3, request(GoLimits::byoyomi(100)), None, move |o| {
+            tx.send(o.clone()).unwrap();
+        })
+        .unwrap();
+        assert_eq!(handle.id(), 3);
+
+        let outcome = handle.wait();
+        assert_eq!(outcome.search_id, 3);
+        assert_eq!(rx.recv().unwrap(), outcome);
+        assert!(handle.is_finished());
+
+        let best = outcome.into_result().unwrap();
+        let pos = Position::startpos().after_move(Move::from_usi("7g7f").unwrap());
+        assert!(pos.is_legal(Move::from_usi(best.as_move().unwrap()).unwrap()));
+    }
+
+    #[test]
+    fn test_stop_ends_long_search() {
+        let engine = shared(Box::new(NativeEngine::new()));
+        let limits = GoLimits::byoyomi(60_000);
+
+        let start = Instant::now();
+        let handle = spawn_search(engine.clone(), 1, request(limits), None, |_| {}).unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let outcome = handle.stop_and_wait(Duration::from_secs(5)).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(outcome.best_move.is_some());
+
+        // The engine is free again once the search has ended
+        assert!(engine.try_lock().is_ok());
+    }
+
+    #[test]
+    fn test_outcome_keeps_last_score() {
+        let engine = shared(Box::new(NativeEngine::new()));
+        let (tx, rx) = std::sync::mpsc::channel();
+        let on_info: InfoCallback = Box::new(move |info| tx.send(info.score).unwrap());
+
+        let limits = GoLimits {
+            depth: Some(2),
+            ..Default::default()
+        };
+        let handle = spawn_search(engine, 1, request(limits), Some(on_info), |_| {}).unwrap();
+        let outcome = handle.wait();
+        let scores: Vec<_> = rx.try_iter().collect();
+        assert_eq!(scores.len(), 2);
+        assert!(outcome.score.is_some());
+        assert_eq!(outcome.score, scores[1]);
+    }
+
+    #[test]
+    fn test_search_errors_are_reported() {
+        let engine: SharedEngine = Arc::new(Mutex::new(None));
+        assert!(spawn_search(engine, 1, request(GoLimits::default()), None, |_| {}).is_err());
+
+        let engine = shared(Box::new(MockEngine::new()));
+        let invalid = request(GoLimits {
+            depth: Some(3),
+            infinite: true,
+            ..Default::default()
+        });
+        assert!(spawn_search(engine.clone(), 1, invalid, None, |_| {}).is_err());
+
+        let mut bad = request(GoLimits::default());
+        bad.moves = vec!["1a1b".to_string()];
+        let outcome = spawn_search(engine, 2, bad, None, |_| {}).unwrap().wait();
+        assert!(outcome.error.is_some());
+        assert!(outcome.into_result().is_err());
+    }
+
+    #[test]
+    fn test_wait_timeout_on_running_search() {
+        let engine = shared(Box::new(NativeEngine::new()));
+        let handle =
+            spawn_search(engine, 1, request(GoLimits::byoyomi(60_000)), None, |_| {}).unwrap();
+        assert!(handle.wait_timeout(Duration::from_millis(20)).is_none());
+        assert!(handle.stop_and_wait(Duration::from_secs(5)).is_some());
+    }
+
+    /// Engine that ponders until "ponderhit" or "stop" and always answers 2g2f
+    fn ponder_engine() -> SharedEngine {
+        shared(Box::new(
+            ScriptedEngine::new().answering("2g2f").pondering(),
+        ))
+    }
+
+    fn ponder_request() -> SearchRequest {
+        let mut request = request(GoLimits {
+            ponder: true,
+            ..GoLimits::byoyomi(1000)
+        });
+        request.moves.push("3c3d".to_string());
+        request
+    }
+
+    #[test]
+    fn test_ponderhit_charges_time_from_the_hit() {
+        let engine = ponder_engine();
+        let request = ponder_request();
+        let handle = spawn_search(engine, 1, request.clone(), None, |_| {}).unwrap();
+
+        assert!(handle.is_pondering());
+        assert!(handle.ponders_on(&request.sfen, &request.moves));
+        assert!(!handle.ponders_on(&request.sfen, &request.moves[..1]));
+
+        thread::sleep(Duration::from_millis(300));
+        handle.ponderhit().unwrap();
+        assert!(!handle.is_pondering());
+
+        let outcome = handle.wait();
+        assert_eq!(outcome.best_move, Some(BestMove::Move("2g2f".to_string())));
+        assert!(!outcome.pondering);
+        assert!(outcome.time_ms < 300);
+    }
+
+    #[test]
+    fn test_stopped_ponder_search_is_marked() {
+        let engine = ponder_engine();
+        let handle = spawn_search(engine.clone(), 1, ponder_request(), None, |_| {}).unwrap();
+
+        let outcome = handle.stop_and_wait(Duration::from_secs(5)).unwrap();
+        assert!(outcome.pondering);
+        assert!(handle.ponderhit().is_err());
+
+        // Normal searches cannot be hit
+        let handle = spawn_search(engine, 2, request(GoLimits::byoyomi(10)), None, |_| {}).unwrap();
+        assert!(!handle.is_pondering());
+        assert!(!handle.wait().pondering);
+    }
+
+    #[test]
+    fn test_illegal_engine_move_is_an_error() {
+        // The engine always answers 2g2f, which gote cannot play after 7g7f
+        let engine = shared(Box::new(ScriptedEngine::new().answering("2g2f")));
+        let outcome = spawn_search(engine, 1, request(GoLimits::byoyomi(10)), None, |_| {})
+            .unwrap()
+            .wait();
+        assert_eq!(outcome.best_move, None);
+        assert_eq!(
+            outcome.into_result(),
+            Err(SearchError::IllegalMove {
+                usi: "2g2f".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,181 @@
+// Common interface for all engine backends (external USI process, native, mock)
+
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::commands::{is_startpos, GoLimits, MateLimit};
+use super::error::UsiError;
+use super::options::EngineInfo;
+use super::parser::{BestMove, ThinkingInfo, TsumeResult};
+use crate::shogi::{DeclarationRule, Move, Position};
+
+/// Callback receiving thinking info while the engine searches
+pub type InfoCallback = Box<dyn FnMut(&ThinkingInfo) + Send>;
+
+/// Thread-safe signal to an engine whose `go` is running on another thread
+type Signal = Arc<dyn Fn() -> Result<(), UsiError> + Send + Sync>;
+
+/// Thread-safe way to interrupt a running `go` while the engine itself is busy,
+/// or to tell a pondering `go` that the expected move was played
+#[derive(Clone)]
+pub struct StopHandle {
+    stop: Signal,
+    ponderhit: Signal,
+}
+
+impl StopHandle {
+    pub fn new<F: Fn() -> Result<(), UsiError> + Send + Sync + 'static>(stop: F) -> Self {
+        StopHandle {
+            stop: Arc::new(stop),
+            ponderhit: Arc::new(|| Err("Engine does not support pondering".into())),
+        }
+    }
+
+    /// Also handle "ponderhit" for engines that can ponder
+    pub fn with_ponderhit<F: Fn() -> Result<(), UsiError> + Send + Sync + 'static>(
+        mut self,
+        ponderhit: F,
+    ) -> Self {
+        self.ponderhit = Arc::new(ponderhit);
+        self
+    }
+
+    /// Handle for engines whose search cannot be interrupted
+    pub fn noop() -> Self {
+        Self::new(|| Ok(()))
+    }
+
+    /// Ask the engine to finish its search as soon as possible
+    pub fn stop(&self) -> Result<(), UsiError> {
+        (self.stop)()
+    }
+
+    /// Turn a pondering search into a normal one
+    pub fn ponderhit(&self) -> Result<(), UsiError> {
+        (self.ponderhit)()
+    }
+}
+
+/// Result of a finished search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoResult {
+    /// Best move, resignation or win declaration, as the engine sent it
+    pub best_move: BestMove,
+    /// Move the engine expects the opponent to reply with
+    pub ponder: Option<String>,
+}
+
+/// How an engine ended when it was asked to quit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ShutdownOutcome {
+    /// The engine quit on its own; `code` is the process exit code, if any
+    Exited { code: Option<i32> },
+    /// The engine was still running at the deadline and was killed
+    Killed,
+    /// There was nothing to shut down
+    NotRunning,
+}
+
+/// Operations shared by every engine backend, mirroring the USI commands
+pub trait ShogiEngine: Send {
+    /// Handshake with the engine ("usi" / "isready"), returning its identity and options
+    fn init(&mut self) -> Result<EngineInfo, UsiError>;
+
+    /// Wait until the engine has finished applying options ("isready" / "readyok")
+    fn wait_ready(&mut self) -> Result<(), UsiError>;
+
+    /// Start a new game ("usinewgame")
+    fn new_game(&mut self) -> Result<(), UsiError>;
+
+    /// Set an engine option ("setoption name <name> [value <value>]");
+    /// buttons take no value
+    fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), UsiError>;
+
+    /// Set the position to search: a starting SFEN followed by USI moves
+    fn set_position(&mut self, sfen: &str, moves: &[String]) -> Result<(), UsiError>;
+
+    /// Set the declaration rule of the game; "bestmove win" must meet it
+    fn set_declaration_rule(&mut self, rule: DeclarationRule);
+
+    /// Search the current position and wait for the best move
+    fn go(&mut self, limits: &GoLimits) -> Result<GoResult, UsiError>;
+
+    /// Search the current position for a forced mate ("go mate") and wait for the answer
+    /// `go` does not accept `GoLimits::mate`
+    fn go_mate(&mut self, limit: MateLimit) -> Result<TsumeResult, UsiError>;
+
+    /// Receive thinking info (depth, score, PV) during `go`; None disables it
+    fn set_info_callback(&mut self, callback: Option<InfoCallback>);
+
+    /// Stop thinking
+    fn stop(&mut self) -> Result<(), UsiError>;
+
+    /// Handle that stops a running `go` (or sends "ponderhit") from another thread
+    fn stop_handle(&self) -> StopHandle;
+
+    /// Whether `go` accepts `GoLimits::ponder`
+    fn can_ponder(&self) -> bool;
+
+    /// The opponent played the expected ponder move
+    fn ponderhit(&mut self) -> Result<(), UsiError>;
+
+    /// Quit the engine, giving it up to `timeout` to exit on its own
+    fn quit(&mut self, timeout: Duration) -> Result<ShutdownOutcome, UsiError>;
+
+    /// Check if the engine is initialized and able to search
+    fn is_ready(&self) -> bool;
+}
+
+/// Build the position reached from `sfen` (or "startpos") after playing `moves`,
+/// rejecting malformed SFEN and illegal moves
+pub fn position_after_moves(sfen: &str, moves: &[String]) -> Result<Position, String> {
+    let mut pos = if is_startpos(sfen) {
+        Position::startpos()
+    } else {
+        Position::from_sfen(sfen).map_err(|e| e.to_string())?
+    };
+    for usi in moves {
+        let mv = Move::from_usi(usi)?;
+        if !pos.is_legal(mv) {
+            return Err(format!("Illegal move in move list: {}", usi));
+        }
+        pos.do_move(mv);
+    }
+    Ok(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shogi::STARTPOS_SFEN;
+
+    fn moves(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_position_after_moves() {
+        let pos = position_after_moves(STARTPOS_SFEN, &moves(&["7g7f", "3c3d", "8h2b+"])).unwrap();
+        assert_eq!(
+            pos.to_sfen(),
+            "lnsgkgsnl/1r5+B1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/7R1/LNSGKGSNL w B 4"
+        );
+    }
+
+    #[test]
+    fn test_position_after_moves_from_startpos_keyword() {
+        let a = position_after_moves("startpos", &moves(&["2g2f"])).unwrap();
+        let b = position_after_moves(STARTPOS_SFEN, &moves(&["2g2f"])).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_position_after_moves_rejects_illegal_move() {
+        let err = position_after_moves(STARTPOS_SFEN, &moves(&["7g7f", "7g7f"])).unwrap_err();
+        assert_eq!(err, "Illegal move in move list: 7g7f");
+        assert!(position_after_moves(STARTPOS_SFEN, &moves(&["xx"])).is_err());
+        assert!(position_after_moves("bad", &[]).is_err());
+    }
+}
@@ -5,6 +5,7 @@ import { GameControls } from './components/GameControls/GameControls';
 import { GameOverDialog } from './components/Dialogs/GameOverDialog';
 import { ModeSelectDialog } from './components/Dialogs/ModeSelectDialog';
 import { useGame } from './hooks/useGame';
+import { isGameOver } from './store/gameStore';
 import { initEngine } from './services/aiService';
 import type { GameMode, AILevel } from './logic/types';
 
@@ -30,7 +31,7 @@ function App() {
 
   // Show game over dialog when game ends
   useEffect(() => {
-    if (gameStatus === 'checkmate' || gameStatus === 'resigned') {
+    if (isGameOver(gameStatus)) {
       setShowGameOver(true);
     } else {
       setShowGameOver(false);
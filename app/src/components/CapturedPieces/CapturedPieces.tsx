@@ -1,5 +1,6 @@
 import type { Player, PieceType } from '../../logic/types';
 import { useGame } from '../../hooks/useGame';
+import { isGameOver as isOver } from '../../store/gameStore';
 
 interface CapturedPiecesProps {
   player: Player;
@@ -33,7 +34,7 @@ export const CapturedPieces = ({ player }: CapturedPiecesProps) => {
 
   const pieces = player === 'sente' ? capturedPieces.sente : capturedPieces.gote;
   const isMyTurn = currentPlayer === player;
-  const isGameOver = gameStatus === 'checkmate' || gameStatus === 'resigned';
+  const isGameOver = isOver(gameStatus);
 
   // Group pieces by type and count them
   const pieceCounts = pieces.reduce((acc, piece) => {
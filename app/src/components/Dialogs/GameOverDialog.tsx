@@ -24,6 +24,18 @@ export const GameOverDialog = ({
     if (gameStatus === 'resigned' && winner) {
       return `${winner === 'sente' ? '先手' : '後手'}の勝利！（投了）`;
     }
+    if (gameStatus === 'declared' && winner) {
+      return `${winner === 'sente' ? '先手' : '後手'}の勝利！（入玉宣言）`;
+    }
+    if (gameStatus === 'illegal' && winner) {
+      return `${winner === 'sente' ? '先手' : '後手'}の勝利！（反則）`;
+    }
+    if (gameStatus === 'timeUp' && winner) {
+      return `${winner === 'sente' ? '先手' : '後手'}の勝利！（時間切れ）`;
+    }
+    if (gameStatus === 'adjudicated') {
+      return winner ? `${winner === 'sente' ? '先手' : '後手'}の勝利！（判定）` : '引き分け（判定）';
+    }
     if (gameStatus === 'stalemate') {
       return '引き分け（ステイルメイト）';
     }
@@ -40,6 +52,15 @@ export const GameOverDialog = ({
     if (gameStatus === 'resigned') {
       return '相手が投了しました。';
     }
+    if (gameStatus === 'declared') {
+      return '入玉宣言による勝ちです。';
+    }
+    if (gameStatus === 'illegal') {
+      return '相手が反則手を指しました。';
+    }
+    if (gameStatus === 'timeUp') {
+      return '持ち時間が切れました。';
+    }
     return '';
   };
 
@@ -1,13 +1,15 @@
 import { useGame } from '../../hooks/useGame';
+import { isGameOver as isOver } from '../../store/gameStore';
 
 interface GameControlsProps {
   onNewGame: () => void;
 }
 
 export const GameControls = ({ onNewGame }: GameControlsProps) => {
-  const { currentPlayer, gameStatus, gameMode, isAIThinking, resign } = useGame();
+  const { currentPlayer, gameStatus, gameMode, isAIThinking, moveHistory, resign, undo } = useGame();
 
-  const isGameOver = gameStatus === 'checkmate' || gameStatus === 'resigned';
+  const isGameOver = isOver(gameStatus);
+  const canUndo = !isAIThinking && moveHistory.length > 0;
 
   const getTurnDisplay = () => {
     if (gameStatus === 'checkmate') {
@@ -16,6 +18,21 @@ export const GameControls = ({ onNewGame }: GameControlsProps) => {
     if (gameStatus === 'resigned') {
       return '投了';
     }
+    if (gameStatus === 'declared') {
+      return '入玉宣言';
+    }
+    if (gameStatus === 'illegal') {
+      return '反則負け';
+    }
+    if (gameStatus === 'repetition') {
+      return '千日手';
+    }
+    if (gameStatus === 'timeUp') {
+      return '時間切れ';
+    }
+    if (gameStatus === 'adjudicated') {
+      return '判定';
+    }
     if (isAIThinking) {
       return 'AIが思考中...';
     }
@@ -70,6 +87,20 @@ export const GameControls = ({ onNewGame }: GameControlsProps) => {
         >
           投了
         </button>
+
+        <button
+          onClick={undo}
+          disabled={!canUndo}
+          className={`
+            px-6 py-2 font-semibold rounded-lg shadow transition-colors
+            ${canUndo
+              ? 'bg-gray-600 hover:bg-gray-700 text-white'
+              : 'bg-gray-300 text-gray-500 cursor-not-allowed'
+            }
+          `}
+        >
+          待った
+        </button>
       </div>
     </div>
   );
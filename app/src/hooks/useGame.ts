@@ -30,6 +30,7 @@ export const useGame = () => {
     makeMove: store.makeMove,
     resign: store.resign,
     newGame: store.newGame,
+    undo: store.undo,
     clearSelection: store.clearSelection,
     requestAIMove: store.requestAIMove,
 
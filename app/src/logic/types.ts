@@ -55,7 +55,11 @@ export type GameStatus =
   | 'checkmate'  // 詰み
   | 'stalemate'  // ステイルメイト(将棋では稀)
   | 'repetition' // 千日手
-  | 'resigned';  // 投了
+  | 'resigned'   // 投了
+  | 'declared'   // 入玉宣言
+  | 'illegal'    // 反則負け
+  | 'timeUp'     // 時間切れ
+  | 'adjudicated'; // 判定(手数制限・持将棋・評価値)
 
 // ゲームモード
 export type GameMode = 'pvp' | 'pve';
@@ -76,8 +80,7 @@ export interface GameState {
   lastMove: Move | null;
   gameMode: GameMode;
   aiLevel: AILevel;
-  moveHistory: Move[];
-  positionHistory: string[]; // SFEN strings for repetition detection
+  moveHistory: string[]; // USI moves of the backend game, which also detects repetition
   isAIThinking?: boolean; // Optional for backwards compatibility
 }
 
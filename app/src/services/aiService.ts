@@ -1,49 +1,373 @@
 // AI Service - Wrapper for Tauri USI engine commands
 
 import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+
+/**
+ * Engine evaluation from the side to move's point of view
+ * mate: plies to mate (negative when being mated); mateUnknown: true when winning
+ */
+export type Score =
+  | { type: 'cp'; value: number }
+  | { type: 'mate'; value: number }
+  | { type: 'mateUnknown'; value: boolean };
+
+/**
+ * Thinking info reported by the engine while it searches
+ */
+export interface ThinkingInfo {
+  depth: number | null;
+  seldepth: number | null;
+  score: Score | null;
+  bound: 'exact' | 'lower' | 'upper';
+  multipv: number | null;
+  nodes: number | null;
+  nps: number | null;
+  hashfull: number | null;
+  time: number | null;
+  currmove: string | null;
+  string: string | null;
+  pv: string[];
+}
+
+/**
+ * Payload of the "engine-info" event
+ */
+export interface EngineInfoEvent {
+  /** Increases with every search; ignore events from older searches */
+  searchId: number;
+  info: ThinkingInfo;
+}
+
+/**
+ * Move selection strategy of the built-in mock engine
+ */
+export type MockStrategy =
+  | { type: 'random'; seed: number }
+  | { type: 'captureFirst'; seed: number }
+  | { type: 'greedy'; seed: number };
+
+/**
+ * Option declared by the engine during the USI handshake
+ */
+export type UsiOptionDecl = { name: string } & (
+  | { type: 'check'; default: boolean | null }
+  | { type: 'spin'; default: number | null; min: number | null; max: number | null }
+  | { type: 'combo'; default: string | null; vars: string[] }
+  | { type: 'string'; default: string | null }
+  | { type: 'button' }
+  | { type: 'filename'; default: string | null }
+);
+
+/**
+ * Engine identity and options reported by the handshake
+ */
+export interface EngineInfo {
+  name: string | null;
+  author: string | null;
+  options: UsiOptionDecl[];
+}
+
+/**
+ * Engine implementation used by the backend
+ */
+export type EngineBackend = 'usi' | 'native' | 'mock';
+
+/**
+ * Why talking to the engine failed
+ * processExited: code is null when the process was killed by a signal;
+ * rejected: the request never reached the engine (unknown option, bad limits or position)
+ */
+export type UsiError =
+  | { type: 'spawnFailed'; reason: string }
+  | { type: 'handshakeTimeout'; reply: string }
+  | { type: 'searchTimeout' }
+  | { type: 'processExited'; code: number | null; lastStderr: string[] }
+  | { type: 'protocolViolation'; detail: string }
+  | { type: 'notStarted' }
+  | { type: 'io'; reason: string }
+  | { type: 'rejected'; reason: string };
+
+/**
+ * Error payload of the engine commands
+ * usiError is null for failures that are not the engine's, such as an unreadable options file
+ */
+export interface EngineErrorPayload {
+  message: string;
+  usiError: UsiError | null;
+}
+
+/**
+ * Thrown when an engine command fails
+ */
+export class EngineError extends Error {
+  constructor(
+    message: string,
+    public readonly usiError: UsiError | null
+  ) {
+    super(message);
+    this.name = 'EngineError';
+  }
+}
+
+/**
+ * Turn an engine command error from the backend into an Error
+ */
+export function engineError(error: unknown, context: string): Error {
+  const payload = error as EngineErrorPayload;
+  if (typeof payload?.message === 'string') {
+    return new EngineError(`${context}: ${payload.message}`, payload.usiError ?? null);
+  }
+  return new Error(`${context}: ${error}`);
+}
 
 /**
  * Initialize the AI engine
- * @param enginePath Optional path to engine binary (not used for mock engine)
+ * @param enginePath Optional path to a USI engine binary
+ * @param mockStrategy Optional move selection strategy for the mock engine
+ * @param backend Optional backend; defaults to 'usi' with a path and 'mock' without
+ * @param maxRestarts Optional number of times a crashed USI engine may be restarted mid-game
  * @returns Success message
  */
-export async function initEngine(enginePath?: string): Promise<string> {
+export async function initEngine(
+  enginePath?: string,
+  mockStrategy?: MockStrategy,
+  backend?: EngineBackend,
+  maxRestarts?: number
+): Promise<string> {
   try {
-    const result = await invoke<string>('init_engine', { enginePath });
+    const result = await invoke<string>('init_engine', {
+      enginePath,
+      backend,
+      mockStrategy,
+      maxRestarts,
+    });
     return result;
   } catch (error) {
-    throw new Error(`Failed to initialize engine: ${error}`);
+    throw engineError(error, 'Failed to initialize engine');
+  }
+}
+
+/**
+ * The engine's answer, checked against the searched position
+ * move: a legal move in USI notation; resign: the engine gives up;
+ * win: the engine declares a win by entering king
+ */
+export type BestMove =
+  | { type: 'move'; usi: string }
+  | { type: 'resign' }
+  | { type: 'win' };
+
+/**
+ * Why a search produced no move
+ * illegalMove: the engine played an illegal move and loses under tournament rules
+ */
+export type SearchError =
+  | { type: 'illegalMove'; usi: string }
+  | ({ type: 'failed' } & EngineErrorPayload);
+
+/**
+ * Thrown when the engine answers with an illegal move
+ */
+export class IllegalEngineMoveError extends Error {
+  constructor(public readonly usi: string) {
+    super(`Engine made an illegal move: ${usi}`);
+    this.name = 'IllegalEngineMoveError';
+  }
+}
+
+/**
+ * Turn a search error from the backend into an Error
+ */
+function searchError(error: unknown, context: string): Error {
+  const payload = error as SearchError;
+  if (payload?.type === 'illegalMove') {
+    return new IllegalEngineMoveError(payload.usi);
   }
+  return engineError(error, context);
 }
 
 /**
  * Get AI move for a given position
  * @param sfen SFEN string representing the current position
  * @param timeMs Time limit in milliseconds
- * @returns USI move string (e.g., "7g7f" or "P*5e")
+ * @returns The engine's move, resignation or win declaration
+ * @throws IllegalEngineMoveError when the engine plays an illegal move
  */
-export async function getAIMove(sfen: string, timeMs: number): Promise<string> {
+export async function getAIMove(sfen: string, timeMs: number): Promise<BestMove> {
   try {
-    const result = await invoke<string>('get_ai_move', {
+    const result = await invoke<BestMove>('get_ai_move', {
       sfen,
       timeMs,
     });
     return result;
   } catch (error) {
-    throw new Error(`Failed to get AI move: ${error}`);
+    throw searchError(error, 'Failed to get AI move');
   }
 }
 
+/**
+ * Let the engine think on its expected reply during the opponent's turn, in the
+ * current game and the following ones
+ * @param ponder Whether the engine ponders
+ */
+export async function setEnginePonder(ponder: boolean): Promise<void> {
+  try {
+    await invoke('set_engine_ponder', { ponder });
+  } catch (error) {
+    throw engineError(error, 'Failed to set pondering');
+  }
+}
+
+/**
+ * Final result of a background search
+ */
+export interface SearchOutcome {
+  searchId: number;
+  bestMove: BestMove | null;
+  ponder: string | null;
+  error: SearchError | null;
+  /** Last score the engine reported for its main line, from its own point of view */
+  score: Score | null;
+  /** Thinking time charged to the engine; pondering before a ponderhit is not counted */
+  timeMs: number;
+  /** A ponder search that was stopped because another move was played */
+  pondering: boolean;
+}
+
+/**
+ * Start searching the game's current position in the background and return immediately
+ * The result arrives through onBestMove, waitSearch or stopSearch; it is not played
+ * @param timeMs Time per move in milliseconds when the game is untimed
+ * @returns Id of the search, also carried by its info and bestmove events
+ */
+export async function startSearch(timeMs: number): Promise<number> {
+  try {
+    return await invoke<number>('start_search', { timeMs });
+  } catch (error) {
+    throw engineError(error, 'Failed to start search');
+  }
+}
+
+/**
+ * Wait for a background search to finish
+ * @param searchId Id returned by startSearch
+ */
+export async function waitSearch(searchId: number): Promise<SearchOutcome> {
+  try {
+    return await invoke<SearchOutcome>('wait_search', { searchId });
+  } catch (error) {
+    throw engineError(error, 'Failed to wait for search');
+  }
+}
+
+/**
+ * Interrupt the running search and get the move the engine settled on
+ * @returns Outcome of the stopped search, or null if none was started
+ */
+export async function stopSearch(): Promise<SearchOutcome | null> {
+  try {
+    return await invoke<SearchOutcome | null>('stop_search');
+  } catch (error) {
+    throw engineError(error, 'Failed to stop search');
+  }
+}
+
+/**
+ * Subscribe to the results of background searches
+ * @param callback Called once per finished search
+ * @returns Function that removes the listener
+ */
+export async function onBestMove(
+  callback: (outcome: SearchOutcome) => void
+): Promise<UnlistenFn> {
+  return listen<SearchOutcome>('engine-bestmove', (event) => callback(event.payload));
+}
+
+/**
+ * Answer to a tsume (mating problem) search
+ * mate: the mating line in USI notation
+ */
+export type TsumeResult =
+  | { type: 'mate'; moves: string[] }
+  | { type: 'noMate' }
+  | { type: 'timeout' }
+  | { type: 'notImplemented' };
+
+/**
+ * Solve a mating problem with the current engine ("go mate")
+ * @param sfen SFEN string of the problem; the attacker is to move
+ * @param timeMs Time limit in milliseconds
+ * @returns The mating line, or why none was found
+ */
+export async function solveTsume(sfen: string, timeMs: number): Promise<TsumeResult> {
+  try {
+    const result = await invoke<TsumeResult>('solve_tsume', { sfen, timeMs });
+    return result;
+  } catch (error) {
+    throw engineError(error, 'Failed to solve tsume');
+  }
+}
+
+/**
+ * Answer of the built-in df-pn tsume solver
+ * mate: the shortest mating line in USI notation
+ * noMateWithin: no mate in that many plies or fewer; a longer one may exist
+ * unknown: the node budget ran out first
+ */
+export type NativeTsumeAnswer =
+  | { type: 'mate'; moves: string[] }
+  | { type: 'noMate' }
+  | { type: 'noMateWithin'; plies: number }
+  | { type: 'unknown' };
+
+/**
+ * Result of the built-in tsume solver
+ */
+export interface TsumeReport {
+  answer: NativeTsumeAnswer;
+  /** Positions the solver expanded */
+  nodes: number;
+}
+
+/**
+ * Solve a mating problem with the built-in solver, independently of the current engine
+ * @param sfen SFEN string of the problem; the attacker is to move
+ * @param maxNodes Optional node budget (default 1,000,000)
+ * @param maxPlies Optional longest mate to look for, in plies (default 31)
+ * @returns The shortest mating line, or why none was found
+ */
+export async function solveTsumeNative(
+  sfen: string,
+  maxNodes?: number,
+  maxPlies?: number
+): Promise<TsumeReport> {
+  try {
+    return await invoke<TsumeReport>('solve_tsume_native', { sfen, maxNodes, maxPlies });
+  } catch (error) {
+    throw new Error(`Failed to solve tsume: ${error}`);
+  }
+}
+
+/**
+ * How the engine ended when it was shut down
+ */
+export type ShutdownOutcome =
+  | { type: 'exited'; code: number | null }
+  | { type: 'killed' }
+  | { type: 'notRunning' };
+
 /**
  * Shutdown the AI engine
- * @returns Success message
+ * @param timeoutMs Optional time the engine gets to quit before it is killed (default 5000)
+ * @returns How the engine ended
  */
-export async function shutdownEngine(): Promise<string> {
+export async function shutdownEngine(timeoutMs?: number): Promise<ShutdownOutcome> {
   try {
-    const result = await invoke<string>('shutdown_engine');
+    const result = await invoke<ShutdownOutcome>('shutdown_engine', { timeoutMs });
     return result;
   } catch (error) {
-    throw new Error(`Failed to shutdown engine: ${error}`);
+    throw engineError(error, 'Failed to shutdown engine');
   }
 }
 
@@ -61,6 +385,52 @@ export async function isEngineReady(): Promise<boolean> {
   }
 }
 
+/**
+ * Get the name, author and declared options of the running engine
+ * @returns Engine info from the USI handshake
+ */
+export async function getEngineInfo(): Promise<EngineInfo> {
+  try {
+    return await invoke<EngineInfo>('get_engine_info');
+  } catch (error) {
+    throw engineError(error, 'Failed to get engine info');
+  }
+}
+
+/**
+ * Saved value of an engine option
+ */
+export interface SavedOption {
+  name: string;
+  value: string;
+}
+
+/**
+ * Set an engine option; the value is checked against the engine's declaration
+ * and saved for the next time this engine starts
+ * @param name Option name as declared by the engine
+ * @param value Option value; omit for button options
+ */
+export async function setEngineOption(name: string, value?: string): Promise<void> {
+  try {
+    await invoke('set_engine_option', { name, value });
+  } catch (error) {
+    throw engineError(error, 'Failed to set engine option');
+  }
+}
+
+/**
+ * Get the option values saved for the running engine
+ * @returns Saved options in the order they were first set
+ */
+export async function getSavedEngineOptions(): Promise<SavedOption[]> {
+  try {
+    return await invoke<SavedOption[]>('get_saved_engine_options');
+  } catch (error) {
+    throw engineError(error, 'Failed to get saved engine options');
+  }
+}
+
 /**
  * Get time limit in milliseconds based on AI difficulty level
  * @param level AI difficulty level
@@ -78,3 +448,14 @@ export function getTimeForLevel(level: 'easy' | 'medium' | 'hard'): number {
       return 2000;
   }
 }
+
+/**
+ * Subscribe to the engine's thinking info stream
+ * @param callback Called for every info line of every search
+ * @returns Function that removes the listener
+ */
+export async function onEngineInfo(
+  callback: (event: EngineInfoEvent) => void
+): Promise<UnlistenFn> {
+  return listen<EngineInfoEvent>('engine-info', (event) => callback(event.payload));
+}
@@ -0,0 +1,336 @@
+// Game Service - Wrapper for Tauri game session commands
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { CapturedPieces, PieceType, Player } from '../logic/types';
+import { engineError, type Score } from './aiService';
+
+/**
+ * Time control of a timed game
+ * byoyomi: time per move once the main time is used up; increment: Fischer bonus
+ */
+export interface TimeControl {
+  mainMs: number;
+  byoyomiMs?: number;
+  incrementMs?: number;
+}
+
+/**
+ * Entering-king declaration rule
+ * points27: CSA rule, 28 points for sente and 27 for gote
+ * points24: 31 points win and 24 to 30 points draw
+ */
+export type DeclarationRule = 'points27' | 'points24';
+
+/**
+ * Adjudicate a loss once both engines' scores pass scoreCp for their last
+ * `moves` moves each
+ */
+export interface ResignThreshold {
+  scoreCp: number;
+  moves: number;
+}
+
+/**
+ * Adjudicate a draw once both engines' scores stay within scoreCp for their
+ * last `moves` moves each, from ply afterPly on
+ */
+export interface DrawThreshold {
+  afterPly: number;
+  scoreCp: number;
+  moves: number;
+}
+
+/**
+ * Rules chosen for a game
+ * maxPlies: draw at this many plies (floodgate uses 256)
+ * impassePlies: count points once both kings have stayed entered this long
+ */
+export interface GameRules {
+  declaration?: DeclarationRule;
+  maxPlies?: number | null;
+  impassePlies?: number | null;
+  resign?: ResignThreshold | null;
+  draw?: DrawThreshold | null;
+}
+
+/**
+ * Main time left to each player at the start of the current turn
+ */
+export interface Clocks {
+  senteMs: number;
+  goteMs: number;
+}
+
+/**
+ * Who is playing and when the game started (Unix time in milliseconds)
+ */
+export interface GameInfo {
+  senteName?: string | null;
+  goteName?: string | null;
+  event?: string | null;
+  startedAt?: number;
+}
+
+export type EndReason =
+  | 'checkmate'
+  | 'resignation'
+  | 'timeUp'
+  | 'illegalMove'
+  | 'declaration'
+  | 'repetition'
+  | 'perpetualCheck'
+  | 'maxMoves'
+  | 'impasse'
+  | 'resignAdjudication'
+  | 'drawAdjudication';
+
+/**
+ * Final result of a game; winner is null for a draw
+ */
+export interface GameResult {
+  winner: Player | null;
+  reason: EndReason;
+}
+
+export interface MoveRecord {
+  usi: string;
+  player: Player;
+  captured: PieceType | null;
+  timeMs: number;
+  /** Engine's score for the move, from the mover's point of view */
+  score: Score | null;
+}
+
+/**
+ * State of the game as held by the backend
+ */
+export interface GameSnapshot {
+  initialSfen: string;
+  sfen: string;
+  sideToMove: Player;
+  inCheck: boolean;
+  hands: CapturedPieces;
+  moves: MoveRecord[];
+  timeControl: TimeControl | null;
+  rules: Required<GameRules>;
+  clocks: Clocks | null;
+  result: GameResult | null;
+  info: GameInfo;
+  /** Increases with every change to the game */
+  version: number;
+}
+
+/**
+ * Start a new game, replacing the current one
+ * @param initialSfen Starting position; the standard initial position when omitted
+ * @param timeControl Clock settings; the game is untimed when omitted
+ * @param rules Declaration rule and other settings; the 27-point rule when omitted
+ * @param info Player names and event
+ */
+export async function newGame(
+  initialSfen?: string,
+  timeControl?: TimeControl,
+  rules?: GameRules,
+  info?: GameInfo
+): Promise<GameSnapshot> {
+  try {
+    return await invoke<GameSnapshot>('new_game', { initialSfen, timeControl, rules, info });
+  } catch (error) {
+    throw engineError(error, 'Failed to start game');
+  }
+}
+
+/**
+ * Play a move for the side to move
+ * @param usi Move in USI notation
+ */
+export async function makeMove(usi: string): Promise<GameSnapshot> {
+  try {
+    return await invoke<GameSnapshot>('make_move', { usi });
+  } catch (error) {
+    throw new Error(`Failed to make move: ${error}`);
+  }
+}
+
+/**
+ * Take back the last move
+ */
+export async function undoMove(): Promise<GameSnapshot> {
+  try {
+    return await invoke<GameSnapshot>('undo_move');
+  } catch (error) {
+    throw new Error(`Failed to undo move: ${error}`);
+  }
+}
+
+/**
+ * Resign the game
+ * @param player Player who resigns; the side to move when omitted
+ */
+export async function resignGame(player?: Player): Promise<GameSnapshot> {
+  try {
+    return await invoke<GameSnapshot>('resign_game', { player });
+  } catch (error) {
+    throw new Error(`Failed to resign: ${error}`);
+  }
+}
+
+/**
+ * Declare a win by entering king for the side to move
+ * Under the 24-point rule the declaration may only earn a draw
+ * @throws Error when the position does not meet the game's declaration rule
+ */
+export async function declareWin(): Promise<GameSnapshot> {
+  try {
+    return await invoke<GameSnapshot>('declare_win');
+  } catch (error) {
+    throw new Error(`Failed to declare win: ${error}`);
+  }
+}
+
+/**
+ * Get the current state of the game
+ */
+export async function getGameState(): Promise<GameSnapshot> {
+  try {
+    return await invoke<GameSnapshot>('get_game_state');
+  } catch (error) {
+    throw new Error(`Failed to get game state: ${error}`);
+  }
+}
+
+/**
+ * Let the engine play the side to move
+ * Under the game's time control the engine is given both clocks
+ * An illegal answer from the engine loses the game for it
+ * @param timeMs Time per move in milliseconds when the game is untimed
+ */
+export async function playEngineMove(timeMs: number): Promise<GameSnapshot> {
+  try {
+    return await invoke<GameSnapshot>('play_engine_move', { timeMs });
+  } catch (error) {
+    throw engineError(error, 'Failed to play engine move');
+  }
+}
+
+/**
+ * Record file format
+ * kif: numbered moves with origin squares and times; ki2: moves as spoken, with 右左上引寄直
+ */
+export type KifuFormat = 'kif' | 'ki2';
+
+/**
+ * Text encoding of a record file
+ */
+export type TextEncoding = 'utf8' | 'shiftJis';
+
+/**
+ * How a recorded game ended (投了, 中断, 千日手, 持将棋, 切れ負け, 反則勝ち, 反則負け, 入玉勝ち, 詰み)
+ * Wins and losses are those of the side to move after the last move
+ */
+export type KifuEnd =
+  | 'resign'
+  | 'interrupt'
+  | 'repetition'
+  | 'impasse'
+  | 'timeUp'
+  | 'illegalWin'
+  | 'illegalLoss'
+  | 'declaration'
+  | 'checkmate';
+
+/**
+ * A recorded move; each variation is a line played instead of this move
+ */
+export interface KifuMove {
+  usi: string;
+  timeMs: number | null;
+  comments: string[];
+  variations: KifuMove[][];
+}
+
+/**
+ * A game record with its headers (such as ["先手", name]), comments and variations
+ */
+export interface Kifu {
+  headers: [string, string][];
+  initialSfen: string;
+  comments: string[];
+  moves: KifuMove[];
+  end: KifuEnd | null;
+}
+
+/**
+ * Save the game as a KIF or KI2 file
+ * @param path File path; .kif/.kifu or .ki2/.ki2u decide the format when it is omitted
+ * @param format Record format
+ * @param encoding UTF-8 for .kifu and .ki2u and Shift_JIS otherwise when omitted
+ */
+export async function saveKifu(
+  path: string,
+  format?: KifuFormat,
+  encoding?: TextEncoding
+): Promise<void> {
+  try {
+    await invoke('save_kifu', { path, format, encoding });
+  } catch (error) {
+    throw new Error(`Failed to save kifu: ${error}`);
+  }
+}
+
+/**
+ * Load a KIF or KI2 file in Shift_JIS or UTF-8 and replay its main line as the game
+ * @param path File path ending in .kif, .kifu, .ki2 or .ki2u
+ * @param rules Rules for the replayed game
+ * @returns The whole record, including comments and variations
+ */
+export async function loadKifu(path: string, rules?: GameRules): Promise<Kifu> {
+  try {
+    return await invoke<Kifu>('load_kifu', { path, rules });
+  } catch (error) {
+    throw engineError(error, 'Failed to load kifu');
+  }
+}
+
+/**
+ * Get the game as KIF or KI2 text
+ * @param format Record format
+ */
+export async function exportKifu(format: KifuFormat): Promise<string> {
+  try {
+    return await invoke<string>('export_kifu', { format });
+  } catch (error) {
+    throw new Error(`Failed to export kifu: ${error}`);
+  }
+}
+
+/**
+ * Replay KIF or KI2 text, such as pasted from the clipboard, as the game
+ * @param text Record text
+ * @param format Record format
+ * @param rules Rules for the replayed game
+ * @returns The whole record, including comments and variations
+ */
+export async function importKifu(
+  text: string,
+  format: KifuFormat,
+  rules?: GameRules
+): Promise<Kifu> {
+  try {
+    return await invoke<Kifu>('import_kifu', { text, format, rules });
+  } catch (error) {
+    throw engineError(error, 'Failed to import kifu');
+  }
+}
+
+/**
+ * Subscribe to changes of the game
+ * @param callback Called with the new state after every change
+ * @returns Function that removes the listener
+ */
+export async function onGameState(
+  callback: (snapshot: GameSnapshot) => void
+): Promise<UnlistenFn> {
+  return listen<GameSnapshot>('game-state', (event) => callback(event.payload));
+}
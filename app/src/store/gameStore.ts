@@ -1,18 +1,15 @@
 import { create } from 'zustand';
 import type {
   GameState,
+  GameStatus,
   Position,
   PieceType,
-  Player,
   Move,
   GameMode,
   AILevel,
-  CapturedPieces,
 } from '../logic/types';
 import {
   createInitialBoard,
-  applyMove,
-  getUnpromotedPiece,
   positionEquals,
 } from '../logic/board';
 import {
@@ -22,11 +19,18 @@ import {
 import {
   isValidMove,
 } from '../logic/validation';
+import { moveToUsi, sfenToBoard, usiToMove } from '../utils/sfen';
+import { getTimeForLevel } from '../services/aiService';
 import {
-  checkGameStatus,
-} from '../logic/judge';
-import { boardToSfen, usiToMove } from '../utils/sfen';
-import { getAIMove, getTimeForLevel } from '../services/aiService';
+  getGameState,
+  makeMove as playMove,
+  newGame as startGame,
+  onGameState,
+  playEngineMove,
+  resignGame,
+  undoMove,
+  type GameSnapshot,
+} from '../services/gameService';
 
 interface GameStore extends GameState {
   // AI state
@@ -35,13 +39,66 @@ interface GameStore extends GameState {
   // Actions
   selectSquare: (pos: Position) => void;
   selectCapturedPiece: (piece: PieceType | null) => void;
-  makeMove: (to: Position, promote?: boolean) => void;
-  resign: () => void;
-  newGame: (mode: GameMode, aiLevel?: AILevel) => void;
+  makeMove: (to: Position, promote?: boolean) => Promise<void>;
+  resign: () => Promise<void>;
+  newGame: (mode: GameMode, aiLevel?: AILevel) => Promise<void>;
+  undo: () => Promise<void>;
   clearSelection: () => void;
   requestAIMove: () => Promise<void>;
+  applySnapshot: (snapshot: GameSnapshot) => void;
 }
 
+/**
+ * Whether the game has ended with this status
+ */
+export const isGameOver = (status: GameStatus): boolean =>
+  status !== 'playing' && status !== 'check';
+
+/**
+ * Status of the backend game, from its result once it has ended
+ */
+const statusOf = (snapshot: GameSnapshot): GameStatus => {
+  if (!snapshot.result) {
+    return snapshot.inCheck ? 'check' : 'playing';
+  }
+  switch (snapshot.result.reason) {
+    case 'checkmate':
+      return 'checkmate';
+    case 'resignation':
+      return 'resigned';
+    case 'declaration':
+      return 'declared';
+    case 'illegalMove':
+    case 'perpetualCheck':
+      return 'illegal';
+    case 'repetition':
+      return 'repetition';
+    case 'timeUp':
+      return 'timeUp';
+    case 'maxMoves':
+    case 'impasse':
+    case 'resignAdjudication':
+    case 'drawAdjudication':
+      return 'adjudicated';
+  }
+};
+
+/**
+ * Last move of the backend game, for highlighting on the board
+ */
+const lastMoveOf = (snapshot: GameSnapshot, board: GameState['board']): Move | null => {
+  const record = snapshot.moves[snapshot.moves.length - 1];
+  const parsed = record && usiToMove(record.usi);
+  if (!parsed) return null;
+  // usiToMove only knows the piece of a drop; a moved piece now stands on `to`
+  const moved = parsed.from !== null ? board[parsed.to.row][parsed.to.col] : null;
+  return {
+    ...parsed,
+    piece: moved?.type ?? parsed.piece,
+    captured: record.captured ?? undefined,
+  };
+};
+
 const createInitialState = (): Omit<GameState, 'gameMode' | 'aiLevel'> => ({
   board: createInitialBoard(),
   capturedPieces: { sente: [], gote: [] },
@@ -53,7 +110,6 @@ const createInitialState = (): Omit<GameState, 'gameMode' | 'aiLevel'> => ({
   legalMoves: [],
   lastMove: null,
   moveHistory: [],
-  positionHistory: [],
 });
 
 export const useGameStore = create<GameStore>((set, get) => ({
@@ -65,8 +121,8 @@ export const useGameStore = create<GameStore>((set, get) => ({
   selectSquare: (pos: Position) => {
     const state = get();
 
-    // Game is over
-    if (state.gameStatus === 'checkmate' || state.gameStatus === 'resigned') {
+    // Game is over or the AI is to move
+    if (isGameOver(state.gameStatus) || state.isAIThinking) {
       return;
     }
 
@@ -130,8 +186,8 @@ export const useGameStore = create<GameStore>((set, get) => ({
   selectCapturedPiece: (piece: PieceType | null) => {
     const state = get();
 
-    // Game is over
-    if (state.gameStatus === 'checkmate' || state.gameStatus === 'resigned') {
+    // Game is over or the AI is to move
+    if (isGameOver(state.gameStatus) || state.isAIThinking) {
       return;
     }
 
@@ -181,7 +237,7 @@ export const useGameStore = create<GameStore>((set, get) => ({
     });
   },
 
-  makeMove: (to: Position, promote: boolean = false) => {
+  makeMove: async (to: Position, promote: boolean = false) => {
     const state = get();
 
     let move: Move;
@@ -200,105 +256,77 @@ export const useGameStore = create<GameStore>((set, get) => ({
       const piece = state.board[state.selectedSquare.row][state.selectedSquare.col];
       if (!piece) return;
 
-      const capturedPiece = state.board[to.row][to.col];
       move = {
         from: state.selectedSquare,
         to,
         piece: piece.type,
         promote,
-        captured: capturedPiece?.type,
       };
     } else {
       return;
     }
 
-    // Validate move
-    if (!isValidMove(state.board, move, state.currentPlayer, state.capturedPieces)) {
-      return;
-    }
+    set({ selectedSquare: null, selectedCapturedPiece: null, legalMoves: [] });
 
-    // Apply move
-    const newBoard = applyMove(state.board, move);
-
-    // Update captured pieces
-    const newCapturedPieces: CapturedPieces = {
-      sente: [...state.capturedPieces.sente],
-      gote: [...state.capturedPieces.gote],
-    };
-
-    if (move.from === null) {
-      // Remove dropped piece from captured
-      const playerCaptured = state.currentPlayer === 'sente'
-        ? newCapturedPieces.sente
-        : newCapturedPieces.gote;
-      const index = playerCaptured.indexOf(move.piece);
-      if (index !== -1) {
-        playerCaptured.splice(index, 1);
-      }
-    } else if (move.captured) {
-      // Add captured piece (unpromoted)
-      const unpromotedPiece = getUnpromotedPiece(move.captured);
-      if (state.currentPlayer === 'sente') {
-        newCapturedPieces.sente.push(unpromotedPiece);
-      } else {
-        newCapturedPieces.gote.push(unpromotedPiece);
+    // The backend game judges the move and broadcasts the new position
+    try {
+      const snapshot = await playMove(moveToUsi(move));
+
+      // If game is not over and it's PvE mode and AI's turn, request AI move
+      if (
+        state.gameMode === 'pve' &&
+        !snapshot.result &&
+        snapshot.sideToMove === 'gote' // AI is always 'gote' (second player)
+      ) {
+        // Request AI move asynchronously
+        setTimeout(() => {
+          get().requestAIMove();
+        }, 500); // Small delay for better UX
       }
+    } catch (error) {
+      console.error('Failed to make move:', error);
     }
+  },
 
-    // Switch player
-    const nextPlayer: Player = state.currentPlayer === 'sente' ? 'gote' : 'sente';
-
-    // Generate current SFEN for position history
-    const currentSfen = boardToSfen(newBoard, nextPlayer, newCapturedPieces, state.moveHistory.length + 1);
-
-    // Check game status
-    const gameStatus = checkGameStatus(newBoard, nextPlayer, state.positionHistory, newCapturedPieces, currentSfen);
-    const winner = gameStatus === 'checkmate' ? state.currentPlayer : null;
+  resign: async () => {
+    try {
+      await resignGame();
+    } catch (error) {
+      console.error('Failed to resign:', error);
+    }
+  },
 
-    // Update state
+  newGame: async (mode: GameMode, aiLevel: AILevel = 'medium') => {
     set({
-      board: newBoard,
-      capturedPieces: newCapturedPieces,
-      currentPlayer: nextPlayer,
-      gameStatus,
-      winner,
+      gameMode: mode,
+      aiLevel,
       selectedSquare: null,
       selectedCapturedPiece: null,
       legalMoves: [],
-      lastMove: move,
-      moveHistory: [...state.moveHistory, move],
-      positionHistory: [...state.positionHistory, currentSfen],
     });
-
-    // If game is not over and it's PvE mode and AI's turn, request AI move
-    const newState = get();
-    if (
-      newState.gameMode === 'pve' &&
-      newState.gameStatus === 'playing' &&
-      newState.currentPlayer === 'gote' // AI is always 'gote' (second player)
-    ) {
-      // Request AI move asynchronously
-      setTimeout(() => {
-        get().requestAIMove();
-      }, 500); // Small delay for better UX
+    try {
+      await startGame();
+    } catch (error) {
+      console.error('Failed to start game:', error);
     }
   },
 
-  resign: () => {
+  undo: async () => {
     const state = get();
-    const winner: Player = state.currentPlayer === 'sente' ? 'gote' : 'sente';
-    set({
-      gameStatus: 'resigned',
-      winner,
-    });
-  },
+    if (state.isAIThinking || state.moveHistory.length === 0) {
+      return;
+    }
 
-  newGame: (mode: GameMode, aiLevel: AILevel = 'medium') => {
-    set({
-      ...createInitialState(),
-      gameMode: mode,
-      aiLevel,
-    });
+    set({ selectedSquare: null, selectedCapturedPiece: null, legalMoves: [] });
+    try {
+      const snapshot = await undoMove();
+      // Against the AI, take back its reply too so the player is to move again
+      if (state.gameMode === 'pve' && snapshot.sideToMove === 'gote' && snapshot.moves.length > 0) {
+        await undoMove();
+      }
+    } catch (error) {
+      console.error('Failed to undo move:', error);
+    }
   },
 
   clearSelection: () => {
@@ -313,7 +341,7 @@ export const useGameStore = create<GameStore>((set, get) => ({
     const state = get();
 
     // Don't request if game is over or already thinking
-    if (state.gameStatus !== 'playing' || state.isAIThinking) {
+    if (isGameOver(state.gameStatus) || state.isAIThinking) {
       return;
     }
 
@@ -326,100 +354,39 @@ export const useGameStore = create<GameStore>((set, get) => ({
       // Set thinking state
       set({ isAIThinking: true });
 
-      // Get current position as SFEN
-      const sfen = boardToSfen(
-        state.board,
-        state.currentPlayer,
-        state.capturedPieces,
-        state.moveHistory.length + 1
-      );
-
-      // Get time limit based on difficulty
-      const timeMs = getTimeForLevel(state.aiLevel);
-
-      // Request AI move
-      const usiMove = await getAIMove(sfen, timeMs);
-
-      // Parse USI move
-      const parsedMove = usiToMove(usiMove);
-      if (!parsedMove) {
-        throw new Error('Invalid move from AI');
-      }
-
-      // Get the piece type from the board if it's a normal move
-      let piece = parsedMove.piece;
-      if (parsedMove.from !== null) {
-        const boardPiece = state.board[parsedMove.from.row][parsedMove.from.col];
-        if (boardPiece) {
-          piece = boardPiece.type;
-        }
-      }
-
-      // Create the move object
-      const capturedPiece = state.board[parsedMove.to.row][parsedMove.to.col];
-      const aiMove: Move = {
-        from: parsedMove.from,
-        to: parsedMove.to,
-        piece,
-        promote: parsedMove.promote,
-        captured: capturedPiece?.type,
-      };
-
-      // Validate and apply the move
-      if (!isValidMove(state.board, aiMove, state.currentPlayer, state.capturedPieces)) {
-        throw new Error('AI returned invalid move');
-      }
-
-      // Apply the move
-      const newBoard = applyMove(state.board, aiMove);
-
-      // Update captured pieces
-      const newCapturedPieces: CapturedPieces = {
-        sente: [...state.capturedPieces.sente],
-        gote: [...state.capturedPieces.gote],
-      };
-
-      if (aiMove.from === null) {
-        // Remove dropped piece from captured
-        const playerCaptured = newCapturedPieces.gote;
-        const index = playerCaptured.indexOf(aiMove.piece);
-        if (index !== -1) {
-          playerCaptured.splice(index, 1);
-        }
-      } else if (aiMove.captured) {
-        // Add captured piece (unpromoted)
-        const unpromotedPiece = getUnpromotedPiece(aiMove.captured);
-        newCapturedPieces.gote.push(unpromotedPiece);
-      }
-
-      // Switch to player's turn
-      const nextPlayer: Player = 'sente';
-
-      // Generate SFEN for position history
-      const currentSfen = boardToSfen(newBoard, nextPlayer, newCapturedPieces, state.moveHistory.length + 2);
-
-      // Check game status
-      const gameStatus = checkGameStatus(newBoard, nextPlayer, state.positionHistory, newCapturedPieces, currentSfen);
-      const winner = gameStatus === 'checkmate' ? state.currentPlayer : null;
-
-      // Update state
-      set({
-        board: newBoard,
-        capturedPieces: newCapturedPieces,
-        currentPlayer: nextPlayer,
-        gameStatus,
-        winner,
-        lastMove: aiMove,
-        moveHistory: [...state.moveHistory, aiMove],
-        positionHistory: [...state.positionHistory, currentSfen],
-        isAIThinking: false,
-      });
+      // The backend searches the game as it stands and plays the engine's answer,
+      // so resignations, declarations and illegal moves end the game there
+      await playEngineMove(getTimeForLevel(state.aiLevel));
     } catch (error) {
       console.error('Failed to get AI move:', error);
-      set({ isAIThinking: false });
 
       // Show error to user
       alert(`AI Error: ${error instanceof Error ? error.message : 'Unknown error'}`);
+    } finally {
+      set({ isAIThinking: false });
     }
   },
+
+  applySnapshot: (snapshot: GameSnapshot) => {
+    const { board } = sfenToBoard(snapshot.sfen);
+    set({
+      board,
+      capturedPieces: snapshot.hands,
+      currentPlayer: snapshot.sideToMove,
+      gameStatus: statusOf(snapshot),
+      winner: snapshot.result?.winner ?? null,
+      lastMove: lastMoveOf(snapshot, board),
+      moveHistory: snapshot.moves.map(record => record.usi),
+    });
+  },
 }));
+
+// The backend game is the single source of truth; the store shows every change to it
+onGameState((snapshot) => useGameStore.getState().applySnapshot(snapshot)).catch((error) => {
+  console.error('Failed to listen for game changes:', error);
+});
+getGameState()
+  .then((snapshot) => useGameStore.getState().applySnapshot(snapshot))
+  .catch((error) => {
+    console.error('Failed to get game state:', error);
+  });